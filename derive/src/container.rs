@@ -0,0 +1,105 @@
+//! The `#[arena(..)]` attribute on a derived type.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::Attribute;
+use syn::Expr;
+use syn::Ident;
+use syn::Path;
+use syn::Token;
+use syn::Type;
+use syn::parenthesized;
+use syn::parse::Parse;
+use syn::parse::ParseStream;
+use syn::punctuated::Punctuated;
+
+/// One arena of the tuple: the item it holds, and the width of its indices if
+/// it is not the default.
+pub struct ArenaEntry {
+    pub item: Path,
+    pub width: Option<Type>,
+}
+
+impl ArenaEntry {
+    /// Returns the name of the variant of the index sum for this arena.
+    pub fn variant(&self) -> syn::Result<&Ident> {
+        self.item
+            .segments
+            .last()
+            .map(|segment| &segment.ident)
+            .ok_or_else(|| syn::Error::new_spanned(&self.item, "empty path"))
+    }
+}
+
+impl Parse for ArenaEntry {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let item = input.parse()?;
+        let width = match input.parse::<Option<Token![:]>>()? {
+            Some(_) => Some(input.parse()?),
+            None => None,
+        };
+        Ok(Self { item, width })
+    }
+}
+
+/// What the `#[arena(..)]` attribute of a derived type says.
+pub struct Container {
+    /// The name of the index sum.
+    pub index: Ident,
+    /// The arenas of the tuple, if the index sum is generated for this type.
+    pub arenas: Option<Vec<ArenaEntry>>,
+    /// How many pending steps each traversal of this type can hold, if it is
+    /// not the default.
+    pub depth: Option<Expr>,
+}
+
+impl Container {
+    /// Reads the `#[arena(index = .., arenas(..), depth = ..)]` attribute
+    /// among `attrs`, where `arenas` and `depth` are optional.
+    pub fn parse(ident: &Ident, attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut index = None;
+        let mut arenas = None;
+        let mut depth = None;
+        for attr in attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("arena"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("index") {
+                    index = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("arenas") {
+                    let content;
+                    parenthesized!(content in meta.input);
+                    let entries =
+                        Punctuated::<ArenaEntry, Token![,]>::parse_terminated(
+                            &content,
+                        )?;
+                    arenas = Some(entries.into_iter().collect());
+                    Ok(())
+                } else if meta.path.is_ident("depth") {
+                    depth = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `index`, `arenas` or `depth`"))
+                }
+            })?;
+        }
+        let index = index.ok_or_else(|| {
+            syn::Error::new_spanned(
+                ident,
+                "expected `#[arena(index = ..)]` naming the index sum",
+            )
+        })?;
+        Ok(Self { index, arenas, depth })
+    }
+
+    /// Returns the capacity of the work stack of each traversal, as a const
+    /// generic argument.
+    pub fn depth(&self) -> TokenStream {
+        match &self.depth {
+            Some(depth) => quote!({ #depth }),
+            None => quote!({ crate::arena::work_stack::TRAVERSAL_DEPTH }),
+        }
+    }
+}
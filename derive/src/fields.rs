@@ -0,0 +1,179 @@
+//! The fields of a derived type, sorted by whether they point into the arenas.
+
+use proc_macro2::TokenStream;
+use quote::format_ident;
+use quote::quote;
+use syn::Data;
+use syn::Fields;
+use syn::GenericArgument;
+use syn::Ident;
+use syn::Member;
+use syn::Meta;
+use syn::PathArguments;
+use syn::Type;
+use syn::punctuated::Punctuated;
+use syn::token::Comma;
+
+/// How the traversals treat a field.
+pub enum Role {
+    /// An `Index<T, I>`, given as its arguments, which is followed into the
+    /// arena of `T`.
+    Index(Punctuated<GenericArgument, Comma>),
+    /// A value implementing `ArenaNode`, whose own indices are followed.
+    Node,
+    /// The number of owners pointing to the slot of an item of this variant,
+    /// marked `#[arena(owners)]`, which is left out of its shallow view.
+    Owners,
+    /// Anything else, which is taken as it is.
+    Plain,
+}
+
+/// A field of a derived type.
+pub struct Field {
+    pub member: Member,
+    pub binding: Ident,
+    pub ty: Type,
+    pub role: Role,
+}
+
+/// A variant of a derived enum, or the whole of a derived struct.
+pub struct Variant {
+    /// The name of the variant, or of the struct.
+    pub name: Ident,
+    /// The path to match on, such as `Self::Cat` or `Self`.
+    pub path: TokenStream,
+    pub fields: Vec<Field>,
+}
+
+impl Variant {
+    /// Returns a pattern matching this variant, binding each field for which
+    /// `bind` is true by reference, and ignoring the others.
+    pub fn pattern(&self, bind: impl Fn(&Role) -> bool) -> TokenStream {
+        self.pattern_as(bind, |field| field.binding.clone())
+    }
+
+    /// Returns a pattern as in [Variant::pattern], binding each field to the
+    /// name given by `binding` instead.
+    pub fn pattern_as(
+        &self,
+        bind: impl Fn(&Role) -> bool,
+        binding: impl Fn(&Field) -> Ident,
+    ) -> TokenStream {
+        let path = &self.path;
+        let fields = self.fields.iter().map(|field| {
+            let member = &field.member;
+            if bind(&field.role) {
+                let binding = binding(field);
+                quote!(#member: #binding)
+            } else {
+                quote!(#member: _)
+            }
+        });
+        quote!(#path { #(#fields,)* })
+    }
+
+    /// Returns the fields which point into the arenas, in order.
+    pub fn children(&self) -> impl Iterator<Item = &Field> {
+        self.fields
+            .iter()
+            .filter(|field| matches!(field.role, Role::Index(_) | Role::Node))
+    }
+
+    /// Returns the field counting the owners of this variant, if it has one.
+    pub fn owners(&self) -> Option<&Field> {
+        self.fields
+            .iter()
+            .find(|field| matches!(field.role, Role::Owners))
+    }
+}
+
+/// Returns the role of a field of type `ty` with the attributes `attrs`.
+fn role(ty: &Type, attrs: &[syn::Attribute]) -> syn::Result<Role> {
+    if let Some(attr) = attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("arena"))
+    {
+        if let Meta::Path(_) = &attr.meta {
+            return Ok(Role::Node);
+        }
+        let ident = attr.parse_args::<Ident>()?;
+        if ident != "owners" {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "expected `#[arena]` or `#[arena(owners)]`",
+            ));
+        }
+        return Ok(Role::Owners);
+    }
+    let Type::Path(path) = ty else {
+        return Ok(Role::Plain);
+    };
+    let Some(segment) = path.path.segments.last() else {
+        return Ok(Role::Plain);
+    };
+    if segment.ident == "Chain" {
+        return Ok(Role::Node);
+    }
+    match (&segment.arguments, segment.ident == "Index") {
+        (PathArguments::AngleBracketed(arguments), true) => {
+            Ok(Role::Index(arguments.args.clone()))
+        }
+        _ => Ok(Role::Plain),
+    }
+}
+
+/// Sorts the fields `fields` of the variant `name` at `path`.
+fn variant(
+    name: &Ident,
+    path: TokenStream,
+    fields: &Fields,
+) -> syn::Result<Variant> {
+    let fields = fields
+        .iter()
+        .enumerate()
+        .map(|(position, field)| {
+            let member = match &field.ident {
+                Some(ident) => Member::Named(ident.clone()),
+                None => Member::Unnamed(position.into()),
+            };
+            Ok(Field {
+                member,
+                binding: format_ident!("field_{}", position),
+                ty: field.ty.clone(),
+                role: role(&field.ty, &field.attrs)?,
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+    if let Some(field) = fields
+        .iter()
+        .filter(|field| matches!(field.role, Role::Owners))
+        .nth(1)
+    {
+        return Err(syn::Error::new_spanned(
+            &field.ty,
+            "a variant can have at most one `#[arena(owners)]` field",
+        ));
+    }
+    Ok(Variant { name: name.clone(), path, fields })
+}
+
+/// Returns each variant of the derived type `data`, in order.
+pub fn variants(data: &Data, ident: &Ident) -> syn::Result<Vec<Variant>> {
+    match data {
+        Data::Struct(data) => {
+            Ok(vec![variant(ident, quote!(Self), &data.fields)?])
+        }
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|variant_data| {
+                let name = &variant_data.ident;
+                variant(name, quote!(Self::#name), &variant_data.fields)
+            })
+            .collect(),
+        Data::Union(_) => Err(syn::Error::new_spanned(
+            ident,
+            "arena traits cannot be derived for unions",
+        )),
+    }
+}
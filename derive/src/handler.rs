@@ -0,0 +1,743 @@
+//! `#[derive(ArenaHandler)]`.
+
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use quote::format_ident;
+use quote::quote;
+use quote::quote_spanned;
+use syn::Data;
+use syn::DeriveInput;
+use syn::GenericParam;
+use syn::Generics;
+use syn::Ident;
+use syn::Member;
+use syn::WherePredicate;
+use syn::parse_quote;
+use syn::spanned::Spanned;
+
+use crate::container::ArenaEntry;
+use crate::container::Container;
+use crate::fields::Field;
+use crate::fields::Role;
+use crate::fields::Variant;
+use crate::fields::variants;
+
+/// Generates the index sum, and its impls, for the arenas `entries`.
+fn index_sum(
+    input: &DeriveInput,
+    index: &Ident,
+    entries: &[ArenaEntry],
+) -> syn::Result<TokenStream> {
+    let vis = &input.vis;
+    let generics = &input.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let names = entries
+        .iter()
+        .map(ArenaEntry::variant)
+        .collect::<syn::Result<Vec<_>>>()?;
+    for (position, name) in names.iter().enumerate() {
+        if names[..position].contains(name) {
+            return Err(syn::Error::new_spanned(
+                name,
+                "each arena must end in a different name",
+            ));
+        }
+    }
+    let index_types = entries
+        .iter()
+        .map(|ArenaEntry { item, width }| match width {
+            Some(width) => quote!(crate::arena::index::Index<#item, #width>),
+            None => quote!(crate::arena::index::Index<#item>),
+        })
+        .collect::<Vec<_>>();
+    let list = entries
+        .iter()
+        .map(|ArenaEntry { item, width }| match width {
+            Some(width) => quote!(#item: #width),
+            None => quote!(#item),
+        })
+        .collect::<Vec<_>>();
+    let holds = entries
+        .iter()
+        .map(|ArenaEntry { item, width }| match width {
+            Some(width) => quote!(crate::arena::node::Holds<#item, #width>),
+            None => quote!(crate::arena::node::Holds<#item>),
+        })
+        .collect::<Vec<_>>();
+    // The arena of each entry, as a field of the right-associated tuple.
+    let arenas = (0..entries.len())
+        .map(|depth| {
+            let rest = (0..depth).map(|_| quote!(.1));
+            quote!(#(#rest)*.0)
+        })
+        .collect::<Vec<_>>();
+    let doc = format!(
+        "An index into one of the arenas of a [{}], for its traversals.",
+        input.ident
+    );
+
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Debug)]
+        #vis enum #index #generics #where_clause {
+            #(
+                #[allow(missing_docs)]
+                #names(#index_types),
+            )*
+        }
+
+        impl #impl_generics Clone for #index #ty_generics #where_clause {
+            fn clone(&self) -> Self {
+                match self {
+                    #(Self::#names(index) => Self::#names(index.clone()),)*
+                }
+            }
+        }
+
+        #(
+            impl #impl_generics #holds for #index #ty_generics #where_clause {
+                fn wrap(index: #index_types) -> Self {
+                    Self::#names(index)
+                }
+
+                #[allow(unreachable_patterns)]
+                fn unwrap(self) -> Result<#index_types, Self> {
+                    match self {
+                        Self::#names(index) => Ok(index),
+                        other => Err(other),
+                    }
+                }
+            }
+        )*
+
+        const _: () = {
+            use crate::handle_dyn_arenas;
+            use crate::handle_indices;
+
+            impl #impl_generics crate::arena::node::IndexSum
+                for #index #ty_generics #where_clause
+            {
+                type Indices = handle_indices!(#(#list),*);
+                type DynArenas<'a> = handle_dyn_arenas!('a, #(#list),*);
+
+                fn dispatch<'a, O: crate::arena::node::IndexOp<Self>>(
+                    self,
+                    arenas: &Self::DynArenas<'a>,
+                    op: &mut O,
+                ) -> O::Output {
+                    match self {
+                        #(Self::#names(index) => op.apply(arenas #arenas, index),)*
+                    }
+                }
+
+                #[allow(unreachable_patterns)]
+                fn dispatch_pair<'a, 'b, O: crate::arena::node::IndexPairOp<Self>>(
+                    self,
+                    other: Self,
+                    this_arenas: &Self::DynArenas<'a>,
+                    other_arenas: &Self::DynArenas<'b>,
+                    op: &mut O,
+                ) -> Option<O::Output> {
+                    match (self, other) {
+                        #(
+                            (Self::#names(this), Self::#names(other)) => Some(op.apply(
+                                this_arenas #arenas,
+                                this,
+                                other_arenas #arenas,
+                                other,
+                            )),
+                        )*
+                        _ => None,
+                    }
+                }
+            }
+        };
+    })
+}
+
+/// Returns `generics` with an extra type parameter `__P` for the index sum,
+/// bounded so that every field pointing into the arenas can be followed.
+fn node_generics(generics: &Generics, variants: &[Variant]) -> Generics {
+    let mut generics = generics.clone();
+    generics
+        .params
+        .push(GenericParam::Type(parse_quote!(__P)));
+    let mut predicates = Vec::<WherePredicate>::new();
+    for field in variants
+        .iter()
+        .flat_map(Variant::children)
+    {
+        let predicate: WherePredicate = match &field.role {
+            Role::Index(arguments) => {
+                parse_quote!(__P: crate::arena::node::Holds<#arguments>)
+            }
+            Role::Node => {
+                let ty = &field.ty;
+                parse_quote!(#ty: crate::arena::node::ArenaNode<__P>)
+            }
+            Role::Owners | Role::Plain => continue,
+        };
+        let key = predicate.to_token_stream().to_string();
+        if !predicates
+            .iter()
+            .any(|other| other.to_token_stream().to_string() == key)
+        {
+            predicates.push(predicate);
+        }
+    }
+    generics
+        .make_where_clause()
+        .predicates
+        .extend(predicates);
+    generics
+}
+
+/// Returns, for each variant, a pattern matching a pair of items of that
+/// variant, along with a comparison of each pair of fields which does not
+/// point into the arenas: by the `ArenaNode` method `node` for a node, and by
+/// `plain` for anything else.
+fn pair_arms(
+    variants: &[Variant],
+    node: &str,
+    plain: impl Fn(&Ident, &Ident) -> TokenStream,
+) -> Vec<(TokenStream, Vec<TokenStream>)> {
+    let node = format_ident!("{}", node);
+    let this = |field: &Field| format_ident!("this_{}", field.binding);
+    let other = |field: &Field| format_ident!("other_{}", field.binding);
+    let shallow = |role: &Role| matches!(role, Role::Node | Role::Plain);
+    variants
+        .iter()
+        .map(|variant| {
+            let this_pattern = variant.pattern_as(shallow, this);
+            let other_pattern = variant.pattern_as(shallow, other);
+            let comparisons = variant
+                .fields
+                .iter()
+                .filter_map(|field| {
+                    let (this, other) = (this(field), other(field));
+                    match &field.role {
+                        Role::Index(_) | Role::Owners => None,
+                        Role::Node => {
+                            let ty = &field.ty;
+                            Some(quote! {
+                                <#ty as crate::arena::node::ArenaNode<__P>>::#node(
+                                    #this, #other,
+                                )
+                            })
+                        }
+                        Role::Plain => Some(plain(&this, &other)),
+                    }
+                })
+                .collect();
+            (quote!((#this_pattern, #other_pattern)), comparisons)
+        })
+        .collect()
+}
+
+/// Generates the `ArenaNode` impl of the derived type.
+fn arena_node(input: &DeriveInput, variants: &[Variant]) -> TokenStream {
+    let ident = &input.ident;
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    let generics = node_generics(&input.generics, variants);
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+    let placeholder = match &input.data {
+        Data::Enum(_) => variants
+            .iter()
+            .find(|variant| variant.fields.is_empty())
+            .map(|variant| {
+                let path = &variant.path;
+                quote!(Some(#path {}))
+            }),
+        _ => None,
+    }
+    .unwrap_or_else(|| quote!(None));
+
+    let counts = variants.iter().map(|variant| {
+        let counts = variant.children().map(|field| match &field.role {
+            Role::Index(_) => quote!(1),
+            _ => {
+                let ty = &field.ty;
+                quote!(<#ty as crate::arena::node::ArenaNode<__P>>::CHILDREN)
+            }
+        });
+        quote!(0usize #(.saturating_add(#counts))*)
+    });
+    let children = |variant: &Variant| {
+        variant.pattern(|role| matches!(role, Role::Index(_) | Role::Node))
+    };
+    let for_each_arms = variants.iter().map(|variant| {
+        let pattern = children(variant);
+        let calls = variant.children().map(|field| {
+            let binding = &field.binding;
+            match &field.role {
+                Role::Index(arguments) => quote! {
+                    f(<__P as crate::arena::node::Holds<#arguments>>::wrap(
+                        #binding.clone(),
+                    ))?;
+                },
+                _ => {
+                    let ty = &field.ty;
+                    quote! {
+                        <#ty as crate::arena::node::ArenaNode<__P>>::for_each_child(
+                            #binding, f,
+                        )?;
+                    }
+                }
+            }
+        });
+        quote!(#pattern => { #(#calls)* })
+    });
+    let map_arms = variants.iter().map(|variant| {
+        let pattern = children(variant);
+        let calls = variant.children().map(|field| {
+            let binding = &field.binding;
+            match &field.role {
+                Role::Index(arguments) => quote! {
+                    *#binding = crate::arena::node::unwrap_index(f(
+                        <__P as crate::arena::node::Holds<#arguments>>::wrap(
+                            #binding.clone(),
+                        ),
+                    )?)?;
+                },
+                _ => {
+                    let ty = &field.ty;
+                    quote! {
+                        <#ty as crate::arena::node::ArenaNode<__P>>::map_children(
+                            #binding, f,
+                        )?;
+                    }
+                }
+            }
+        });
+        quote!(#pattern => { #(#calls)* })
+    });
+    let hash_arms = variants.iter().map(|variant| {
+        let pattern =
+            variant.pattern(|role| matches!(role, Role::Node | Role::Plain));
+        let calls = variant.fields.iter().map(|field| {
+            let binding = &field.binding;
+            match &field.role {
+                Role::Index(_) | Role::Owners => quote!(),
+                Role::Node => {
+                    let ty = &field.ty;
+                    quote! {
+                        <#ty as crate::arena::node::ArenaNode<__P>>::shallow_hash(
+                            #binding, &mut **state,
+                        );
+                    }
+                }
+                Role::Plain => {
+                    quote!(core::hash::Hash::hash(#binding, state);)
+                }
+            }
+        });
+        quote!(#pattern => { #(#calls)* })
+    });
+    let eq_arms = pair_arms(
+        variants,
+        "shallow_eq",
+        |this, other| quote!(#this == #other),
+    )
+    .into_iter()
+    .map(|(pattern, checks)| {
+        let equal = match checks.is_empty() {
+            true => quote!(true),
+            false => quote!(#(#checks)&&*),
+        };
+        quote!(#pattern => #equal,)
+    });
+    let cmp_arms = pair_arms(
+        variants,
+        "shallow_cmp",
+        |this, other| quote!(core::cmp::Ord::cmp(#this, #other)),
+    )
+    .into_iter()
+    .map(|(pattern, comparisons)| {
+        quote! {
+            #pattern => core::cmp::Ordering::Equal
+                #(.then_with(|| #comparisons))*,
+        }
+    });
+    let ranks = variants
+        .iter()
+        .enumerate()
+        .map(|(rank, variant)| {
+            let path = &variant.path;
+            quote!(#path { .. } => #rank,)
+        });
+    let fmt_arms = variants.iter().map(|variant| {
+        let pattern = variant.pattern(|role| matches!(role, Role::Plain));
+        let name = variant.name.to_string();
+        let plain = variant
+            .fields
+            .iter()
+            .filter(|field| matches!(field.role, Role::Plain))
+            .collect::<Vec<_>>();
+        let write = match plain.first().map(|field| &field.member) {
+            None => quote!(f.write_str(#name)),
+            Some(Member::Unnamed(_)) => {
+                let bindings = plain.iter().map(|field| &field.binding);
+                quote!(f.debug_tuple(#name)#(.field(#bindings))*.finish())
+            }
+            Some(Member::Named(_)) => {
+                let fields = plain.iter().map(|field| {
+                    let (member, binding) = (&field.member, &field.binding);
+                    let member = quote!(#member).to_string();
+                    quote!(.field(#member, #binding))
+                });
+                quote!(f.debug_struct(#name)#(#fields)*.finish())
+            }
+        };
+        quote!(#pattern => #write,)
+    });
+    let discriminant = match &input.data {
+        Data::Enum(_) => quote! {
+            core::hash::Hash::hash(&core::mem::discriminant(self), state);
+        },
+        _ => quote!(),
+    };
+    let owners = owner_methods(variants);
+
+    quote! {
+        impl #impl_generics crate::arena::node::ArenaNode<__P>
+            for #ident #ty_generics #where_clause
+        {
+            const CHILDREN: usize = crate::arena::node::check_children(
+                crate::arena::node::most_children(&[#(#counts),*]),
+            );
+
+            fn placeholder() -> Option<Self> {
+                #placeholder
+            }
+
+            fn for_each_child(
+                &self,
+                f: &mut dyn FnMut(__P) -> crate::arena::error::ArenaResult<()>,
+            ) -> crate::arena::error::ArenaResult<()> {
+                // Checks the number of children once this impl is used.
+                let _ = <Self as crate::arena::node::ArenaNode<__P>>::CHILDREN;
+                match self {
+                    #(#for_each_arms)*
+                }
+                Ok(())
+            }
+
+            fn map_children(
+                &mut self,
+                f: &mut dyn FnMut(__P) -> crate::arena::error::ArenaResult<__P>,
+            ) -> crate::arena::error::ArenaResult<()> {
+                match self {
+                    #(#map_arms)*
+                }
+                Ok(())
+            }
+
+            fn shallow_hash(&self, mut state: &mut dyn core::hash::Hasher) {
+                let state = &mut state;
+                #discriminant
+                match self {
+                    #(#hash_arms)*
+                }
+            }
+
+            fn shallow_eq(&self, other: &Self) -> bool {
+                #[allow(unreachable_patterns)]
+                match (self, other) {
+                    #(#eq_arms)*
+                    _ => false,
+                }
+            }
+
+            fn shallow_cmp(&self, other: &Self) -> core::cmp::Ordering {
+                // Different variants are ordered as they are declared.
+                let rank = |item: &Self| -> usize {
+                    match item {
+                        #(#ranks)*
+                    }
+                };
+                #[allow(unreachable_patterns)]
+                match (self, other) {
+                    #(#cmp_arms)*
+                    _ => rank(self).cmp(&rank(other)),
+                }
+            }
+
+            fn shallow_fmt(
+                &self,
+                f: &mut core::fmt::Formatter<'_>,
+            ) -> core::fmt::Result {
+                match self {
+                    #(#fmt_arms)*
+                }
+            }
+
+            #owners
+        }
+    }
+}
+
+/// Generates the `ArenaNode` methods by which the variants with an
+/// `#[arena(owners)]` field count the owners pointing to their slot, so that
+/// copying an owner adds one rather than copying the item, or nothing if no
+/// variant has such a field.
+fn owner_methods(variants: &[Variant]) -> TokenStream {
+    let counted = variants
+        .iter()
+        .filter(|variant| variant.owners().is_some())
+        .collect::<Vec<_>>();
+    if counted.is_empty() {
+        return quote!();
+    }
+    let matches = counted
+        .iter()
+        .map(|variant| variant.pattern(|_| false));
+    let patterns = counted
+        .iter()
+        .map(|variant| variant.pattern(|role| matches!(role, Role::Owners)))
+        .collect::<Vec<_>>();
+    let owners = counted
+        .iter()
+        .filter_map(|variant| variant.owners())
+        .map(|field| &field.binding)
+        .collect::<Vec<_>>();
+
+    quote! {
+        fn shallow_clone(&self) -> Self {
+            let mut copy = self.clone();
+            #[allow(unreachable_patterns)]
+            match &mut copy {
+                #(#patterns => *#owners = 1,)*
+                _ => {}
+            }
+            copy
+        }
+
+        fn link_at<__I: crate::arena::index::IndexWidth>(
+            arena: &dyn crate::arena::Arena<Self, __I>,
+            index: &crate::arena::index::Index<Self, __I>,
+        ) -> crate::arena::error::ArenaResult<crate::arena::validation::Link> {
+            crate::arena::extension::Inspect::inspect(
+                arena,
+                index.clone(),
+                |item| {
+                    #[allow(unreachable_patterns)]
+                    match item {
+                        #(#matches => crate::arena::validation::Link::Counted,)*
+                        _ => crate::arena::validation::Link::Owned,
+                    }
+                },
+            )
+        }
+
+        fn release<__I: crate::arena::index::IndexWidth>(
+            arena: &dyn crate::arena::Arena<Self, __I>,
+            index: crate::arena::index::Index<Self, __I>,
+        ) -> crate::arena::error::ArenaResult<Option<Self>> {
+            let owners = crate::arena::extension::Inspect::inspect_mut(
+                arena,
+                index.clone(),
+                |item| {
+                    #[allow(unreachable_patterns)]
+                    match item {
+                        #(#patterns => {
+                            *#owners = #owners.saturating_sub(1);
+                            *#owners
+                        })*
+                        _ => 0,
+                    }
+                },
+            )?;
+            if owners > 0 {
+                return Ok(None);
+            }
+            crate::arena::Arena::take(arena, index).map(Some)
+        }
+
+        fn retain<__I: crate::arena::index::IndexWidth>(
+            arena: &dyn crate::arena::Arena<Self, __I>,
+            index: &crate::arena::index::Index<Self, __I>,
+        ) -> Option<
+            crate::arena::error::ArenaResult<crate::arena::index::Index<Self, __I>>,
+        > {
+            let counted = crate::arena::extension::Inspect::inspect_mut(
+                arena,
+                index.clone(),
+                |item| {
+                    #[allow(unreachable_patterns)]
+                    match item {
+                        #(#patterns => {
+                            *#owners = #owners.saturating_add(1);
+                            true
+                        })*
+                        _ => false,
+                    }
+                },
+            );
+            match counted {
+                Ok(true) => Some(Ok(index.clone())),
+                Ok(false) => None,
+                Err(error) => Some(Err(error)),
+            }
+        }
+    }
+}
+
+/// Generates the `ArenaHandler` impl of the derived type, which runs each
+/// traversal with the index sum and depth of `container`.
+fn arena_handler(input: &DeriveInput, container: &Container) -> TokenStream {
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) =
+        input.generics.split_for_impl();
+    let index = &container.index;
+    let depth = container.depth();
+    let sum = quote!(#index #ty_generics);
+    let traversal = quote!(crate::arena::traversal);
+    let dyn_arenas = quote!(crate::arena::tuple::DynArenasOf);
+    let result = quote!(crate::arena::error::ArenaResult);
+
+    quote! {
+        impl #impl_generics crate::arena::handler::ArenaHandler
+            for #ident #ty_generics #where_clause
+        {
+            type Indices = <#sum as crate::arena::node::IndexSum>::Indices;
+            type DynArenas<'a> =
+                <#sum as crate::arena::node::IndexSum>::DynArenas<'a>;
+
+            fn try_drop_in<'a>(
+                self,
+                arenas: &#dyn_arenas<'a, Self>,
+            ) -> #result<()> {
+                #traversal::drop_item::<#sum, Self, #depth>(self, arenas)
+            }
+
+            fn try_clone_in<'a>(
+                &self,
+                arenas: &#dyn_arenas<'a, Self>,
+            ) -> #result<Self> {
+                #traversal::copy_item::<#sum, Self, #depth>(
+                    self,
+                    arenas,
+                    arenas,
+                    #traversal::SharedCopies::Retain,
+                )
+            }
+
+            fn remap_in<'a>(
+                &mut self,
+                arenas: &#dyn_arenas<'a, Self>,
+            ) -> #result<()> {
+                #traversal::remap_item::<#sum, Self, #depth>(self, arenas)
+            }
+
+            fn check_remap_in<'a>(
+                &self,
+                arenas: &#dyn_arenas<'a, Self>,
+            ) -> #result<()> {
+                #traversal::check_remap_item::<#sum, Self, #depth>(self, arenas)
+            }
+
+            fn mark_in<'a>(
+                &self,
+                arenas: &#dyn_arenas<'a, Self>,
+                marks: &mut crate::arena::reachability::Marks,
+            ) -> #result<()> {
+                #traversal::mark_item::<#sum, Self, #depth>(self, arenas, marks)
+            }
+
+            fn check_in<'a>(
+                &self,
+                arenas: &#dyn_arenas<'a, Self>,
+                validator: &mut crate::arena::validation::Validator,
+            ) -> #result<()> {
+                #traversal::check_item::<#sum, Self, #depth>(self, arenas, validator)
+            }
+
+            fn transfer_in<'a, 'b>(
+                &self,
+                source: &#dyn_arenas<'a, Self>,
+                destination: &#dyn_arenas<'b, Self>,
+                transfers: &mut crate::arena::transfer::Transfers,
+            ) -> #result<Self> {
+                #traversal::copy_item::<#sum, Self, #depth>(
+                    self,
+                    source,
+                    destination,
+                    #traversal::SharedCopies::Transfer(transfers),
+                )
+            }
+        }
+    }
+}
+
+/// Generates a check, at the type of each field which points into the arenas,
+/// that the index sum `index` has an arena for what it points to, so that an
+/// arena missing from `#[arena(arenas(..))]` is reported at the field which
+/// needs it.
+fn arena_checks(
+    input: &DeriveInput,
+    index: &Ident,
+    variants: &[Variant],
+) -> TokenStream {
+    let (impl_generics, ty_generics, where_clause) =
+        input.generics.split_for_impl();
+    let sum = quote!(#index #ty_generics);
+    let checks = variants
+        .iter()
+        .flat_map(Variant::children)
+        .map(|field| {
+            let ty = &field.ty;
+            let check = match field.role {
+                Role::Index(_) => quote!(holds),
+                _ => quote!(node),
+            };
+            quote_spanned!(ty.span()=>
+                #check(core::marker::PhantomData::<(#sum, #ty)>);
+            )
+        });
+
+    quote! {
+        const _: () = {
+            fn holds<P, T, I>(
+                _: core::marker::PhantomData<(
+                    P,
+                    crate::arena::index::Index<T, I>,
+                )>,
+            ) where
+                P: crate::arena::node::Holds<T, I>,
+                I: crate::arena::index::IndexWidth,
+            {
+            }
+
+            fn node<P, N>(_: core::marker::PhantomData<(P, N)>)
+            where
+                N: crate::arena::node::ArenaNode<P>,
+            {
+            }
+
+            #[allow(dead_code)]
+            fn check #impl_generics () #where_clause {
+                #(#checks)*
+            }
+        };
+    }
+}
+
+/// Generates everything `#[derive(ArenaHandler)]` stands for.
+pub fn derive(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let container = Container::parse(&input.ident, &input.attrs)?;
+    let variants = variants(&input.data, &input.ident)?;
+    let index_sum = match &container.arenas {
+        Some(entries) => index_sum(input, &container.index, entries)?,
+        None => quote!(),
+    };
+    let arena_node = arena_node(input, &variants);
+    let arena_checks = arena_checks(input, &container.index, &variants);
+    let arena_handler = arena_handler(input, &container);
+    Ok(quote! {
+        #index_sum
+        #arena_checks
+        #arena_node
+        #arena_handler
+    })
+}
@@ -0,0 +1,170 @@
+//! Derive macros for the arena traits of `synth`.
+//!
+//! The generated code names items by `crate::` paths, so these derives are
+//! only meant to be used within `synth` itself.
+
+#![deny(missing_docs, unused)]
+
+mod container;
+mod fields;
+mod handler;
+mod whole;
+
+use proc_macro::TokenStream;
+use syn::DeriveInput;
+use syn::parse_macro_input;
+
+/// Derives `ArenaHandler`, along with the `ArenaNode` view of the type which
+/// the traversals in `arena::traversal` walk through.
+///
+/// Every field of type `Index<T, I>` is followed into the arena of `T`, and so
+/// is every field of type `Chain<..>`, or of any other type implementing
+/// `ArenaNode`, such as an alias of a chain, if it is marked `#[arena]`. All
+/// other fields are copied, hashed and compared as they are.
+///
+/// The type must name its index sum, an enum with a variant holding an
+/// `Index` into each arena of the tuple, which takes the same generic
+/// parameters as the type:
+///
+/// ```ignore
+/// #[derive(ArenaHandler)]
+/// #[arena(index = ExprIndex, arenas(Expr, Chain<Expr>, Atom: u8))]
+/// enum Expr {
+///     Add(Index<Expr>, Index<Expr>),
+///     Sum(Chain<Expr>),
+///     Unit(Index<Atom, u8>),
+///     Zero,
+/// }
+///
+/// #[derive(ArenaHandler)]
+/// #[arena(index = ExprIndex)]
+/// struct Atom(u32, Index<Expr>);
+/// ```
+///
+/// Listing the `arenas`, each with its index width if it is not the default,
+/// generates the index sum, as well as the `Indices` and `DynArenas` of every
+/// type which shares it. Each arena must be named by a path whose last
+/// segment is unique among them, which names its variant. A field which
+/// points into an arena that is missing from the list, or listed with another
+/// index width, is reported as an error at the field.
+///
+/// Each traversal keeps its pending steps in a `WorkStack` of capacity
+/// `TRAVERSAL_DEPTH`, unless `#[arena(depth = ..)]` gives another, and fails
+/// with `ArenaError::DepthLimitExceeded` on an item nested more deeply than
+/// that. The same `depth` is used by the other derives of the type.
+///
+/// No variant may hold more than `MAX_CHILDREN` indices, counting two for each
+/// chain held by value. A type which does fails to compile once it is
+/// traversed.
+///
+/// The first fieldless variant of an enum is the placeholder which stands in
+/// for a copy until it is filled in. A type without one, such as a struct, is
+/// copied eagerly instead, so every recursive type needs one.
+///
+/// A variant may mark one integer field `#[arena(owners)]`, making its slot
+/// reference-counted: that field counts the owners pointing to the slot, and
+/// is left out of hashing, comparing and formatting. Copying an owner within
+/// the same arenas adds one rather than copying the item, and dropping an
+/// owner only drops the item once it has no owners left.
+#[proc_macro_derive(ArenaHandler, attributes(arena))]
+pub fn derive_arena_handler(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    handler::derive(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives `ArenaEq` for a type which also derives `ArenaHandler`, reading the
+/// index sum from the same `#[arena(index = ..)]` attribute.
+///
+/// Two items are equal if they agree in every field which does not point into
+/// the arenas, compared with `PartialEq`, and if the items their `Index` and
+/// `Chain` fields point to are equal in turn, wherever they are stored.
+#[proc_macro_derive(ArenaEq, attributes(arena))]
+pub fn derive_arena_eq(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    whole::arena_eq(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives `ArenaOrd` for a type which also derives `ArenaHandler` and
+/// `ArenaEq`, reading the index sum from the same `#[arena(index = ..)]`
+/// attribute.
+///
+/// Items are ordered by their variants, in the order they are declared, then
+/// by each field which does not point into the arenas, compared with `Ord`,
+/// and then by the items their `Index` and `Chain` fields point to, in turn.
+/// A chain comes before any longer chain which it begins.
+#[proc_macro_derive(ArenaOrd, attributes(arena))]
+pub fn derive_arena_ord(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    whole::arena_ord(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives `ArenaHash` for a type which also derives `ArenaHandler`, reading
+/// the index sum from the same `#[arena(index = ..)]` attribute.
+///
+/// Each field which does not point into the arenas is hashed with `Hash`, and
+/// the items its `Index` and `Chain` fields point to are hashed in turn, so
+/// that items which are equal by `ArenaEq` hash the same.
+#[proc_macro_derive(ArenaHash, attributes(arena))]
+pub fn derive_arena_hash(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    whole::arena_hash(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives `ArenaDebug` for a type which also derives `ArenaHandler`, reading
+/// the index sum from the same `#[arena(index = ..)]` attribute.
+///
+/// Each item is written on its own line, as the name of its variant along with
+/// every field which does not point into the arenas, and the items its `Index`
+/// and `Chain` fields point to are written on the lines below it, indented one
+/// level further. The cells of a chain are not written themselves, so the
+/// items in a chain are written as siblings.
+#[proc_macro_derive(ArenaDebug, attributes(arena))]
+pub fn derive_arena_debug(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    whole::arena_debug(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives `ArenaVisit` for a type which also derives `ArenaHandler`, reading
+/// the index sum from the same `#[arena(index = ..)]` attribute.
+///
+/// The visitor is shown the item, and then each item its `Index` and `Chain`
+/// fields point to, depth first and in order. The cells of a chain are not
+/// shown themselves.
+#[proc_macro_derive(ArenaVisit, attributes(arena))]
+pub fn derive_arena_visit(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    whole::arena_visit(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives `ArenaVisitMut` for a type which also derives `ArenaHandler`, which
+/// visits items as `#[derive(ArenaVisit)]` does, storing back whatever the
+/// visitor rewrites.
+#[proc_macro_derive(ArenaVisitMut, attributes(arena))]
+pub fn derive_arena_visit_mut(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    whole::arena_visit_mut(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives `ArenaFold` for a type which also derives `ArenaHandler`, which
+/// folds items in the order that `#[derive(ArenaVisit)]` visits them.
+#[proc_macro_derive(ArenaFold, attributes(arena))]
+pub fn derive_arena_fold(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    whole::arena_fold(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,161 @@
+//! `#[derive(ArenaEq)]`, `#[derive(ArenaOrd)]`, `#[derive(ArenaHash)]`,
+//! `#[derive(ArenaDebug)]`, `#[derive(ArenaVisit)]`, `#[derive(ArenaVisitMut)]`
+//! and `#[derive(ArenaFold)]`, each of which runs a single traversal over a
+//! whole item through the `ArenaNode` impl generated by
+//! `#[derive(ArenaHandler)]`.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::DeriveInput;
+
+use crate::container::Container;
+
+/// Generates an impl of the trait `path` for the derived type, with the
+/// methods given by `methods` for the generic arguments of its traversals:
+/// its index sum, the type itself, and then the depth of the traversal.
+fn whole(
+    input: &DeriveInput,
+    path: TokenStream,
+    methods: impl FnOnce(TokenStream, TokenStream) -> TokenStream,
+) -> syn::Result<TokenStream> {
+    let container = Container::parse(&input.ident, &input.attrs)?;
+    let ident = &input.ident;
+    let index = &container.index;
+    let (impl_generics, ty_generics, where_clause) =
+        input.generics.split_for_impl();
+    let depth = container.depth();
+    let methods = methods(quote!(#index #ty_generics), depth);
+
+    Ok(quote! {
+        impl #impl_generics #path for #ident #ty_generics #where_clause {
+            #methods
+        }
+    })
+}
+
+/// Generates the `ArenaEq` impl of the derived type.
+pub fn arena_eq(input: &DeriveInput) -> syn::Result<TokenStream> {
+    whole(input, quote!(crate::arena::equality::ArenaEq), |sum, depth| {
+        quote! {
+            fn try_eq_in<'a, 'b>(
+                this: &Self,
+                other: &Self,
+                this_arenas: &crate::arena::tuple::DynArenasOf<'a, Self>,
+                other_arenas: &crate::arena::tuple::DynArenasOf<'b, Self>,
+            ) -> crate::arena::error::ArenaResult<bool> {
+                crate::arena::traversal::eq_item::<#sum, Self, #depth>(
+                    this,
+                    other,
+                    this_arenas,
+                    other_arenas,
+                )
+            }
+        }
+    })
+}
+
+/// Generates the `ArenaOrd` impl of the derived type.
+pub fn arena_ord(input: &DeriveInput) -> syn::Result<TokenStream> {
+    whole(input, quote!(crate::arena::ordering::ArenaOrd), |sum, depth| {
+        quote! {
+            fn try_cmp_in<'a, 'b>(
+                this: &Self,
+                other: &Self,
+                this_arenas: &crate::arena::tuple::DynArenasOf<'a, Self>,
+                other_arenas: &crate::arena::tuple::DynArenasOf<'b, Self>,
+            ) -> crate::arena::error::ArenaResult<core::cmp::Ordering> {
+                crate::arena::traversal::cmp_item::<#sum, Self, #depth>(
+                    this,
+                    other,
+                    this_arenas,
+                    other_arenas,
+                )
+            }
+        }
+    })
+}
+
+/// Generates the `ArenaHash` impl of the derived type.
+pub fn arena_hash(input: &DeriveInput) -> syn::Result<TokenStream> {
+    whole(input, quote!(crate::arena::hash::ArenaHash), |sum, depth| {
+        quote! {
+            fn try_hash_in<H: core::hash::Hasher>(
+                &self,
+                arenas: &crate::arena::tuple::DynArenasOf<'_, Self>,
+                state: &mut H,
+            ) -> crate::arena::error::ArenaResult<()> {
+                crate::arena::traversal::hash_item::<#sum, Self, #depth>(
+                    self, arenas, state,
+                )
+            }
+        }
+    })
+}
+
+/// Generates the `ArenaDebug` impl of the derived type.
+pub fn arena_debug(input: &DeriveInput) -> syn::Result<TokenStream> {
+    whole(input, quote!(crate::arena::debug::ArenaDebug), |sum, depth| {
+        quote! {
+            fn fmt_in(
+                &self,
+                arenas: &crate::arena::tuple::DynArenasOf<'_, Self>,
+                f: &mut core::fmt::Formatter<'_>,
+            ) -> core::fmt::Result {
+                crate::arena::traversal::fmt_item::<#sum, Self, #depth>(
+                    self, arenas, f,
+                )
+            }
+        }
+    })
+}
+
+/// Generates the `ArenaVisit` impl of the derived type.
+pub fn arena_visit(input: &DeriveInput) -> syn::Result<TokenStream> {
+    whole(input, quote!(crate::arena::visit::ArenaVisit), |sum, depth| {
+        quote! {
+            fn try_visit_in(
+                &self,
+                arenas: &crate::arena::tuple::DynArenasOf<'_, Self>,
+                visitor: &mut dyn crate::arena::visit::Visitor,
+            ) -> crate::arena::error::ArenaResult<()> {
+                crate::arena::traversal::visit_item::<#sum, Self, #depth>(
+                    self, arenas, visitor,
+                )
+            }
+        }
+    })
+}
+
+/// Generates the `ArenaVisitMut` impl of the derived type.
+pub fn arena_visit_mut(input: &DeriveInput) -> syn::Result<TokenStream> {
+    whole(input, quote!(crate::arena::visit::ArenaVisitMut), |sum, depth| {
+        quote! {
+            fn try_visit_mut_in(
+                &mut self,
+                arenas: &crate::arena::tuple::DynArenasOf<'_, Self>,
+                visitor: &mut dyn crate::arena::visit::VisitorMut,
+            ) -> crate::arena::error::ArenaResult<()> {
+                crate::arena::traversal::visit_item_mut::<#sum, Self, #depth>(
+                    self, arenas, visitor,
+                )
+            }
+        }
+    })
+}
+
+/// Generates the `ArenaFold` impl of the derived type.
+pub fn arena_fold(input: &DeriveInput) -> syn::Result<TokenStream> {
+    whole(input, quote!(crate::arena::visit::ArenaFold), |sum, depth| {
+        quote! {
+            fn try_fold_in<F: crate::arena::visit::Folder>(
+                &self,
+                arenas: &crate::arena::tuple::DynArenasOf<'_, Self>,
+                folder: &mut F,
+            ) -> crate::arena::error::ArenaResult<F::Output> {
+                crate::arena::traversal::fold_item::<#sum, Self, F, #depth>(
+                    self, arenas, folder,
+                )
+            }
+        }
+    })
+}
@@ -1,10 +1,11 @@
 #![allow(unused)]
 //! For compatibility between `std` and `no_std` environments,
-//! re-export commonly used heap-allocated types: `Box`, `Rc` and `Vec`.
+//! re-export commonly used heap-allocated types: `Box`, `Rc`, `Vec`,
+//! `BTreeMap` (with its `Entry`) and `BTreeSet`.
 
 #[rustfmt::skip]
 #[cfg(not(feature = "std"))]
-pub use alloc::{boxed::Box, rc::Rc, vec::Vec};
+pub use alloc::{boxed::Box, collections::BTreeMap, collections::btree_map, collections::BTreeSet, rc::Rc, vec::Vec};
 #[rustfmt::skip]
 #[cfg(feature = "std")]
-pub use std::{boxed::Box, rc::Rc, vec::Vec};
+pub use std::{boxed::Box, collections::BTreeMap, collections::btree_map, collections::BTreeSet, rc::Rc, vec::Vec};
@@ -0,0 +1,95 @@
+//! Owning handles to arena-allocated items, which release the item from its
+//! arenas when dropped.
+
+use core::fmt::Debug;
+use core::mem::ManuallyDrop;
+use core::ops::Deref;
+
+use super::error::ArenaResult;
+use super::handler::ArenaHandler;
+use super::tuple::DynArenasOf;
+
+/// An item along with the arenas it was created in, which owns the item in the
+/// same way that a `Box` owns its contents.
+///
+/// Dropping the handle calls [ArenaHandler::try_drop_in], so that the item and
+/// everything it points to is released from its arenas, and cloning it calls
+/// [ArenaHandler::clone_in]. Any error met while dropping is ignored, so
+/// [ArenaBox::try_drop] should be used where it matters.
+pub struct ArenaBox<'a, T: ArenaHandler> {
+    item: ManuallyDrop<T>,
+    arenas: &'a DynArenasOf<'a, T>,
+}
+
+impl<'a, T: ArenaHandler> ArenaBox<'a, T> {
+    /// Takes ownership of `item`, which must have been created in `arenas`.
+    #[allow(unused)]
+    pub fn new(item: T, arenas: &'a DynArenasOf<'a, T>) -> Self {
+        Self { item: ManuallyDrop::new(item), arenas }
+    }
+
+    /// Returns the arenas which the item was created in.
+    #[allow(unused)]
+    pub fn arenas(&self) -> &'a DynArenasOf<'a, T> {
+        self.arenas
+    }
+
+    /// Clones the item as in [ArenaHandler::try_clone_in], into a new handle
+    /// to the same arenas.
+    #[allow(unused)]
+    pub fn try_clone(&self) -> ArenaResult<Self> {
+        let cloned = self.item.try_clone_in(self.arenas)?;
+        Ok(Self::new(cloned, self.arenas))
+    }
+
+    /// Drops the item from its arenas as in [ArenaHandler::try_drop_in],
+    /// returning the first error met.
+    #[allow(unused)]
+    pub fn try_drop(self) -> ArenaResult<()> {
+        let arenas = self.arenas;
+        self.into_inner().try_drop_in(arenas)
+    }
+
+    /// Gives up ownership of the item without releasing it, after which it must
+    /// be dropped from its arenas by hand.
+    #[allow(unused)]
+    pub fn into_inner(self) -> T {
+        let mut this = ManuallyDrop::new(self);
+        // SAFETY: `this` is never used again, and is not dropped, so the item
+        // is neither read again nor dropped twice.
+        unsafe { ManuallyDrop::take(&mut this.item) }
+    }
+}
+
+impl<T: ArenaHandler> Deref for ArenaBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.item
+    }
+}
+
+impl<T: ArenaHandler> Drop for ArenaBox<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: the handle is being dropped, so the item is never read again.
+        let item = unsafe { ManuallyDrop::take(&mut self.item) };
+        let _ = item.try_drop_in(self.arenas);
+    }
+}
+
+/// Panics if the clone cannot be allocated, as in [ArenaHandler::clone_in];
+/// see [ArenaBox::try_clone] for a version which does not panic.
+#[cfg(any(test, not(feature = "no-panic")))]
+impl<T: ArenaHandler> Clone for ArenaBox<'_, T> {
+    fn clone(&self) -> Self {
+        Self::new(self.item.clone_in(self.arenas), self.arenas)
+    }
+}
+
+impl<T: ArenaHandler + Debug> Debug for ArenaBox<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("ArenaBox")
+            .field(&*self.item)
+            .finish()
+    }
+}
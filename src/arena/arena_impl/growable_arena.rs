@@ -3,77 +3,98 @@ use crate::arena::Arena;
 use crate::arena::ArenaItem;
 use crate::arena::arena_impl::helpers::IndexableMap;
 use crate::arena::arena_impl::helpers::IndexableMapArena;
+use crate::arena::arena_impl::helpers::Slot;
 use crate::arena::error::ArenaError;
 use crate::arena::error::ArenaResult;
-use crate::arena::index::Index;
+use crate::arena::index::IndexWidth;
 
 #[derive(Debug)]
-pub struct GrowableArena<T: ArenaItem>(IndexableMapArena<T, GAMap<T>>);
+pub struct GrowableArena<T: ArenaItem, I: IndexWidth = u16>(
+    IndexableMapArena<T, GAMap<T, I>, I>,
+);
 
+/// The backing map of a [GrowableArena], which grows a [Vec] of slots as
+/// needed.
 #[derive(Debug)]
-struct GAMap<T>(Vec<Option<T>>);
+pub struct GAMap<T, I = u16>(Vec<Slot<T, I>>);
+
+impl<T, I> GAMap<T, I> {
+    /// Creates an empty map.
+    pub const fn new() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<T, I: IndexWidth> IndexableMap<T, I> for GAMap<T, I> {
+    const CAPACITY: usize = usize::MAX;
 
-impl<T> IndexableMap<T> for GAMap<T> {
     fn size(&self) -> usize {
         self.0
             .iter()
-            .filter_map(Option::as_ref)
+            .filter(|slot| slot.is_full())
             .count()
     }
 
-    fn get_slot(&mut self, index: Index<T>) -> Option<&mut Option<T>> {
-        self.0.get_mut(usize::from(index))
+    fn get_slot(&mut self, index: I) -> Option<&mut Slot<T, I>> {
+        self.0.get_mut(index.to_usize())
     }
 
-    fn clear(&mut self) {
-        self.0.clear()
+    fn get_slot_ref(&self, index: I) -> Option<&Slot<T, I>> {
+        self.0.get(index.to_usize())
     }
-}
 
-impl<T: ArenaItem> GrowableArena<T> {
-    #[allow(unused)]
-    pub fn new() -> Self {
-        // Note that we are allowed to make an arena larger than u16::MAX slots
-        // (but we will never be able to allocate into the excess portion).
-        Self(IndexableMapArena::new(GAMap(Vec::new())))
-    }
-
-    #[allow(unused)]
-    pub fn reset(&self) {
-        self.0.with_inner(|next_index, map| {
-            *next_index = 0;
-            map.clear();
-        })
+    fn new_slot(&mut self, index: I) -> ArenaResult<&mut Slot<T, I>> {
+        // Slots are only ever created at the end of the inner `Vec`.
+        let index = index.to_usize();
+        if index == self.0.len() {
+            // Running out of heap is reported like any other full arena.
+            self.0
+                .try_reserve(1)
+                .map_err(|_| ArenaError::LimitReached)?;
+            self.0.push(Slot::EMPTY);
+        }
+        self.0
+            .get_mut(index)
+            .ok_or(ArenaError::IndexOutOfBounds)
     }
-}
 
-impl<T: ArenaItem> Arena<T> for GrowableArena<T> {
-    fn size(&self) -> usize {
-        self.0.size()
+    fn full_slots_from<'a>(
+        &'a self,
+        from: I,
+    ) -> impl Iterator<Item = (I, &'a Slot<T, I>)>
+    where
+        T: 'a,
+    {
+        self.0
+            .iter()
+            .enumerate()
+            .skip(from.to_usize())
+            .map_while(|(index, slot)| Some((I::from_usize(index)?, slot)))
+            .filter(|(_, slot)| slot.is_full())
     }
 
-    fn alloc(&self, value: T) -> ArenaResult<Index<T>> {
-        // We need to extend the inner `Vec` with one extra slot, provided we
-        // have not already exceeded the limit.
-        self.0.with_inner(|next_index, map| {
-            if *next_index == u16::MAX {
-                return Err(ArenaError::LimitReached);
-            }
-            map.0.push(None);
-            Ok(())
-        })?;
-        self.0.alloc(value)
+    fn clear(&mut self) {
+        self.0.clear()
     }
 
-    fn take(&self, index: Index<T>) -> ArenaResult<T> {
-        self.0.take(index)
+    fn truncate(&mut self, len: I) {
+        self.0.truncate(len.to_usize());
+        self.0.shrink_to_fit()
     }
+}
 
-    fn has_slot(&self, index: Index<T>) -> ArenaResult<bool> {
-        self.0.has_slot(index)
+impl<T: ArenaItem, I: IndexWidth> GrowableArena<T, I> {
+    #[allow(unused)]
+    pub fn new() -> Self {
+        // Note that we are allowed to make an arena larger than `I::MAX` slots
+        // (but we will never be able to allocate into the excess portion).
+        Self(IndexableMapArena::new(GAMap::new()))
     }
 
-    fn insert(&self, index: Index<T>, value: T) -> ArenaResult<()> {
-        self.0.insert(index, value)
+    #[allow(unused)]
+    pub fn reset(&self) -> ArenaResult<()> {
+        self.0.reset()
     }
 }
+
+forward_arena_impl!(impl[T: ArenaItem, I: IndexWidth] Arena<T, I> for GrowableArena<T, I>);
@@ -1,14 +1,106 @@
+use core::cell::Cell;
 use core::cell::RefCell;
 use core::marker::PhantomData;
-use core::ops::DerefMut;
+use core::mem;
+use core::sync::atomic::AtomicUsize;
+
+use spin::RwLock;
 
 use crate::arena::Arena;
 use crate::arena::ArenaItem;
+use crate::arena::arena_impl::state_cell::StateCell;
 use crate::arena::error::ArenaError;
 use crate::arena::error::ArenaResult;
 use crate::arena::index::Index;
+use crate::arena::index::IndexWidth;
+use crate::arena::stats::ArenaCounters;
+use crate::arena::stats::ArenaStats;
+use crate::arena::stats::Counter;
+
+/// A slot of an [IndexableMap], which is either full or free, along with the
+/// number of times it has been reused.
+#[derive(Debug)]
+pub struct Slot<T, I> {
+    generation: I,
+    entry: Entry<T, I>,
+}
+
+#[derive(Debug)]
+enum Entry<T, I> {
+    /// A slot which currently holds a value.
+    Full(T),
+    /// A slot which does not hold a value. Every free slot below the arena's
+    /// next index belongs to the arena's free list, and `prev` and `next` are
+    /// the neighbouring free slots in that list.
+    Free { prev: Option<I>, next: Option<I> },
+    /// A slot which holds a value allocated during the arena's open
+    /// transaction, where `next` is the slot allocated or taken before it in
+    /// that transaction.
+    Pending { value: T, next: Option<I> },
+    /// A slot which held the value `original` before the arena's open
+    /// transaction, and which has been mutated or taken during it, so that
+    /// `original` is kept until the transaction is closed. The slot is full if
+    /// and only if `value` is `Some`, and `next` is as in [Entry::Pending].
+    Held { original: T, value: Option<T>, next: Option<I> },
+    /// A slot whose value has been moved to the slot at `to` by
+    /// [Arena::compact], which now has the given `generation`.
+    Moved { to: I, generation: I },
+}
+
+impl<T, I: IndexWidth> Slot<T, I> {
+    /// A free slot which has never been used.
+    pub const EMPTY: Self = Self {
+        generation: I::ZERO,
+        entry: Entry::Free { prev: None, next: None },
+    };
+
+    /// Returns true if and only if this slot holds a value.
+    pub const fn is_full(&self) -> bool {
+        matches!(
+            self.entry,
+            Entry::Full(_)
+                | Entry::Pending { .. }
+                | Entry::Held { value: Some(_), .. }
+        )
+    }
+
+    /// Returns a reference to the value held in this slot, if it is full.
+    const fn value(&self) -> Option<&T> {
+        match &self.entry {
+            Entry::Full(value)
+            | Entry::Pending { value, .. }
+            | Entry::Held { value: Some(value), .. } => Some(value),
+            Entry::Free { .. }
+            | Entry::Held { value: None, .. }
+            | Entry::Moved { .. } => None,
+        }
+    }
+
+    /// Returns a mutable reference to the value held in this slot, if it is
+    /// full.
+    const fn value_mut(&mut self) -> Option<&mut T> {
+        match &mut self.entry {
+            Entry::Full(value)
+            | Entry::Pending { value, .. }
+            | Entry::Held { value: Some(value), .. } => Some(value),
+            Entry::Free { .. }
+            | Entry::Held { value: None, .. }
+            | Entry::Moved { .. } => None,
+        }
+    }
+}
+
+impl<T, I: IndexWidth> Default for Slot<T, I> {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+pub trait IndexableMap<T, I: IndexWidth> {
+    /// The largest number of slots the map can hold, which may be more than
+    /// an [Index] can address.
+    const CAPACITY: usize;
 
-pub trait IndexableMap<T> {
     /// Returns the number of full slots in the current map.
     fn size(&self) -> usize;
 
@@ -16,104 +108,800 @@ pub trait IndexableMap<T> {
     ///
     /// Returns `None` if the index is out of bounds, otherwise returns
     /// `Some(slot)` where `slot` is a mutable value mapped by `index`.
-    fn get_slot(&mut self, index: Index<T>) -> Option<&mut Option<T>>;
+    fn get_slot(&mut self, index: I) -> Option<&mut Slot<T, I>>;
+
+    /// Provides the entry of the map at the given index, as in
+    /// [IndexableMap::get_slot], but immutably.
+    fn get_slot_ref(&self, index: I) -> Option<&Slot<T, I>>;
+
+    /// Provides the entry of the map at the given index, which has never been
+    /// used before, creating it if necessary.
+    ///
+    /// Returns [ArenaError::LimitReached] if the map has no room for another
+    /// entry.
+    fn new_slot(&mut self, index: I) -> ArenaResult<&mut Slot<T, I>>;
+
+    /// Iterates over the full slots of the map at or after the given index,
+    /// in order of index.
+    fn full_slots_from<'a>(
+        &'a self,
+        from: I,
+    ) -> impl Iterator<Item = (I, &'a Slot<T, I>)>
+    where
+        T: 'a;
 
     /// Clears the map, dropping all items stored inside.
     fn clear(&mut self);
+
+    /// Drops every slot at or above `len`, releasing any storage they used.
+    fn truncate(&mut self, len: I);
 }
 
 /// An adapter for implementing [Arena] with a backing [IndexableMap] field.
+///
+/// Slots freed by [Arena::take] are kept in a doubly-linked free list which
+/// runs through the free slots themselves, so that [Arena::alloc] reuses them
+/// before moving on to slots which have never been used.
+///
+/// The arena's state is kept in the [StateCell] `C`, which is a [RefCell] by
+/// default, or a [RwLock] for an arena which is [Sync]. Its usage statistics
+/// are kept outside of `C`, so that they can be read during any access, in
+/// counters of type `K`, which are atomic only when `C` is a [RwLock].
 #[derive(Debug)]
-pub struct IndexableMapArena<T, M>(RefCell<IMInner<T, M>>);
+pub struct IndexableMapArena<
+    T,
+    M,
+    I = u16,
+    C = RefCell<IMInner<T, M, I>>,
+    K = Cell<usize>,
+>(C, ArenaCounters<K>, PhantomData<(T, M, I)>);
+
+/// An [IndexableMapArena] whose state is guarded by a spin lock and whose
+/// usage statistics are atomic, so that it is [Sync].
+pub type LockedArena<T, M, I> =
+    IndexableMapArena<T, M, I, RwLock<IMInner<T, M, I>>, AtomicUsize>;
 
+/// The state of an [IndexableMapArena].
 #[derive(Debug)]
-struct IMInner<T, M> {
-    next_index: u16,
+pub struct IMInner<T, M, I> {
+    next_index: I,
+    free_head: Option<I>,
+    in_transaction: bool,
+    /// Whether the arena has been compacted without being truncated since, so
+    /// that it may still hold forwarding entries.
+    compacting: bool,
+    /// The most recent slot allocated or taken during the open transaction,
+    /// which heads the log of every such slot.
+    log_head: Option<I>,
+    /// The generation which a slot starts with when it is created, which is
+    /// above that of every slot dropped by [Arena::truncate] or
+    /// [IndexableMapArena::reset], so that no [Index] to a dropped slot
+    /// matches an item later put in its place.
+    fresh_generation: I,
     map: M,
     phantom: PhantomData<T>,
 }
 
-impl<T, M: IndexableMap<T>> IndexableMapArena<T, M> {
+impl<T, M, I: IndexWidth> IMInner<T, M, I> {
+    const fn new(map: M) -> Self {
+        Self {
+            next_index: I::ZERO,
+            free_head: None,
+            in_transaction: false,
+            compacting: false,
+            log_head: None,
+            fresh_generation: I::ZERO,
+            map,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T, I: IndexWidth, M: IndexableMap<T, I>> IMInner<T, M, I> {
+    /// Indexes the map at the given `index`, returning
+    /// [ArenaError::IndexOutOfBounds] if the slot has never been allocated.
+    fn slot(&mut self, index: I) -> ArenaResult<&mut Slot<T, I>> {
+        if index >= self.next_index {
+            return Err(ArenaError::IndexOutOfBounds);
+        }
+        self.map
+            .get_slot(index)
+            .ok_or(ArenaError::IndexOutOfBounds)
+    }
+
+    /// Indexes the map at the given `index` as in [IMInner::slot], but
+    /// immutably.
+    fn slot_ref(&self, index: I) -> ArenaResult<&Slot<T, I>> {
+        if index >= self.next_index {
+            return Err(ArenaError::IndexOutOfBounds);
+        }
+        self.map
+            .get_slot_ref(index)
+            .ok_or(ArenaError::IndexOutOfBounds)
+    }
+
+    /// Indexes the map at the given `index` as in [IMInner::slot_ref], but
+    /// also returns [ArenaError::StaleIndex] if the slot has been reused since
+    /// `index` was allocated.
+    fn checked_slot_ref(
+        &self,
+        index: &Index<T, I>,
+    ) -> ArenaResult<&Slot<T, I>> {
+        let slot = self.slot_ref(index.inner())?;
+        if slot.generation != index.generation() {
+            return Err(ArenaError::StaleIndex);
+        }
+        if let Entry::Moved { .. } = slot.entry {
+            // The index must be forwarded before it can be used again.
+            return Err(ArenaError::StaleIndex);
+        }
+        Ok(slot)
+    }
+
+    /// Indexes the map at the given `index` as in [IMInner::checked_slot_ref],
+    /// but mutably.
+    fn checked_slot(
+        &mut self,
+        index: &Index<T, I>,
+    ) -> ArenaResult<&mut Slot<T, I>> {
+        self.checked_slot_ref(index)?;
+        self.slot(index.inner())
+    }
+
+    /// Sets the `prev` link of the free slot at `index`, if there is one.
+    fn set_prev(
+        &mut self,
+        index: Option<I>,
+        link: Option<I>,
+    ) -> ArenaResult<()> {
+        let Some(index) = index else {
+            return Ok(());
+        };
+        match &mut self.slot(index)?.entry {
+            Entry::Free { prev, .. } => *prev = link,
+            Entry::Full(_)
+            | Entry::Pending { .. }
+            | Entry::Held { .. }
+            | Entry::Moved { .. } => {
+                return Err(ArenaError::ExpectedFreeSlot);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the `next` link of the free slot at `index`, or the head of the
+    /// free list if `index` is `None`.
+    fn set_next(
+        &mut self,
+        index: Option<I>,
+        link: Option<I>,
+    ) -> ArenaResult<()> {
+        let Some(index) = index else {
+            self.free_head = link;
+            return Ok(());
+        };
+        match &mut self.slot(index)?.entry {
+            Entry::Free { next, .. } => *next = link,
+            Entry::Full(_)
+            | Entry::Pending { .. }
+            | Entry::Held { .. }
+            | Entry::Moved { .. } => {
+                return Err(ArenaError::ExpectedFreeSlot);
+            }
+        }
+        Ok(())
+    }
+
+    /// Empties the full slot at `index`, pushing it onto the free list and
+    /// returning its value.
+    fn free(&mut self, index: &Index<T, I>) -> ArenaResult<T> {
+        let old_head = self.free_head;
+        let slot = self.checked_slot(index)?;
+        let freed = Entry::Free { prev: None, next: old_head };
+        let (value, pending_next) = match mem::replace(&mut slot.entry, freed) {
+            Entry::Full(value) => (value, None),
+            Entry::Pending { value, next } => (value, Some(next)),
+            free => {
+                slot.entry = free;
+                return Err(ArenaError::ExpectedFullSlot);
+            }
+        };
+        let inner_index = index.inner();
+        if let Some(next) = pending_next {
+            self.unlink_logged(inner_index, next)?;
+        }
+        self.set_prev(old_head, Some(inner_index))?;
+        self.free_head = Some(inner_index);
+        Ok(value)
+    }
+
+    /// Removes the slot at `index`, whose link is `next`, from the log of the
+    /// open transaction.
+    fn unlink_logged(&mut self, index: I, next: Option<I>) -> ArenaResult<()> {
+        if self.log_head == Some(index) {
+            self.log_head = next;
+            return Ok(());
+        }
+        let mut current = self.log_head;
+        while let Some(current_index) = current {
+            let (Entry::Pending { next: link, .. }
+            | Entry::Held { next: link, .. }) =
+                &mut self.slot(current_index)?.entry
+            else {
+                break;
+            };
+            if *link == Some(index) {
+                *link = next;
+                return Ok(());
+            }
+            current = *link;
+        }
+        Err(ArenaError::ExpectedFullSlot)
+    }
+
+    /// Fills the free slot at `index` with `value`, unlinking it from the free
+    /// list, and returns the slot's generation, or gives back `value` on
+    /// failure. If `pending` is true, the slot is added to the log of the open
+    /// transaction.
+    fn fill(
+        &mut self,
+        index: I,
+        value: T,
+        pending: bool,
+    ) -> Result<I, (ArenaError, T)> {
+        let links = match self.slot(index).map(|slot| &slot.entry) {
+            Ok(Entry::Free { prev, next }) => Ok((*prev, *next)),
+            Ok(_) => Err(ArenaError::ExpectedFreeSlot),
+            Err(error) => Err(error),
+        };
+        let log_head = self.log_head;
+        let unlinked = links.and_then(|(prev, next)| {
+            self.set_next(prev, next)?;
+            self.set_prev(next, prev)?;
+            self.slot(index)
+        });
+        let slot = match unlinked {
+            Ok(slot) => slot,
+            Err(error) => return Err((error, value)),
+        };
+        if pending {
+            slot.entry = Entry::Pending { value, next: log_head };
+        } else {
+            slot.entry = Entry::Full(value);
+        }
+        let generation = slot.generation;
+        if pending {
+            self.log_head = Some(index);
+        }
+        Ok(generation)
+    }
+
+    /// Raises the generation which new slots start with above that of every
+    /// slot at or above `len`, before they are dropped.
+    fn retire_from(&mut self, len: usize) {
+        let slots = (len..self.next_index.to_usize()).filter_map(|index| {
+            self.map
+                .get_slot_ref(I::from_usize(index)?)
+        });
+        for slot in slots {
+            let generation = slot.generation.wrapping_increment();
+            self.fresh_generation = self.fresh_generation.max(generation);
+        }
+    }
+
+    /// Finds a free slot for a new value, which is the head of the free list
+    /// if there is one, and otherwise a slot which has never been used.
+    fn vacant(&mut self) -> ArenaResult<I> {
+        if let Some(free_index) = self.free_head {
+            // Reusing the slot invalidates every index to its previous value.
+            let slot = self.slot(free_index)?;
+            slot.generation = slot.generation.wrapping_increment();
+            return Ok(free_index);
+        }
+        let index = self.next_index;
+        // The largest index of the width is never handed out, as there would
+        // be no next index to progress to.
+        let next_index = index
+            .checked_increment()
+            .filter(|_| index != I::MAX)
+            .ok_or(ArenaError::LimitReached)?;
+        let slot = self.map.new_slot(index)?;
+        if slot.is_full() {
+            return Err(ArenaError::ExpectedFreeSlot);
+        }
+        slot.generation = self.fresh_generation;
+        // The new slot is unlinked, so it forms the whole free list.
+        self.next_index = next_index;
+        self.free_head = Some(index);
+        Ok(index)
+    }
+
+    /// Keeps the value of the full slot at `index` as the original of an
+    /// [Entry::Held], if the value was held before the open transaction, so
+    /// that the slot can be mutated or taken and later put back by
+    /// [Arena::rollback]. Does nothing if no transaction is open.
+    fn hold(&mut self, index: &Index<T, I>) -> ArenaResult<()>
+    where
+        T: Clone,
+    {
+        if !self.in_transaction {
+            return Ok(());
+        }
+        let log_head = self.log_head;
+        let slot = self.checked_slot(index)?;
+        let Entry::Full(value) = &slot.entry else {
+            return Ok(());
+        };
+        let copy = value.clone();
+        let unlinked = Entry::Free { prev: None, next: None };
+        if let Entry::Full(original) = mem::replace(&mut slot.entry, unlinked) {
+            let value = Some(copy);
+            slot.entry = Entry::Held { original, value, next: log_head };
+        }
+        self.log_head = Some(index.inner());
+        Ok(())
+    }
+
+    /// Takes the value out of the full slot at `index` as in [IMInner::free],
+    /// unless the value was held before the open transaction, in which case
+    /// the slot keeps the original as in [IMInner::hold].
+    fn take(&mut self, index: &Index<T, I>) -> ArenaResult<T>
+    where
+        T: Clone,
+    {
+        self.hold(index)?;
+        if let Entry::Held { value, .. } = &mut self.checked_slot(index)?.entry
+        {
+            return value
+                .take()
+                .ok_or(ArenaError::ExpectedFullSlot);
+        }
+        self.free(index)
+    }
+
+    /// Closes the open transaction, keeping the change made to every slot in
+    /// its log as in [IMInner::commit_logged].
+    ///
+    /// A log which cannot be followed, which only a broken [IndexableMap]
+    /// could cause, is cut short where it breaks, leaving the slots past that
+    /// point as they are.
+    fn commit_all(&mut self) {
+        self.in_transaction = false;
+        while let Some(index) = self.log_head {
+            if self.commit_logged(index).is_err() {
+                self.log_head = None;
+            }
+        }
+    }
+
+    /// Keeps the change made to the most recent slot in the log of the open
+    /// transaction, removing it from the log: a pending or held value becomes
+    /// full, and the slot of a taken value is freed.
+    fn commit_logged(&mut self, index: I) -> ArenaResult<()> {
+        let old_head = self.free_head;
+        let slot = self.slot(index)?;
+        let freed = Entry::Free { prev: None, next: old_head };
+        match mem::replace(&mut slot.entry, freed) {
+            Entry::Pending { value, next }
+            | Entry::Held { value: Some(value), next, .. } => {
+                slot.entry = Entry::Full(value);
+                self.log_head = next;
+            }
+            Entry::Held { value: None, next, .. } => {
+                self.log_head = next;
+                self.set_prev(old_head, Some(index))?;
+                self.free_head = Some(index);
+            }
+            entry => {
+                slot.entry = entry;
+                return Err(ArenaError::ExpectedFullSlot);
+            }
+        }
+        Ok(())
+    }
+
+    /// Undoes the change made to the most recent slot in the log of the open
+    /// transaction, removing it from the log: a pending value is dropped,
+    /// freeing its slot, and a held slot gets its original value back.
+    fn roll_back_logged(&mut self, index: I) -> ArenaResult<Undone> {
+        let slot = self.slot(index)?;
+        if let Entry::Held { value, next, .. } = &slot.entry {
+            let undone = match value {
+                Some(_) => Undone::Restored,
+                None => Undone::Refilled,
+            };
+            let next = *next;
+            let unlinked = Entry::Free { prev: None, next: None };
+            if let Entry::Held { original, .. } =
+                mem::replace(&mut slot.entry, unlinked)
+            {
+                slot.entry = Entry::Full(original);
+            }
+            self.log_head = next;
+            return Ok(undone);
+        }
+        // Freeing the slot also removes it from the log.
+        let generation = slot.generation;
+        self.free(&Index::new(index, generation))?;
+        Ok(Undone::Freed)
+    }
+
+    /// Returns [ArenaError::Compacting] if the arena still holds forwarding
+    /// entries, which must be truncated before the arena is changed.
+    const fn check_not_compacting(&self) -> ArenaResult<()> {
+        if self.compacting {
+            return Err(ArenaError::Compacting);
+        }
+        Ok(())
+    }
+
+    /// Moves the value at `from` into the free slot at `to`, leaving behind a
+    /// forwarding entry in its place. On failure, the value is left at `from`.
+    fn relocate(&mut self, from: I, to: I) -> ArenaResult<()> {
+        let slot = self.slot(from)?;
+        let moved = Entry::Moved { to, generation: I::ZERO };
+        let value = match mem::replace(&mut slot.entry, moved) {
+            Entry::Full(value) => value,
+            entry => {
+                slot.entry = entry;
+                return Err(ArenaError::ExpectedFullSlot);
+            }
+        };
+        if let Err((error, value)) = self.fill(to, value, false) {
+            self.slot(from)?.entry = Entry::Full(value);
+            return Err(error);
+        }
+        // As with a reused slot, the destination's old indices are now stale.
+        let slot = self.slot(to)?;
+        slot.generation = slot.generation.wrapping_increment();
+        let new_generation = slot.generation;
+        if let Entry::Moved { generation, .. } = &mut self.slot(from)?.entry {
+            *generation = new_generation;
+        }
+        Ok(())
+    }
+}
+
+/// How undoing a slot of a transaction changed whether the slot is full.
+enum Undone {
+    /// A value allocated during the transaction was dropped.
+    Freed,
+    /// A value taken during the transaction was put back.
+    Refilled,
+    /// A value mutated during the transaction was put back as it was.
+    Restored,
+}
+
+/// Converts a position counted as a [usize] back to the arena's index width,
+/// returning [ArenaError::IndexOutOfBounds] if it does not fit.
+fn position<I: IndexWidth>(index: usize) -> ArenaResult<I> {
+    I::from_usize(index).ok_or(ArenaError::IndexOutOfBounds)
+}
+
+impl<T, I: IndexWidth, M: IndexableMap<T, I>> IndexableMapArena<T, M, I> {
     /// Creates an arena with the given [IndexableMap] backing field.
     #[allow(unused)]
-    pub fn new(map: M) -> Self {
-        Self(RefCell::new(IMInner { next_index: 0, map, phantom: PhantomData }))
+    pub const fn new(map: M) -> Self {
+        Self(RefCell::new(IMInner::new(map)), ArenaCounters::new(), PhantomData)
+    }
+}
+
+impl<T, I, M> LockedArena<T, M, I>
+where
+    I: IndexWidth,
+    M: IndexableMap<T, I>,
+{
+    /// Creates a [Sync] arena with the given [IndexableMap] backing field,
+    /// whose state is guarded by a spin lock.
+    #[allow(unused)]
+    pub const fn new_locked(map: M) -> Self {
+        Self(RwLock::new(IMInner::new(map)), ArenaCounters::new(), PhantomData)
+    }
+}
+
+impl<T, I, M, C, K> IndexableMapArena<T, M, I, C, K>
+where
+    I: IndexWidth,
+    M: IndexableMap<T, I>,
+    C: StateCell<IMInner<T, M, I>>,
+    K: Counter,
+{
+    /// Borrows the arena's state immutably, returning
+    /// [ArenaError::AlreadyBorrowed] if one of its items is being mutated.
+    fn inner(&self) -> ArenaResult<C::Ref<'_>> {
+        self.0
+            .try_borrow()
+            .ok_or(ArenaError::AlreadyBorrowed)
+    }
+
+    /// Borrows the arena's state mutably, returning
+    /// [ArenaError::AlreadyBorrowed] if one of its items is being accessed.
+    fn inner_mut(&self) -> ArenaResult<C::RefMut<'_>> {
+        self.0
+            .try_borrow_mut()
+            .ok_or(ArenaError::AlreadyBorrowed)
     }
 
-    /// Performs the given action using the arena's next index and map fields.
-    pub fn with_inner<U>(&self, f: impl FnOnce(&mut u16, &mut M) -> U) -> U {
-        let mut inner = self.0.borrow_mut();
-        let IMInner { next_index, map, phantom: _ } = inner.deref_mut();
-        f(next_index, map)
+    /// Empties the arena, dropping all items stored inside and forgetting all
+    /// free slots. Slots created afterwards start at a generation above that
+    /// of every dropped slot, so that no earlier [Index] matches them.
+    pub fn reset(&self) -> ArenaResult<()> {
+        let mut inner = self.inner_mut()?;
+        inner.retire_from(0);
+        inner.next_index = I::ZERO;
+        inner.free_head = None;
+        inner.in_transaction = false;
+        inner.compacting = false;
+        inner.log_head = None;
+        inner.map.clear();
+        self.1.record_clear();
+        Ok(())
     }
 
-    /// Indexes the arena's map at the given `index` (returning
-    /// [ArenaError::IndexOutOfBounds] on failure), then performs the given
-    /// `action` on the slot (i.e., `&mut Option<T>`), then modifies the arena's
-    /// next index using `update_index`.
-    fn with_slot<U>(
+    /// Allocates `value` as in [Arena::alloc_or_return], without counting a
+    /// failure.
+    fn alloc_uncounted(
         &self,
-        index: Index<T>,
-        update_index: impl FnOnce(&mut u16),
-        action: impl FnOnce(&mut Option<T>) -> ArenaResult<U>,
-    ) -> ArenaResult<U> {
-        self.with_inner(|next_index, map| {
-            let entry = map
-                .get_slot(index.clone())
-                .ok_or(ArenaError::IndexOutOfBounds)?;
-            let result = action(entry);
-            update_index(next_index);
-            result
-        })
+        value: T,
+    ) -> Result<Index<T, I>, (ArenaError, T)> {
+        let mut inner = match self.inner_mut() {
+            Ok(inner) => inner,
+            Err(error) => return Err((error, value)),
+        };
+        if let Err(error) = inner.check_not_compacting() {
+            return Err((error, value));
+        }
+        let index = match inner.vacant() {
+            Ok(index) => index,
+            Err(error) => return Err((error, value)),
+        };
+        let pending = inner.in_transaction;
+        let generation = inner.fill(index, value, pending)?;
+        self.1.record_fill(true);
+        Ok(Index::new(index, generation))
     }
 }
 
-impl<T: ArenaItem, M: IndexableMap<T>> Arena<T> for IndexableMapArena<T, M> {
+impl<T, I, M, C, K> Arena<T, I> for IndexableMapArena<T, M, I, C, K>
+where
+    T: ArenaItem,
+    I: IndexWidth,
+    M: IndexableMap<T, I>,
+    C: StateCell<IMInner<T, M, I>>,
+    K: Counter,
+{
     fn size(&self) -> usize {
-        self.with_inner(|_, map| map.size())
-    }
-
-    fn alloc(&self, value: T) -> ArenaResult<Index<T>> {
-        let inner_next_index = self.with_inner(|next_index, _| *next_index);
-        let index = Index::new(inner_next_index);
-        if inner_next_index == u16::MAX {
-            // We can't progress to the next index
-            return Err(ArenaError::LimitReached);
-        }
-        self.with_slot(
-            index.clone(),
-            |ind| *ind += 1,
-            |entry| match entry {
-                Some(_) => Err(ArenaError::ExpectedFreeSlot),
-                None => {
-                    let _ = entry.insert(value);
-                    Ok(index)
+        self.1.occupied()
+    }
+
+    fn alloc_or_return(
+        &self,
+        value: T,
+    ) -> Result<Index<T, I>, (ArenaError, T)> {
+        self.alloc_uncounted(value)
+            .inspect_err(|(error, _)| self.1.record_failure(*error))
+    }
+
+    fn take(&self, index: Index<T, I>) -> ArenaResult<T> {
+        let mut inner = self.inner_mut()?;
+        inner.check_not_compacting()?;
+        let value = inner.take(&index)?;
+        self.1.record_take();
+        Ok(value)
+    }
+
+    fn has_slot(&self, index: Index<T, I>) -> ArenaResult<bool> {
+        self.inner()?
+            .checked_slot_ref(&index)
+            .map(Slot::is_full)
+    }
+
+    fn insert(&self, index: Index<T, I>, value: T) -> ArenaResult<()> {
+        let mut inner = self.inner_mut()?;
+        inner.check_not_compacting()?;
+        if let Entry::Held { value: taken @ None, .. } =
+            &mut inner.checked_slot(&index)?.entry
+        {
+            *taken = Some(value);
+            self.1.record_fill(false);
+            return Ok(());
+        }
+        let pending = inner.in_transaction;
+        inner
+            .fill(index.inner(), value, pending)
+            .map_err(|(error, _)| error)?;
+        self.1.record_fill(false);
+        Ok(())
+    }
+
+    fn with_ref(
+        &self,
+        index: Index<T, I>,
+        f: &mut dyn FnMut(&T),
+    ) -> ArenaResult<()> {
+        let inner = self.inner()?;
+        let value = inner
+            .checked_slot_ref(&index)?
+            .value()
+            .ok_or(ArenaError::ExpectedFullSlot)?;
+        f(value);
+        Ok(())
+    }
+
+    fn with_mut(
+        &self,
+        index: Index<T, I>,
+        f: &mut dyn FnMut(&mut T),
+    ) -> ArenaResult<()> {
+        let mut inner = self.inner_mut()?;
+        inner.hold(&index)?;
+        let value = inner
+            .checked_slot(&index)?
+            .value_mut()
+            .ok_or(ArenaError::ExpectedFullSlot)?;
+        f(value);
+        Ok(())
+    }
+
+    fn compact(&self) -> ArenaResult<()> {
+        let mut inner = self.inner_mut()?;
+        if inner.in_transaction {
+            return Err(ArenaError::TransactionOpen);
+        }
+        inner.check_not_compacting()?;
+        inner.compacting = true;
+        let live = inner.map.size();
+        let mut free_index = 0;
+        for index in live..inner.next_index.to_usize() {
+            let index = position(index)?;
+            if !inner.slot(index)?.is_full() {
+                continue;
+            }
+            // Every full slot above `live` has a free slot below `live`.
+            while inner
+                .slot_ref(position(free_index)?)?
+                .is_full()
+            {
+                free_index = free_index
+                    .checked_add(1)
+                    .ok_or(ArenaError::IndexOutOfBounds)?;
+            }
+            inner.relocate(index, position(free_index)?)?;
+        }
+        Ok(())
+    }
+
+    fn forward(&self, index: Index<T, I>) -> ArenaResult<Index<T, I>> {
+        let inner = self.inner()?;
+        let slot = inner.slot_ref(index.inner())?;
+        if slot.generation != index.generation() {
+            return Err(ArenaError::StaleIndex);
+        }
+        match slot.entry {
+            Entry::Full(_) | Entry::Pending { .. } => Ok(index),
+            Entry::Moved { to, generation } => Ok(Index::new(to, generation)),
+            Entry::Held { value: Some(_), .. } => Ok(index),
+            Entry::Free { .. } | Entry::Held { value: None, .. } => {
+                Err(ArenaError::ExpectedFullSlot)
+            }
+        }
+    }
+
+    fn truncate(&self) -> ArenaResult<()> {
+        let mut inner = self.inner_mut()?;
+        if inner.in_transaction {
+            return Err(ArenaError::TransactionOpen);
+        }
+        let mut len = 0;
+        for index in 0..inner.next_index.to_usize() {
+            if inner.slot(position(index)?)?.is_full() {
+                len = index.saturating_add(1);
+            }
+        }
+        // Rebuild the free list from the slots which are kept, in ascending
+        // order, turning any forwarding entries among them into free slots.
+        inner.free_head = None;
+        for index in (0..len).rev() {
+            let index = position(index)?;
+            let free_head = inner.free_head;
+            let slot = inner.slot(index)?;
+            match slot.entry {
+                Entry::Full(_) | Entry::Pending { .. } | Entry::Held { .. } => {
+                    continue;
                 }
-            },
-        )
+                Entry::Free { .. } => (),
+                Entry::Moved { .. } => {
+                    slot.generation = slot.generation.wrapping_increment();
+                }
+            }
+            slot.entry = Entry::Free { prev: None, next: free_head };
+            inner.set_prev(free_head, Some(index))?;
+            inner.free_head = Some(index);
+        }
+        inner.retire_from(len);
+        let len = position(len)?;
+        inner.next_index = len;
+        inner.map.truncate(len);
+        inner.compacting = false;
+        Ok(())
     }
 
-    fn take(&self, index: Index<T>) -> ArenaResult<T> {
-        let take_entry = |entry: &mut Option<T>| {
-            entry
-                .take()
-                .ok_or(ArenaError::ExpectedFullSlot)
-        };
-        self.with_slot(index, |_| (), take_entry)
+    fn begin(&self) -> ArenaResult<()> {
+        let mut inner = self.inner_mut()?;
+        if inner.in_transaction {
+            return Err(ArenaError::TransactionOpen);
+        }
+        inner.check_not_compacting()?;
+        inner.in_transaction = true;
+        Ok(())
+    }
+
+    fn commit(&self) -> ArenaResult<()> {
+        self.inner_mut()?.commit_all();
+        Ok(())
+    }
+
+    fn commit_with(
+        &self,
+        prepared: &mut dyn FnMut() -> ArenaResult<()>,
+    ) -> ArenaResult<()> {
+        let mut inner = self.inner_mut()?;
+        prepared()?;
+        inner.commit_all();
+        Ok(())
     }
 
-    fn has_slot(&self, index: Index<T>) -> ArenaResult<bool> {
-        self.with_slot(index, |_| (), |entry| Ok(entry.is_some()))
+    fn rollback(&self) -> ArenaResult<()> {
+        let mut inner = self.inner_mut()?;
+        inner.in_transaction = false;
+        while let Some(index) = inner.log_head {
+            match inner.roll_back_logged(index)? {
+                Undone::Freed => self.1.record_take(),
+                Undone::Refilled => self.1.record_fill(false),
+                Undone::Restored => (),
+            }
+        }
+        Ok(())
     }
 
-    fn insert(&self, index: Index<T>, value: T) -> ArenaResult<()> {
-        let insert_entry = |entry: &mut Option<T>| match entry {
-            Some(_) => Err(ArenaError::ExpectedFreeSlot),
-            None => {
-                let _ = entry.insert(value);
-                Ok(())
+    fn for_each_occupied(
+        &self,
+        f: &mut dyn FnMut(Index<T, I>, &T),
+    ) -> ArenaResult<()> {
+        let inner = self.inner()?;
+        let next_index = inner.next_index;
+        let full_slots = inner
+            .map
+            .full_slots_from(I::ZERO)
+            .take_while(|(index, _)| *index < next_index);
+        for (index, slot) in full_slots {
+            if let Some(value) = slot.value() {
+                f(Index::new(index, slot.generation), value);
             }
+        }
+        Ok(())
+    }
+
+    fn next_occupied(
+        &self,
+        position: usize,
+    ) -> ArenaResult<Option<Index<T, I>>> {
+        let Some(from) = I::from_usize(position) else {
+            return Ok(None);
         };
-        self.with_slot(index, |_| (), insert_entry)
+        let inner = self.inner()?;
+        let next = inner
+            .map
+            .full_slots_from(from)
+            .next()
+            .filter(|(index, _)| *index < inner.next_index)
+            .map(|(index, slot)| Index::new(index, slot.generation));
+        Ok(next)
+    }
+
+    fn stats(&self) -> ArenaStats {
+        let addressable = I::MAX.to_usize();
+        self.1
+            .snapshot(M::CAPACITY.min(addressable))
     }
 }
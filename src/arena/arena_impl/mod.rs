@@ -1,3 +1,136 @@
+/// Implements [Arena](crate::arena::Arena) for a newtype around an
+/// [IndexableMapArena](helpers::IndexableMapArena), by forwarding every method
+/// to the wrapped arena in field `0`.
+///
+/// The generic parameters of the impl are given in square brackets, followed
+/// by `Arena<T, I>` as imported at the call site and the newtype, and then any
+/// bounds in square brackets after `where`.
+macro_rules! forward_arena_impl {
+    (
+        impl[$($generics:tt)*]
+        $arena_trait:ident<$item:ident, $width:ident> for $arena:ty
+        $(where [$($bounds:tt)*])?
+    ) => {
+        impl<$($generics)*> $arena_trait<$item, $width> for $arena
+        $(where $($bounds)*)?
+        {
+            fn size(&self) -> usize {
+                self.0.size()
+            }
+
+            fn alloc_or_return(
+                &self,
+                value: $item,
+            ) -> Result<
+                $crate::arena::index::Index<$item, $width>,
+                ($crate::arena::error::ArenaError, $item),
+            > {
+                self.0.alloc_or_return(value)
+            }
+
+            fn take(
+                &self,
+                index: $crate::arena::index::Index<$item, $width>,
+            ) -> $crate::arena::error::ArenaResult<$item> {
+                self.0.take(index)
+            }
+
+            fn has_slot(
+                &self,
+                index: $crate::arena::index::Index<$item, $width>,
+            ) -> $crate::arena::error::ArenaResult<bool> {
+                self.0.has_slot(index)
+            }
+
+            fn insert(
+                &self,
+                index: $crate::arena::index::Index<$item, $width>,
+                value: $item,
+            ) -> $crate::arena::error::ArenaResult<()> {
+                self.0.insert(index, value)
+            }
+
+            fn with_ref(
+                &self,
+                index: $crate::arena::index::Index<$item, $width>,
+                f: &mut dyn FnMut(&$item),
+            ) -> $crate::arena::error::ArenaResult<()> {
+                self.0.with_ref(index, f)
+            }
+
+            fn with_mut(
+                &self,
+                index: $crate::arena::index::Index<$item, $width>,
+                f: &mut dyn FnMut(&mut $item),
+            ) -> $crate::arena::error::ArenaResult<()> {
+                self.0.with_mut(index, f)
+            }
+
+            fn compact(&self) -> $crate::arena::error::ArenaResult<()> {
+                self.0.compact()
+            }
+
+            fn forward(
+                &self,
+                index: $crate::arena::index::Index<$item, $width>,
+            ) -> $crate::arena::error::ArenaResult<
+                $crate::arena::index::Index<$item, $width>,
+            > {
+                self.0.forward(index)
+            }
+
+            fn truncate(&self) -> $crate::arena::error::ArenaResult<()> {
+                self.0.truncate()
+            }
+
+            fn begin(&self) -> $crate::arena::error::ArenaResult<()> {
+                self.0.begin()
+            }
+
+            fn commit(&self) -> $crate::arena::error::ArenaResult<()> {
+                self.0.commit()
+            }
+
+            fn commit_with(
+                &self,
+                prepared: &mut dyn FnMut() -> $crate::arena::error::ArenaResult<()>,
+            ) -> $crate::arena::error::ArenaResult<()> {
+                self.0.commit_with(prepared)
+            }
+
+            fn rollback(&self) -> $crate::arena::error::ArenaResult<()> {
+                self.0.rollback()
+            }
+
+            fn for_each_occupied(
+                &self,
+                f: &mut dyn FnMut(
+                    $crate::arena::index::Index<$item, $width>,
+                    &$item,
+                ),
+            ) -> $crate::arena::error::ArenaResult<()> {
+                self.0.for_each_occupied(f)
+            }
+
+            fn next_occupied(
+                &self,
+                position: usize,
+            ) -> $crate::arena::error::ArenaResult<
+                Option<$crate::arena::index::Index<$item, $width>>,
+            > {
+                self.0.next_occupied(position)
+            }
+
+            fn stats(&self) -> $crate::arena::stats::ArenaStats {
+                self.0.stats()
+            }
+        }
+    };
+}
+
 pub mod growable_arena;
-mod helpers;
+pub mod helpers;
 pub mod scapegoat_arena;
+pub mod spin_arena;
+mod state_cell;
+pub mod static_arena;
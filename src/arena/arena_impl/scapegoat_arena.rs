@@ -4,70 +4,105 @@ use crate::arena::Arena;
 use crate::arena::ArenaItem;
 use crate::arena::arena_impl::helpers::IndexableMap;
 use crate::arena::arena_impl::helpers::IndexableMapArena;
+use crate::arena::arena_impl::helpers::Slot;
+use crate::arena::error::ArenaError;
 use crate::arena::error::ArenaResult;
-use crate::arena::index::Index;
+use crate::arena::index::IndexWidth;
 
 /// An [Arena] that uses [scapegoat]'s backing structures for allocating
 /// structures without dynamic allocation.
+///
+/// Entries of the backing map are created on demand, so at most `N` items can
+/// be held at once, after which [Arena::alloc] returns
+/// [ArenaError::LimitReached] until an item is taken.
 #[derive(Debug)]
-pub struct ScapegoatArena<T: ArenaItem, const N: usize>(
-    IndexableMapArena<T, SgInnerMap<T, N>>,
+pub struct ScapegoatArena<T: ArenaItem, const N: usize, I: IndexWidth = u16>(
+    IndexableMapArena<T, SgInnerMap<T, N, I>, I>,
 );
 
+/// The backing map of a [ScapegoatArena], which holds at most `N` entries.
 #[derive(Debug)]
-struct SgInnerMap<T: ArenaItem, const N: usize>(
-    SgMap<Option<Index<T>>, Option<T>, N>,
+pub struct SgInnerMap<T: ArenaItem, const N: usize, I: IndexWidth = u16>(
+    SgMap<I, Slot<T, I>, N>,
 );
 
-impl<T: ArenaItem, const N: usize> IndexableMap<T> for SgInnerMap<T, N> {
-    fn size(&self) -> usize {
-        self.0.len()
+impl<T: ArenaItem, const N: usize, I: IndexWidth> SgInnerMap<T, N, I> {
+    /// Creates an empty map with room for `N` entries.
+    pub fn new() -> Self {
+        Self(SgMap::new())
     }
+}
 
-    fn get_slot(&mut self, index: Index<T>) -> Option<&mut Option<T>> {
-        self.0.get_mut(&Some(index))
+impl<T, const N: usize, I> IndexableMap<T, I> for SgInnerMap<T, N, I>
+where
+    T: ArenaItem,
+    I: IndexWidth,
+{
+    const CAPACITY: usize = N;
+
+    fn size(&self) -> usize {
+        self.0
+            .values()
+            .filter(|slot| slot.is_full())
+            .count()
     }
 
-    fn clear(&mut self) {
-        self.0.clear()
+    fn get_slot(&mut self, index: I) -> Option<&mut Slot<T, I>> {
+        self.0.get_mut(&index)
     }
-}
 
-impl<T: ArenaItem, const N: usize> ScapegoatArena<T, N> {
-    #[allow(unused)]
-    pub fn new() -> Self {
-        // Note that we are allowed to make an arena larger than u16::MAX slots
-        // (but we will never be able to allocate into the excess portion).
-        Self(IndexableMapArena::new(SgInnerMap(SgMap::new())))
+    fn get_slot_ref(&self, index: I) -> Option<&Slot<T, I>> {
+        self.0.get(&index)
     }
 
-    #[allow(unused)]
-    pub fn reset(&self) {
-        self.0.with_inner(|next_index, map| {
-            *next_index = 0;
-            map.clear();
-        })
+    fn new_slot(&mut self, index: I) -> ArenaResult<&mut Slot<T, I>> {
+        if !self.0.contains_key(&index) {
+            self.0
+                .try_insert(index, Slot::EMPTY)
+                .map_err(|_| ArenaError::LimitReached)?;
+        }
+        self.0
+            .get_mut(&index)
+            .ok_or(ArenaError::IndexOutOfBounds)
     }
-}
 
-impl<T: ArenaItem, const N: usize> Arena<T> for ScapegoatArena<T, N> {
-    fn size(&self) -> usize {
-        self.0.size()
+    fn full_slots_from<'a>(
+        &'a self,
+        from: I,
+    ) -> impl Iterator<Item = (I, &'a Slot<T, I>)>
+    where
+        T: 'a,
+    {
+        self.0
+            .range(from..)
+            .map(|(index, slot)| (*index, slot))
+            .filter(|(_, slot)| slot.is_full())
     }
 
-    fn alloc(&self, value: T) -> ArenaResult<Index<T>> {
-        self.0.alloc(value)
+    fn clear(&mut self) {
+        self.0.clear()
     }
 
-    fn take(&self, index: Index<T>) -> ArenaResult<T> {
-        self.0.take(index)
+    fn truncate(&mut self, len: I) {
+        self.0.retain(|index, _| *index < len)
     }
+}
 
-    fn has_slot(&self, index: Index<T>) -> ArenaResult<bool> {
-        self.0.has_slot(index)
+impl<T: ArenaItem, const N: usize, I: IndexWidth> ScapegoatArena<T, N, I> {
+    #[allow(unused)]
+    pub fn new() -> Self {
+        // Note that we are allowed to make an arena larger than `I::MAX` slots
+        // (but we will never be able to allocate into the excess portion).
+        Self(IndexableMapArena::new(SgInnerMap::new()))
     }
 
-    fn insert(&self, index: Index<T>, value: T) -> ArenaResult<()> {
-        self.0.insert(index, value)
+    #[allow(unused)]
+    pub fn reset(&self) -> ArenaResult<()> {
+        self.0.reset()
     }
 }
+
+forward_arena_impl!(
+    impl[T, const N: usize, I] Arena<T, I> for ScapegoatArena<T, N, I>
+    where [T: ArenaItem, I: IndexWidth]
+);
@@ -0,0 +1,80 @@
+use crate::arena::Arena;
+use crate::arena::ArenaItem;
+use crate::arena::arena_impl::growable_arena::GAMap;
+use crate::arena::arena_impl::helpers::IndexableMap;
+use crate::arena::arena_impl::helpers::IndexableMapArena;
+use crate::arena::arena_impl::helpers::LockedArena;
+use crate::arena::arena_impl::scapegoat_arena::SgInnerMap;
+use crate::arena::arena_impl::static_arena::SAMap;
+use crate::arena::error::ArenaResult;
+use crate::arena::index::IndexWidth;
+
+/// An [Arena] which is [Sync], so that it can be shared between threads, or
+/// between a main loop and its interrupt handlers.
+///
+/// The arena's state is guarded by a spin lock, which is only ever acquired
+/// without waiting. An access which conflicts with another in progress, such
+/// as an interrupt handler allocating while the code it interrupted is
+/// mutating an item, returns
+/// [AlreadyBorrowed](crate::arena::error::ArenaError::AlreadyBorrowed) rather
+/// than spinning, since spinning on a lock held by the interrupted code
+/// would never finish.
+#[derive(Debug)]
+pub struct SpinArena<T, M, I = u16>(LockedArena<T, M, I>)
+where
+    T: ArenaItem,
+    I: IndexWidth,
+    M: IndexableMap<T, I>;
+
+/// A [SpinArena] backed by a growable vector, as in a
+/// [GrowableArena](crate::arena::arena_impl::growable_arena::GrowableArena).
+pub type SpinGrowableArena<T, I = u16> = SpinArena<T, GAMap<T, I>, I>;
+
+/// A [SpinArena] backed by a [scapegoat] map of capacity `N`, as in a
+/// [ScapegoatArena](crate::arena::arena_impl::scapegoat_arena::ScapegoatArena).
+pub type SpinScapegoatArena<T, const N: usize, I = u16> =
+    SpinArena<T, SgInnerMap<T, N, I>, I>;
+
+/// A [SpinArena] backed by an array of `N` slots, as in a
+/// [StaticArena](crate::arena::arena_impl::static_arena::StaticArena), which
+/// can be placed in a `static`.
+pub type SpinStaticArena<T, const N: usize, I = u16> =
+    SpinArena<T, SAMap<T, N, I>, I>;
+
+impl<T, I, M> SpinArena<T, M, I>
+where
+    T: ArenaItem,
+    I: IndexWidth,
+    M: IndexableMap<T, I>,
+{
+    #[allow(unused)]
+    pub fn reset(&self) -> ArenaResult<()> {
+        self.0.reset()
+    }
+}
+
+impl<T: ArenaItem, I: IndexWidth> SpinGrowableArena<T, I> {
+    #[allow(unused)]
+    pub const fn new() -> Self {
+        Self(IndexableMapArena::new_locked(GAMap::new()))
+    }
+}
+
+impl<T: ArenaItem, const N: usize, I: IndexWidth> SpinScapegoatArena<T, N, I> {
+    #[allow(unused)]
+    pub fn new() -> Self {
+        Self(IndexableMapArena::new_locked(SgInnerMap::new()))
+    }
+}
+
+impl<T: ArenaItem, const N: usize, I: IndexWidth> SpinStaticArena<T, N, I> {
+    #[allow(unused)]
+    pub const fn new() -> Self {
+        Self(IndexableMapArena::new_locked(SAMap::new()))
+    }
+}
+
+forward_arena_impl!(
+    impl[T, I, M] Arena<T, I> for SpinArena<T, M, I>
+    where [T: ArenaItem, I: IndexWidth, M: IndexableMap<T, I>]
+);
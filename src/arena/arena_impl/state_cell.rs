@@ -0,0 +1,68 @@
+use core::cell::Ref;
+use core::cell::RefCell;
+use core::cell::RefMut;
+use core::ops::Deref;
+use core::ops::DerefMut;
+
+use spin::RwLock;
+use spin::RwLockReadGuard;
+use spin::RwLockWriteGuard;
+
+/// A cell guarding the state of an arena, which determines whether the arena
+/// can be shared between threads or interrupt contexts.
+///
+/// Borrows never wait or panic, so that an arena reports a conflicting access
+/// as an error in the same way, whichever cell it uses.
+pub trait StateCell<S> {
+    type Ref<'a>: Deref<Target = S>
+    where
+        Self: 'a;
+    type RefMut<'a>: DerefMut<Target = S>
+    where
+        Self: 'a;
+
+    /// Borrows the state immutably, returning `None` if it is exclusively
+    /// borrowed.
+    fn try_borrow(&self) -> Option<Self::Ref<'_>>;
+
+    /// Borrows the state mutably, returning `None` if it is borrowed.
+    fn try_borrow_mut(&self) -> Option<Self::RefMut<'_>>;
+}
+
+impl<S> StateCell<S> for RefCell<S> {
+    type Ref<'a>
+        = Ref<'a, S>
+    where
+        Self: 'a;
+    type RefMut<'a>
+        = RefMut<'a, S>
+    where
+        Self: 'a;
+
+    fn try_borrow(&self) -> Option<Self::Ref<'_>> {
+        RefCell::try_borrow(self).ok()
+    }
+
+    fn try_borrow_mut(&self) -> Option<Self::RefMut<'_>> {
+        RefCell::try_borrow_mut(self).ok()
+    }
+}
+
+impl<S> StateCell<S> for RwLock<S> {
+    type Ref<'a>
+        = RwLockReadGuard<'a, S>
+    where
+        Self: 'a;
+    type RefMut<'a>
+        = RwLockWriteGuard<'a, S>
+    where
+        Self: 'a;
+
+    fn try_borrow(&self) -> Option<Self::Ref<'_>> {
+        self.try_read()
+    }
+
+    fn try_borrow_mut(&self) -> Option<Self::RefMut<'_>> {
+        self.try_write()
+    }
+}
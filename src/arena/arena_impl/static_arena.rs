@@ -0,0 +1,165 @@
+use core::borrow::BorrowMut;
+use core::marker::PhantomData;
+
+use crate::arena::Arena;
+use crate::arena::ArenaItem;
+use crate::arena::arena_impl::helpers::IndexableMap;
+use crate::arena::arena_impl::helpers::IndexableMapArena;
+use crate::arena::arena_impl::helpers::Slot;
+use crate::arena::error::ArenaError;
+use crate::arena::error::ArenaResult;
+use crate::arena::index::IndexWidth;
+
+/// An [Arena] backed by a plain array of `N` slots, which uses neither the
+/// heap nor any tree structure.
+///
+/// Because it can be created in a `const` context, it can be placed in static
+/// memory, for example on a microcontroller without a heap. Once all `N` slots
+/// are full, [Arena::alloc] returns [ArenaError::LimitReached] until an item is
+/// taken.
+///
+/// The slots are held in the arena itself, unless [StaticArena::in_buffer]
+/// is given a buffer of slots to use instead, so that `S` is a
+/// `&'static mut [Slot<T, I>; N]`.
+#[derive(Debug)]
+pub struct StaticArena<
+    T: ArenaItem,
+    const N: usize,
+    I: IndexWidth = u16,
+    S: BorrowMut<[Slot<T, I>; N]> = [Slot<T, I>; N],
+>(IndexableMapArena<T, SAMap<T, N, I, S>, I>);
+
+/// The backing map of a [StaticArena], which is a plain array of `N` slots,
+/// either held in `S` itself or borrowed by it.
+#[derive(Debug)]
+pub struct SAMap<T, const N: usize, I = u16, S = [Slot<T, I>; N]>(
+    S,
+    PhantomData<[Slot<T, I>; N]>,
+);
+
+impl<T, const N: usize, I: IndexWidth> SAMap<T, N, I> {
+    /// Creates a map whose slots are all empty.
+    pub const fn new() -> Self {
+        Self([Slot::EMPTY; N], PhantomData)
+    }
+}
+
+impl<T, const N: usize, I, S> SAMap<T, N, I, S>
+where
+    I: IndexWidth,
+    S: BorrowMut<[Slot<T, I>; N]>,
+{
+    /// Creates a map in the slots of `slots`, emptying each of them first.
+    pub fn in_slots(mut slots: S) -> Self {
+        slots
+            .borrow_mut()
+            .iter_mut()
+            .for_each(|slot| *slot = Slot::EMPTY);
+        Self(slots, PhantomData)
+    }
+
+    fn slots(&self) -> &[Slot<T, I>; N] {
+        self.0.borrow()
+    }
+
+    fn slots_mut(&mut self) -> &mut [Slot<T, I>; N] {
+        self.0.borrow_mut()
+    }
+}
+
+impl<T, const N: usize, I, S> IndexableMap<T, I> for SAMap<T, N, I, S>
+where
+    I: IndexWidth,
+    S: BorrowMut<[Slot<T, I>; N]>,
+{
+    const CAPACITY: usize = N;
+
+    fn size(&self) -> usize {
+        self.slots()
+            .iter()
+            .filter(|slot| slot.is_full())
+            .count()
+    }
+
+    fn get_slot(&mut self, index: I) -> Option<&mut Slot<T, I>> {
+        self.slots_mut()
+            .get_mut(index.to_usize())
+    }
+
+    fn get_slot_ref(&self, index: I) -> Option<&Slot<T, I>> {
+        self.slots().get(index.to_usize())
+    }
+
+    fn new_slot(&mut self, index: I) -> ArenaResult<&mut Slot<T, I>> {
+        self.slots_mut()
+            .get_mut(index.to_usize())
+            .ok_or(ArenaError::LimitReached)
+    }
+
+    fn full_slots_from<'a>(
+        &'a self,
+        from: I,
+    ) -> impl Iterator<Item = (I, &'a Slot<T, I>)>
+    where
+        T: 'a,
+    {
+        self.slots()
+            .iter()
+            .enumerate()
+            .skip(from.to_usize())
+            .map_while(|(index, slot)| Some((I::from_usize(index)?, slot)))
+            .filter(|(_, slot)| slot.is_full())
+    }
+
+    fn clear(&mut self) {
+        self.slots_mut()
+            .iter_mut()
+            .for_each(|slot| *slot = Slot::EMPTY)
+    }
+
+    fn truncate(&mut self, len: I) {
+        self.slots_mut()
+            .iter_mut()
+            .skip(len.to_usize())
+            .for_each(|slot| *slot = Slot::EMPTY)
+    }
+}
+
+impl<T: ArenaItem, const N: usize, I: IndexWidth> StaticArena<T, N, I> {
+    #[allow(unused)]
+    pub const fn new() -> Self {
+        // Note that we are allowed to make an arena larger than `I::MAX` slots
+        // (but we will never be able to allocate into the excess portion).
+        Self(IndexableMapArena::new(SAMap::new()))
+    }
+}
+
+impl<T, const N: usize, I> StaticArena<T, N, I, &'static mut [Slot<T, I>; N]>
+where
+    T: ArenaItem,
+    I: IndexWidth,
+{
+    /// Creates an arena in the slots of `buffer`, such as a `static` array
+    /// placed in a particular region of memory, dropping whatever they held.
+    #[allow(unused)]
+    pub fn in_buffer(buffer: &'static mut [Slot<T, I>; N]) -> Self {
+        Self(IndexableMapArena::new(SAMap::in_slots(buffer)))
+    }
+}
+
+impl<T, const N: usize, I, S> StaticArena<T, N, I, S>
+where
+    T: ArenaItem,
+    I: IndexWidth,
+    S: BorrowMut<[Slot<T, I>; N]>,
+{
+    #[allow(unused)]
+    pub fn reset(&self) -> ArenaResult<()> {
+        self.0.reset()
+    }
+}
+
+forward_arena_impl!(
+    impl[T, const N: usize, I, S] Arena<T, I> for StaticArena<T, N, I, S>
+    where [T: ArenaItem, I: IndexWidth, S: BorrowMut<[Slot<T, I>; N]>]
+);
@@ -0,0 +1,188 @@
+//! Tuples of arenas tagged with a unique brand, so that an item created in one
+//! tuple cannot be used with another without a compile error.
+//!
+//! Each call to [with_brand] gives its closure a fresh, invariant lifetime
+//! `'id` which no other call can name, so a [Branded] item of one
+//! [BrandedArenas] does not type-check where an item of another is expected.
+//! The unbranded [ArenaHandler] methods stay available for tuples which are
+//! only chosen at run time, through [BrandedArenas::arenas] and
+//! [Branded::into_inner].
+
+use core::marker::PhantomData;
+
+use super::equality::ArenaEq;
+use super::error::ArenaResult;
+use super::handler::ArenaHandler;
+use super::transaction::TupleTransaction;
+use super::transaction::transaction;
+use super::transfer::MoveError;
+use super::tuple::DynArenasOf;
+
+/// A marker which is invariant in `'id`, so that two brands only unify if they
+/// were created by the same call to [with_brand].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Brand<'id>(PhantomData<fn(&'id ()) -> &'id ()>);
+
+/// An item created in the [BrandedArenas] of brand `'id`.
+///
+/// Unlike the item itself, a [Branded] item is not [Clone], since a shallow
+/// copy would share the slots of the original; see [BrandedArenas::try_clone].
+#[derive(Debug)]
+pub struct Branded<'id, T> {
+    item: T,
+    _brand: Brand<'id>,
+}
+
+impl<T> Branded<'_, T> {
+    /// Returns a reference to the item.
+    #[allow(unused)]
+    pub const fn get(&self) -> &T {
+        &self.item
+    }
+
+    /// Gives up the brand, after which the item must be passed to the arenas
+    /// it was created in by hand.
+    #[allow(unused)]
+    pub fn into_inner(self) -> T {
+        self.item
+    }
+}
+
+/// A tuple of arenas tagged with the brand `'id`, whose methods only accept
+/// items of the same brand.
+pub struct BrandedArenas<'id, 'a, T: ArenaHandler> {
+    arenas: &'a DynArenasOf<'a, T>,
+    brand: Brand<'id>,
+}
+
+/// Calls `f` with `arenas` tagged with a brand which is distinct from that of
+/// every other call.
+#[allow(unused)]
+pub fn with_brand<'a, T: ArenaHandler, U>(
+    arenas: &'a DynArenasOf<'a, T>,
+    f: impl for<'id> FnOnce(BrandedArenas<'id, 'a, T>) -> U,
+) -> U {
+    f(BrandedArenas { arenas, brand: Brand(PhantomData) })
+}
+
+impl<'id, 'a, T: ArenaHandler> BrandedArenas<'id, 'a, T> {
+    /// Returns the unbranded arenas.
+    #[allow(unused)]
+    pub fn arenas(&self) -> &'a DynArenasOf<'a, T> {
+        self.arenas
+    }
+
+    /// Tags `item` with the brand of these arenas, which it must have been
+    /// created in.
+    ///
+    /// This is the one place where the brand is taken on trust; prefer
+    /// [BrandedArenas::build] where the item is created here.
+    #[allow(unused)]
+    pub fn adopt(&self, item: T) -> Branded<'id, T> {
+        Branded { item, _brand: self.brand }
+    }
+
+    /// Creates an item in these arenas with `f`, inside a [transaction], so
+    /// that everything it allocates is taken out again if it fails.
+    #[allow(unused)]
+    pub fn build(
+        &self,
+        f: impl FnOnce(&DynArenasOf<'a, T>) -> ArenaResult<T>,
+    ) -> ArenaResult<Branded<'id, T>>
+    where
+        DynArenasOf<'a, T>: TupleTransaction,
+    {
+        transaction(self.arenas, f).map(|item| self.adopt(item))
+    }
+
+    /// Clones `item` within these arenas, as in
+    /// [ArenaHandler::try_clone_in].
+    #[allow(unused)]
+    pub fn try_clone(
+        &self,
+        item: &Branded<'id, T>,
+    ) -> ArenaResult<Branded<'id, T>> {
+        item.item
+            .try_clone_in(self.arenas)
+            .map(|item| self.adopt(item))
+    }
+
+    /// Drops `item` from these arenas, as in [ArenaHandler::try_drop_in].
+    #[allow(unused)]
+    pub fn try_drop(&self, item: Branded<'id, T>) -> ArenaResult<()> {
+        item.item.try_drop_in(self.arenas)
+    }
+
+    /// Compares `this` and `other`, both created in these arenas, as in
+    /// [ArenaEq::try_eq_in].
+    #[allow(unused)]
+    pub fn try_eq(
+        &self,
+        this: &Branded<'id, T>,
+        other: &Branded<'id, T>,
+    ) -> ArenaResult<bool>
+    where
+        T: ArenaEq,
+    {
+        ArenaEq::try_eq_in(&this.item, &other.item, self.arenas, self.arenas)
+    }
+
+    /// Compares `this`, created in these arenas, with `other`, created in
+    /// `other_arenas`, as in [ArenaEq::try_eq_in].
+    #[allow(unused)]
+    pub fn try_eq_across<'other, 'b>(
+        &self,
+        this: &Branded<'id, T>,
+        other_arenas: &BrandedArenas<'other, 'b, T>,
+        other: &Branded<'other, T>,
+    ) -> ArenaResult<bool>
+    where
+        T: ArenaEq,
+    {
+        ArenaEq::try_eq_in(
+            &this.item,
+            &other.item,
+            self.arenas,
+            other_arenas.arenas,
+        )
+    }
+
+    /// Copies `item` into the `destination` arenas, as in
+    /// [ArenaHandler::try_copy_to].
+    #[allow(unused)]
+    pub fn try_copy_to<'other, 'b>(
+        &self,
+        item: &Branded<'id, T>,
+        destination: &BrandedArenas<'other, 'b, T>,
+    ) -> ArenaResult<Branded<'other, T>> {
+        item.item
+            .try_copy_to(self.arenas, destination.arenas)
+            .map(|item| destination.adopt(item))
+    }
+
+    /// Moves `item` into the `destination` arenas, as in
+    /// [ArenaHandler::try_move_to], giving it back if it cannot be copied, or
+    /// giving back its copy if it cannot then be dropped.
+    #[allow(unused)]
+    pub fn try_move_to<'other, 'b>(
+        &self,
+        item: Branded<'id, T>,
+        destination: &BrandedArenas<'other, 'b, T>,
+    ) -> Result<
+        Branded<'other, T>,
+        MoveError<Branded<'id, T>, Branded<'other, T>>,
+    > {
+        match item
+            .item
+            .try_move_to(self.arenas, destination.arenas)
+        {
+            Ok(copy) => Ok(destination.adopt(copy)),
+            Err(MoveError::NotCopied(error, item)) => {
+                Err(MoveError::NotCopied(error, self.adopt(item)))
+            }
+            Err(MoveError::NotReleased(error, copy)) => {
+                Err(MoveError::NotReleased(error, destination.adopt(copy)))
+            }
+        }
+    }
+}
@@ -1,15 +1,31 @@
+use core::cmp::Ordering;
+use core::fmt;
+use core::fmt::Formatter;
+use core::hash::Hash;
+use core::hash::Hasher;
+
+use super::ArenaItem;
+use super::error::ArenaResult;
 use super::index::Index;
+use super::index::IndexWidth;
+use super::node::ArenaNode;
+use super::node::Holds;
+use super::node::unwrap_index;
+use super::validation::Link;
 
 #[allow(unused)]
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 /// A type used to create a singly-linked arena-allocated list of items of type
 /// `T`, where the `next` pointer is an [Index] to the same [Chain] type.
-pub enum Chain<T> {
-    Cons { head: Index<T>, tail: Index<Self> },
+///
+/// The `head` points into an arena of index width `I`, and the `tail` into the
+/// arena of the chain itself, of index width `C`.
+pub enum Chain<T, I: IndexWidth = u16, C: IndexWidth = u16> {
+    Cons { head: Index<T, I>, tail: Index<Self, C> },
     Nil,
 }
 
-impl<T> Clone for Chain<T> {
+impl<T, I: IndexWidth, C: IndexWidth> Clone for Chain<T, I, C> {
     fn clone(&self) -> Self {
         match self {
             Self::Cons { head, tail } => {
@@ -20,8 +36,76 @@ impl<T> Clone for Chain<T> {
     }
 }
 
+/// A cell is reached through the `tail` of the cell before it, and copied
+/// into a [Chain::Nil] placeholder, so that following a long chain never
+/// recurses.
+impl<T, I, C, P> ArenaNode<P> for Chain<T, I, C>
+where
+    T: ArenaItem,
+    I: IndexWidth,
+    C: IndexWidth,
+    P: Holds<T, I> + Holds<Self, C>,
+{
+    const LINK: Link = Link::Tail;
+    const CHILDREN: usize = 2;
+
+    fn placeholder() -> Option<Self> {
+        Some(Self::Nil)
+    }
+
+    fn for_each_child(
+        &self,
+        f: &mut dyn FnMut(P) -> ArenaResult<()>,
+    ) -> ArenaResult<()> {
+        if let Self::Cons { head, tail } = self {
+            f(<P as Holds<T, I>>::wrap(head.clone()))?;
+            f(<P as Holds<Self, C>>::wrap(tail.clone()))?;
+        }
+        Ok(())
+    }
+
+    fn map_children(
+        &mut self,
+        f: &mut dyn FnMut(P) -> ArenaResult<P>,
+    ) -> ArenaResult<()> {
+        if let Self::Cons { head, tail } = self {
+            *head = unwrap_index(f(<P as Holds<T, I>>::wrap(head.clone()))?)?;
+            *tail =
+                unwrap_index(f(<P as Holds<Self, C>>::wrap(tail.clone()))?)?;
+        }
+        Ok(())
+    }
+
+    fn shallow_hash(&self, mut state: &mut dyn Hasher) {
+        // Marks the end of each chain, so that the chains of nested items
+        // cannot be told apart only by their lengths.
+        matches!(self, Self::Cons { .. }).hash(&mut state);
+    }
+
+    fn shallow_eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::Cons { .. }, Self::Cons { .. }) | (Self::Nil, Self::Nil)
+        )
+    }
+
+    fn shallow_cmp(&self, other: &Self) -> Ordering {
+        // The end of a chain comes first, so that chains are ordered by their
+        // items in turn, with a chain before any longer one it begins.
+        let is_cons = |chain: &Self| matches!(chain, Self::Cons { .. });
+        is_cons(self).cmp(&is_cons(other))
+    }
+
+    fn shallow_fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cons { .. } => f.write_str("Cons"),
+            Self::Nil => f.write_str("Nil"),
+        }
+    }
+}
+
 #[derive(Debug)]
-pub enum ChainOrIndex<T> {
-    Chain(Chain<T>),
-    Index(Index<Chain<T>>),
+pub enum ChainOrIndex<T, I: IndexWidth = u16, C: IndexWidth = u16> {
+    Chain(Chain<T, I, C>),
+    Index(Index<Chain<T, I, C>, C>),
 }
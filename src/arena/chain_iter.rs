@@ -7,6 +7,7 @@ use crate::arena::ArenaItem;
 use crate::arena::chain::Chain;
 use crate::arena::chain::ChainOrIndex;
 use crate::arena::extension::Inspect;
+use crate::arena::index::IndexWidth;
 
 enum ItemFunction<T, U, RefFunc, MutFunc> {
     ByRef(RefFunc, PhantomData<T>, PhantomData<U>),
@@ -23,14 +24,20 @@ impl<T, U, RefFunc, MutFunc> Debug for ItemFunction<T, U, RefFunc, MutFunc> {
 }
 
 #[derive(Debug)]
-struct Iter<'a, T, U, HeadA: ?Sized, ChainA: ?Sized, RefFunc, MutFunc> {
-    chain_or_index: ChainOrIndex<T>,
+struct Iter<'a, T, I, C, U, HeadA: ?Sized, ChainA: ?Sized, RefFunc, MutFunc>
+where
+    I: IndexWidth,
+    C: IndexWidth,
+{
+    chain_or_index: ChainOrIndex<T, I, C>,
     head_arena: &'a HeadA,
     chain_arena: &'a ChainA,
     func: ItemFunction<T, U, RefFunc, MutFunc>,
 }
 
-impl<T: ArenaItem> Chain<T> {
+impl<T: ArenaItem, I: IndexWidth, C: IndexWidth> Chain<T, I, C> {
+    /// Iterates over the heads of this chain, mapping each one in place with
+    /// `func`, during which `head_arena` may only be read.
     #[allow(unused)]
     pub fn iter<'a, U, HeadA, ChainA>(
         &'a self,
@@ -39,8 +46,8 @@ impl<T: ArenaItem> Chain<T> {
         func: impl FnMut(&T) -> U,
     ) -> impl Iterator<Item = U>
     where
-        HeadA: Arena<T> + ?Sized,
-        ChainA: Arena<Chain<T>> + ?Sized,
+        HeadA: Arena<T, I> + ?Sized,
+        ChainA: Arena<Self, C> + ?Sized,
     {
         let chain_or_index = ChainOrIndex::Chain(self.clone());
         Iter {
@@ -55,6 +62,8 @@ impl<T: ArenaItem> Chain<T> {
         }
     }
 
+    /// Iterates over the heads of this chain, mapping each one in place with
+    /// `func`, during which `head_arena` must not be accessed.
     #[allow(unused)]
     pub fn iter_mut<'a, U, HeadA, ChainA>(
         &'a self,
@@ -63,8 +72,8 @@ impl<T: ArenaItem> Chain<T> {
         func: impl FnMut(&mut T) -> U,
     ) -> impl Iterator<Item = U>
     where
-        HeadA: Arena<T>,
-        ChainA: Arena<Chain<T>>,
+        HeadA: Arena<T, I>,
+        ChainA: Arena<Self, C>,
     {
         let chain_or_index = ChainOrIndex::Chain(self.clone());
         Iter {
@@ -80,12 +89,14 @@ impl<T: ArenaItem> Chain<T> {
     }
 }
 
-impl<'a, T, U, HeadA, ChainA, RefFunc, MutFunc> Iterator
-    for Iter<'a, T, U, HeadA, ChainA, RefFunc, MutFunc>
+impl<'a, T, I, C, U, HeadA, ChainA, RefFunc, MutFunc> Iterator
+    for Iter<'a, T, I, C, U, HeadA, ChainA, RefFunc, MutFunc>
 where
     T: ArenaItem,
-    HeadA: Arena<T> + ?Sized,
-    ChainA: Arena<Chain<T>> + ?Sized,
+    I: IndexWidth,
+    C: IndexWidth,
+    HeadA: Arena<T, I> + ?Sized,
+    ChainA: Arena<Chain<T, I, C>, C> + ?Sized,
     RefFunc: FnMut(&T) -> U,
     MutFunc: FnMut(&mut T) -> U,
 {
@@ -110,10 +121,10 @@ where
         };
         let inspected = match func {
             ItemFunction::ByRef(f, _, _) => {
-                <HeadA as Inspect<T>>::inspect(*head_arena, head, f)
+                <HeadA as Inspect<T, I>>::inspect(*head_arena, head, f)
             }
             ItemFunction::ByMut(f, _, _) => {
-                <HeadA as Inspect<T>>::inspect_mut(*head_arena, head, f)
+                <HeadA as Inspect<T, I>>::inspect_mut(*head_arena, head, f)
             }
         };
         let head_value = inspected.ok()?;
@@ -0,0 +1,83 @@
+//! Compaction of a whole tuple of arenas, rewriting the indices held by their
+//! items.
+
+use super::Arena;
+use super::ArenaItem;
+use super::error::ArenaResult;
+use super::handler::ArenaHandler;
+use super::index::IndexWidth;
+use super::tuple::DynArenasOf;
+use super::tuple::RightTuple;
+
+/// A [RightTuple] of dynamically-dispatched [Arena] references, such as an
+/// [ArenaHandler::DynArenas], whose arenas can be compacted together.
+#[allow(unused)]
+pub trait TupleCompact: RightTuple {
+    /// Calls [Arena::compact] on each arena in this tuple.
+    fn compact(&self) -> ArenaResult<()>;
+
+    /// Calls [Arena::truncate] on each arena in this tuple.
+    fn truncate(&self) -> ArenaResult<()>;
+}
+
+impl TupleCompact for () {
+    fn compact(&self) -> ArenaResult<()> {
+        Ok(())
+    }
+
+    fn truncate(&self) -> ArenaResult<()> {
+        Ok(())
+    }
+}
+
+impl<T, I, TS> TupleCompact for (&dyn Arena<T, I>, TS)
+where
+    T: ArenaItem,
+    I: IndexWidth,
+    TS: TupleCompact,
+{
+    fn compact(&self) -> ArenaResult<()> {
+        let (head, tail) = self;
+        head.compact()?;
+        tail.compact()
+    }
+
+    fn truncate(&self) -> ArenaResult<()> {
+        let (head, tail) = self;
+        head.truncate()?;
+        tail.truncate()
+    }
+}
+
+/// Compacts every arena in `arenas`, then rewrites the indices held by each of
+/// the `roots` and by every item reachable from them, and finally releases the
+/// storage freed by compaction.
+///
+/// Items which are not reachable from any of the `roots` are moved, but their
+/// indices are not rewritten, so they must already have been leaked.
+///
+/// Remapping fails only on an [Index](super::index::Index) which cannot be
+/// followed, on items nested too deeply, or on an arena which is borrowed. The
+/// roots are walked for the first two before anything is moved, returning the
+/// error with the arenas untouched, and a borrowed arena cannot be compacted,
+/// so no root is ever left half rewritten. Other threads must not access a
+/// shared arena meanwhile. If an arena cannot be compacted, the arenas may
+/// still hold forwarding entries, and should be reset.
+#[allow(unused)]
+pub fn compact_in<'a, T>(
+    roots: &mut [T],
+    arenas: &DynArenasOf<'a, T>,
+) -> ArenaResult<()>
+where
+    T: ArenaHandler,
+    DynArenasOf<'a, T>: TupleCompact,
+{
+    roots
+        .iter()
+        .try_for_each(|root| root.check_remap_in(arenas))?;
+    arenas.compact()?;
+    roots
+        .iter_mut()
+        .try_for_each(|root| root.remap_in(arenas))?;
+    arenas.truncate()
+}
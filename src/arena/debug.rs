@@ -0,0 +1,49 @@
+use core::fmt;
+use core::fmt::Debug;
+use core::fmt::Display;
+use core::fmt::Formatter;
+
+use super::handler::ArenaHandler;
+use super::tuple::DynArenasOf;
+
+/// A trait to format arena-allocated types by what their indices point to,
+/// rather than by the indices themselves.
+#[allow(unused)]
+pub trait ArenaDebug: ArenaHandler {
+    /// Writes this item into `f` as an indented tree, with a line for each
+    /// item which it points to in `arenas`, indented below the line of its
+    /// owner.
+    fn fmt_in(
+        &self,
+        arenas: &DynArenasOf<'_, Self>,
+        f: &mut Formatter<'_>,
+    ) -> fmt::Result;
+
+    /// Returns a value which formats this item with [ArenaDebug::fmt_in],
+    /// through either [Debug] or [Display], such as in an assertion message.
+    fn debug_in<'s, 'a>(
+        &'s self,
+        arenas: &'s DynArenasOf<'a, Self>,
+    ) -> DebugIn<'s, 'a, Self> {
+        DebugIn { item: self, arenas }
+    }
+}
+
+/// An item along with the arenas its indices point to, as returned by
+/// [ArenaDebug::debug_in].
+pub struct DebugIn<'s, 'a, T: ArenaHandler> {
+    item: &'s T,
+    arenas: &'s DynArenasOf<'a, T>,
+}
+
+impl<T: ArenaDebug> Debug for DebugIn<'_, '_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.item.fmt_in(self.arenas, f)
+    }
+}
+
+impl<T: ArenaDebug> Display for DebugIn<'_, '_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.item.fmt_in(self.arenas, f)
+    }
+}
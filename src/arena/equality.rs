@@ -1,10 +1,11 @@
+use super::error::ArenaResult;
 use super::handler::ArenaHandler;
 use super::tuple::DynArenasOf;
 
 /// A trait to define equality on arena-allocated types.
 #[allow(unused)]
 pub trait ArenaEq: ArenaHandler {
-    /// Returns true if and only if `this`, assuming its indices
+    /// Returns `Ok(true)` if and only if `this`, assuming its indices
     /// ([super::Index]) point to values in `this_arenas`,
     /// is taken to be equal to `other`, whose indices point to values in
     /// `other_arenas`.
@@ -13,10 +14,34 @@ pub trait ArenaEq: ArenaHandler {
     /// the same for `other` and `other_arenas`), but we cannot assume that
     /// simply because `this` and `other` refer to an equal index, that this
     /// means the values at that index in their corresponding arenas are equal.
-    fn eq_in<'a>(
-        this: &'a Self,
-        other: &'a Self,
+    ///
+    /// Returns an error if an index cannot be followed, or if the items are
+    /// nested too deeply to be compared.
+    fn try_eq_in<'a, 'b>(
+        this: &Self,
+        other: &Self,
+        this_arenas: &DynArenasOf<'a, Self>,
+        other_arenas: &DynArenasOf<'b, Self>,
+    ) -> ArenaResult<bool>;
+
+    /// Returns whether `this` and `other` are equal as in
+    /// [ArenaEq::try_eq_in].
+    ///
+    /// Panics if they cannot be compared, rather than taking them to be
+    /// different; see [ArenaEq::try_eq_in] for a version which does not panic.
+    ///
+    /// Since the two tuples of arenas can easily be swapped by mistake, see
+    /// [BrandedArenas::try_eq](super::brand::BrandedArenas::try_eq) for a
+    /// version which checks at compile time that each item is paired with its
+    /// own arenas.
+    #[cfg(any(test, not(feature = "no-panic")))]
+    fn eq_in<'a, 'b>(
+        this: &Self,
+        other: &Self,
         this_arenas: &DynArenasOf<'a, Self>,
-        other_arenas: &DynArenasOf<'a, Self>,
-    ) -> bool;
+        other_arenas: &DynArenasOf<'b, Self>,
+    ) -> bool {
+        Self::try_eq_in(this, other, this_arenas, other_arenas)
+            .expect("[ArenaEq::eq_in]: items should have been compared")
+    }
 }
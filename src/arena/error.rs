@@ -17,6 +17,29 @@ const INDEX_OUT_OF_BOUNDS: &str = "[Arena]: Index out of bounds";
 /// value into a slot ([Option]) which is empty, i.e. [None].
 const EXPECTED_FULL_SLOT: &str =
     "[Arena::take]: Arena slot should have been full at the given index";
+/// The message to use during an attempt to access a slot with an
+/// [super::Index] whose generation shows that the slot has since been freed
+/// and reused.
+const STALE_INDEX: &str =
+    "[Arena]: Index refers to a slot which has since been reused";
+/// The message to use during an attempt to access an [super::Arena] while
+/// one of its items is borrowed in a way which forbids that access.
+const ALREADY_BORROWED: &str =
+    "[Arena]: Arena is borrowed by an ongoing access to one of its items";
+/// The message to use during an attempt to open a transaction on, or compact,
+/// an [super::Arena] which already has an open transaction.
+const TRANSACTION_OPEN: &str =
+    "[Arena::begin]: Arena already has an open transaction";
+/// The message to use during an attempt to change an [super::Arena] which has
+/// been compacted but not yet truncated, since it still holds forwarding
+/// entries.
+const COMPACTING: &str =
+    "[Arena::compact]: Arena must be truncated before it is changed again";
+/// The message to use when a traversal of an item through its
+/// [super::handler::ArenaHandler] needs more pending steps than its
+/// [super::work_stack::WorkStack] can hold.
+const DEPTH_LIMIT_EXCEEDED: &str =
+    "[ArenaHandler]: Item is nested too deeply for its work stack";
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(unused)]
@@ -25,6 +48,11 @@ pub enum ArenaError {
     LimitReached,
     ExpectedFreeSlot,
     ExpectedFullSlot,
+    StaleIndex,
+    AlreadyBorrowed,
+    TransactionOpen,
+    Compacting,
+    DepthLimitExceeded,
 }
 
 pub type ArenaResult<T> = Result<T, ArenaError>;
@@ -36,6 +64,11 @@ impl Display for ArenaError {
             Self::LimitReached => LIMIT_REACHED,
             Self::ExpectedFreeSlot => EXPECTED_FREE_SLOT,
             Self::ExpectedFullSlot => EXPECTED_FULL_SLOT,
+            Self::StaleIndex => STALE_INDEX,
+            Self::AlreadyBorrowed => ALREADY_BORROWED,
+            Self::TransactionOpen => TRANSACTION_OPEN,
+            Self::Compacting => COMPACTING,
+            Self::DepthLimitExceeded => DEPTH_LIMIT_EXCEEDED,
         };
         f.write_str(msg)
     }
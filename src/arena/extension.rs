@@ -1,62 +1,165 @@
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+
 use super::Arena;
 use super::ArenaItem;
+use super::error::ArenaError;
 use super::error::ArenaResult;
 use super::index::Index;
+use super::index::IndexWidth;
 
 /// A helper trait for constructing a value from an array of indices after
 /// allocating (appending) values of that array's length into this [Arena].
 #[allow(unused)]
-pub trait AllocMany<T: ArenaItem>: Arena<T> {
+pub trait AllocMany<T: ArenaItem, I: IndexWidth = u16>: Arena<T, I> {
     /// Constructs the given value after allocating each of the array of values
     /// and obtaining all the corresponding [Index]es that
     /// key into the array.
+    ///
+    /// If any value cannot be allocated, the values which were allocated are
+    /// taken out again, and the first error is returned.
     fn alloc_many<const N: usize, U, F>(
         &mut self,
         values: [T; N],
-        constructor: impl FnOnce([Index<T>; N]) -> U,
+        constructor: impl FnOnce([Index<T, I>; N]) -> U,
     ) -> ArenaResult<U> {
-        let mut result = [const { None }; N];
-        values
-            .into_iter()
-            .enumerate()
-            .try_for_each(|(index, value)| {
-                result[index] = Some(self.alloc(value)?);
-                Ok(())
-            })?;
-        Ok(constructor(result.map(Option::unwrap)))
+        let mut error = None;
+        let result = values.map(|value| {
+            self.alloc(value)
+                .inspect_err(|e| _ = error.get_or_insert(*e))
+                .ok()
+        });
+        if let Some(error) = error {
+            for index in result.into_iter().flatten() {
+                let _ = self.take(index);
+            }
+            return Err(error);
+        }
+        // Every value was allocated, so the placeholder is never used.
+        let placeholder = || Index::new(I::ZERO, I::ZERO);
+        Ok(constructor(result.map(|index| index.unwrap_or_else(placeholder))))
     }
 }
 
-impl<T: ArenaItem, A: Arena<T> + ?Sized> AllocMany<T> for A {}
+impl<T, I, A> AllocMany<T, I> for A
+where
+    T: ArenaItem,
+    I: IndexWidth,
+    A: Arena<T, I> + ?Sized,
+{
+}
 
 /// A helper trait for keying into the `Arena` to get a temporary reference to
 /// the corresponding item, and mapping it to a specified type as given by the
 /// function passed in.
 /// Because the [Arena] is accessed immutably, for memory safety, the function
 /// needs to operate with a temporary value (lifetime) of `&T`.
+///
+/// The item stays in its slot throughout (see [Arena::with_ref] and
+/// [Arena::with_mut]), so the function must not allocate into or take from
+/// the same [Arena].
 #[allow(unused)]
-pub trait Inspect<T: ArenaItem>: Arena<T> {
+pub trait Inspect<T: ArenaItem, I: IndexWidth = u16>: Arena<T, I> {
     fn inspect<U>(
         &self,
-        index: Index<T>,
+        index: Index<T, I>,
         func: impl FnOnce(&T) -> U,
     ) -> ArenaResult<U> {
-        let x = self.take(index.clone())?;
-        let y = func(&x);
-        self.insert(index, x)?;
-        Ok(y)
+        let mut func = Some(func);
+        let mut result = None;
+        self.with_ref(index, &mut |x| result = func.take().map(|f| f(x)))?;
+        result.ok_or(ArenaError::ExpectedFullSlot)
     }
 
     fn inspect_mut<U>(
         &self,
-        index: Index<T>,
+        index: Index<T, I>,
         func: impl FnOnce(&mut T) -> U,
     ) -> ArenaResult<U> {
-        let mut x = self.take(index.clone())?;
-        let y = func(&mut x);
-        self.insert(index, x)?;
-        Ok(y)
+        let mut func = Some(func);
+        let mut result = None;
+        self.with_mut(index, &mut |x| result = func.take().map(|f| f(x)))?;
+        result.ok_or(ArenaError::ExpectedFullSlot)
+    }
+}
+
+impl<T, I, A> Inspect<T, I> for A
+where
+    T: ArenaItem,
+    I: IndexWidth,
+    A: Arena<T, I> + ?Sized,
+{
+}
+
+/// An iterator over the [Index] of each full slot of an [Arena], in order of
+/// position, returned by [Occupied::occupied].
+///
+/// The arena is only borrowed while each [Index] is found, so the arena may be
+/// used in between, and a slot filled behind the iterator is not visited. The
+/// iterator ends after the first error.
+#[derive(Debug)]
+pub struct OccupiedIndices<'a, T, I, A: ?Sized> {
+    arena: &'a A,
+    /// The position to search from, or `None` once the iterator has ended.
+    position: Option<usize>,
+    phantom: PhantomData<(T, I)>,
+}
+
+impl<T, I, A> Iterator for OccupiedIndices<'_, T, I, A>
+where
+    T: ArenaItem,
+    I: IndexWidth,
+    A: Arena<T, I> + ?Sized,
+{
+    type Item = ArenaResult<Index<T, I>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let position = self.position.take()?;
+        let next = self.arena.next_occupied(position);
+        if let Ok(Some(index)) = &next {
+            let next_position = index.inner().to_usize().checked_add(1);
+            self.position = next_position;
+        }
+        next.transpose()
     }
 }
 
-impl<T: ArenaItem, A: Arena<T> + ?Sized> Inspect<T> for A {}
+impl<T, I, A> FusedIterator for OccupiedIndices<'_, T, I, A>
+where
+    T: ArenaItem,
+    I: IndexWidth,
+    A: Arena<T, I> + ?Sized,
+{
+}
+
+/// A helper trait for iterating over the full slots of an [Arena], which also
+/// works through a `&dyn Arena`, such as each element of an
+/// [ArenaHandler::DynArenas](super::handler::ArenaHandler::DynArenas).
+#[allow(unused)]
+pub trait Occupied<T: ArenaItem, I: IndexWidth = u16>: Arena<T, I> {
+    /// Iterates over the [Index] of each full slot, in order of position.
+    fn occupied(&self) -> OccupiedIndices<'_, T, I, Self> {
+        OccupiedIndices { arena: self, position: Some(0), phantom: PhantomData }
+    }
+
+    /// Iterates over the [Index] of each full slot, in order of position,
+    /// along with the result of `func` on its item, as in [Inspect::inspect].
+    fn occupied_map<U>(
+        &self,
+        mut func: impl FnMut(&T) -> U,
+    ) -> impl Iterator<Item = ArenaResult<(Index<T, I>, U)>> {
+        self.occupied().map(move |index| {
+            let index = index?;
+            let mapped = self.inspect(index.clone(), &mut func)?;
+            Ok((index, mapped))
+        })
+    }
+}
+
+impl<T, I, A> Occupied<T, I> for A
+where
+    T: ArenaItem,
+    I: IndexWidth,
+    A: Arena<T, I> + ?Sized,
+{
+}
@@ -1,5 +1,11 @@
 use super::ArenaItem;
+use super::error::ArenaResult;
+use super::reachability::Marks;
+use super::transfer::MoveError;
+use super::transfer::Transfers;
 use super::tuple::RightTuple;
+use super::validation::Validation;
+use super::validation::Validator;
 use crate::arena::tuple::DynArenasOf;
 
 /// A trait to specify how the given arena item should be dropped and cloned.
@@ -8,30 +14,168 @@ use crate::arena::tuple::DynArenasOf;
 /// a logic error to clone or drop this item by passing arenas which are
 /// different to those it was created in, because if the item points to a given
 /// [super::Index], then this will be treated as a dangling index.
+///
+/// Such dangling indices, along with items which no longer have an owner, can
+/// be found with [check_reachability](super::reachability::check_reachability).
 #[allow(dead_code)]
-pub trait ArenaHandler: ArenaItem {
-    /// A right-associated tuple of types for which this type may have arenas,
-    /// where the rightmost element is `()`.
+pub trait ArenaHandler: ArenaItem + Sized {
+    /// A right-associated tuple of the [super::Index] types for which this
+    /// type may have arenas, where the rightmost element is `()`.
     ///
     /// For example, if `enum Expr` had variants `Add(Index<Expr>, Index<Expr>)`
-    /// and `Unit(Index<Atom>)`, then the type would be
-    /// `(Expr, (Atom, ()))`
+    /// and `Unit(Index<Atom, u8>)`, then the type would be
+    /// `(Index<Expr>, (Index<Atom, u8>, ()))`
     type Indices: RightTuple;
-    /// A right-associated tuple of `&'a dyn Arena<A>` of the same length as
-    /// [ArenaHandler::Indices], where `A` is the corresponding type in
+    /// A right-associated tuple of `&'a dyn Arena<A, I>` of the same length as
+    /// [ArenaHandler::Indices], where `Index<A, I>` is the corresponding type in
     /// [ArenaHandler::Indices], and the rightmost element is `()` (not a
     /// reference).
     ///
     /// For example, if `enum Expr` had variants `Add(Index<Expr>, Index<Expr>)`
-    /// and `Unit(Index<Atom>)`, then the value would be
-    /// `(&'a dyn Arena<Expr>, (&'a dyn Arena<Atom>, ()))`.
+    /// and `Unit(Index<Atom, u8>)`, then the value would be
+    /// `(&'a dyn Arena<Expr>, (&'a dyn Arena<Atom, u8>, ()))`.
     type DynArenas<'a>: RightTuple;
 
     /// Drops this item from the given `arenas`, assuming that it was
     /// created in these `arenas`.
-    fn drop_in<'a>(self, arenas: &DynArenasOf<'a, Self>);
+    ///
+    /// Panics if an item it points to cannot be taken; see
+    /// [ArenaHandler::try_drop_in] for a version which does not panic.
+    #[cfg(any(test, not(feature = "no-panic")))]
+    fn drop_in<'a>(self, arenas: &DynArenasOf<'a, Self>) {
+        self.try_drop_in(arenas)
+            .expect("[ArenaHandler::drop_in]: item should have been dropped")
+    }
 
     /// Clones this item using space in the given `arenas`, assuming that it
     /// was created in these `arenas`.
-    fn clone_in<'a>(&self, arenas: &DynArenasOf<'a, Self>) -> Self;
+    ///
+    /// Panics if the clone cannot be allocated; see
+    /// [ArenaHandler::try_clone_in] for a version which does not panic.
+    #[cfg(any(test, not(feature = "no-panic")))]
+    fn clone_in<'a>(&self, arenas: &DynArenasOf<'a, Self>) -> Self {
+        self.try_clone_in(arenas)
+            .expect("[ArenaHandler::clone_in]: item should have been cloned")
+    }
+
+    /// Drops this item from the given `arenas` as in [ArenaHandler::drop_in],
+    /// returning the first error met.
+    ///
+    /// Every item which can still be reached is dropped before returning, even
+    /// after an error, so that as little as possible is leaked.
+    fn try_drop_in<'a>(self, arenas: &DynArenasOf<'a, Self>)
+    -> ArenaResult<()>;
+
+    /// Clones this item using space in the given `arenas` as in
+    /// [ArenaHandler::clone_in], returning the first error met.
+    ///
+    /// If cloning fails partway, everything allocated for the clone so far is
+    /// released again before returning.
+    fn try_clone_in<'a>(
+        &self,
+        arenas: &DynArenasOf<'a, Self>,
+    ) -> ArenaResult<Self>;
+
+    /// Rewrites each [super::Index] held by this item to the one given by
+    /// [super::Arena::forward] after its arena was compacted, and then does the
+    /// same for each item it points to in the given `arenas`.
+    fn remap_in<'a>(
+        &mut self,
+        arenas: &DynArenasOf<'a, Self>,
+    ) -> ArenaResult<()>;
+
+    /// Walks this item, and each item it points to in the given `arenas`, as
+    /// [ArenaHandler::remap_in] does, but without rewriting anything,
+    /// returning the first error which remapping them would meet.
+    fn check_remap_in<'a>(
+        &self,
+        arenas: &DynArenasOf<'a, Self>,
+    ) -> ArenaResult<()>;
+
+    /// Marks each [super::Index] held by this item in `marks`, and then does
+    /// the same for each item it points to in the given `arenas` which has
+    /// not been marked before, for
+    /// [check_reachability](super::reachability::check_reachability).
+    fn mark_in<'a>(
+        &self,
+        arenas: &DynArenasOf<'a, Self>,
+        marks: &mut Marks,
+    ) -> ArenaResult<()>;
+
+    /// Reports each [super::Index] held by this item to `validator`, and then
+    /// does the same for each item it points to in the given `arenas` which
+    /// the validator reaches for the first time, for
+    /// [ArenaHandler::validate_in].
+    fn check_in<'a>(
+        &self,
+        arenas: &DynArenasOf<'a, Self>,
+        validator: &mut Validator,
+    ) -> ArenaResult<()>;
+
+    /// Checks that the items this item reaches in the given `arenas` form a
+    /// tree: that each [super::Index] points to a full slot, that no slot
+    /// other than a reference-counted one has two owners, that each chain
+    /// ends, and that nothing points back to an item it is reached from.
+    ///
+    /// Problems are listed in the returned [Validation] rather than returned
+    /// as errors, which are only met if the items are nested too deeply to be
+    /// walked, or an arena cannot be read.
+    fn validate_in<'a>(
+        &self,
+        arenas: &DynArenasOf<'a, Self>,
+    ) -> ArenaResult<Validation> {
+        let mut validator = Validator::default();
+        self.check_in(arenas, &mut validator)?;
+        Ok(validator.finish())
+    }
+
+    /// Copies this item, which was created in the `source` arenas, into the
+    /// `destination` arenas as in [ArenaHandler::try_copy_to], recording each
+    /// item with several owners in `transfers` the first time it is copied,
+    /// so that later owners point to the same copy.
+    ///
+    /// If copying fails partway, everything allocated for the copy so far is
+    /// released again before returning.
+    fn transfer_in<'a, 'b>(
+        &self,
+        source: &DynArenasOf<'a, Self>,
+        destination: &DynArenasOf<'b, Self>,
+        transfers: &mut Transfers,
+    ) -> ArenaResult<Self>;
+
+    /// Copies this item, which was created in the `source` arenas, into a
+    /// different tuple of `destination` arenas, rebuilding each
+    /// [super::Index] against them, and leaving the source untouched. Unlike
+    /// [ArenaHandler::try_clone_in], the two tuples may use different
+    /// [super::Arena] implementations.
+    fn try_copy_to<'a, 'b>(
+        &self,
+        source: &DynArenasOf<'a, Self>,
+        destination: &DynArenasOf<'b, Self>,
+    ) -> ArenaResult<Self> {
+        self.transfer_in(source, destination, &mut Transfers::default())
+    }
+
+    /// Copies this item into the `destination` arenas as in
+    /// [ArenaHandler::try_copy_to], and then drops it from the `source`
+    /// arenas, returning the copy.
+    ///
+    /// If the item cannot be copied, it is given back still in the `source`
+    /// arenas, as [MoveError::NotCopied]. If it is copied but cannot then be
+    /// fully dropped from the `source` arenas, the copy is given back as
+    /// [MoveError::NotReleased].
+    fn try_move_to<'a, 'b>(
+        self,
+        source: &DynArenasOf<'a, Self>,
+        destination: &DynArenasOf<'b, Self>,
+    ) -> Result<Self, MoveError<Self>> {
+        let copy = match self.try_copy_to(source, destination) {
+            Ok(copy) => copy,
+            Err(error) => return Err(MoveError::NotCopied(error, self)),
+        };
+        match self.try_drop_in(source) {
+            Ok(()) => Ok(copy),
+            Err(error) => Err(MoveError::NotReleased(error, copy)),
+        }
+    }
 }
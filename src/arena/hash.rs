@@ -0,0 +1,23 @@
+use core::hash::Hasher;
+
+use super::error::ArenaResult;
+use super::handler::ArenaHandler;
+use super::tuple::DynArenasOf;
+
+/// A trait to hash arena-allocated types by what their indices point to
+/// rather than by the indices themselves.
+#[allow(unused)]
+pub trait ArenaHash: ArenaHandler {
+    /// Feeds this item into `state`, following every index ([super::Index])
+    /// it holds into `arenas`, so that items which are equal by
+    /// [ArenaEq](super::equality::ArenaEq) feed the same values, wherever they
+    /// are stored.
+    ///
+    /// Returns an error if an index cannot be followed, after feeding in
+    /// whatever was reached before it.
+    fn try_hash_in<H: Hasher>(
+        &self,
+        arenas: &DynArenasOf<'_, Self>,
+        state: &mut H,
+    ) -> ArenaResult<()>;
+}
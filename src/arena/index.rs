@@ -1,63 +1,141 @@
 use core::fmt::Debug;
 use core::marker::PhantomData;
 
-type IndexInner = u16;
+mod index_width {
+    pub trait Sealed {}
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
+}
+
+/// An unsigned integer type used for the position and generation of an
+/// [Index], which bounds how many slots its [super::Arena] can address.
+///
+/// Narrower widths make every [Index] (and so every item holding one)
+/// smaller, at the cost of fewer addressable slots and generations which wrap
+/// around sooner.
+pub trait IndexWidth:
+    index_width::Sealed + Copy + Ord + Default + Debug + 'static
+{
+    /// The zero value of this width, which is the first slot and generation.
+    const ZERO: Self;
+    /// The largest value of this width, which is never used as a position, so
+    /// that an arena can address at most this many slots.
+    const MAX: Self;
+
+    /// Converts this value to a [usize].
+    fn to_usize(self) -> usize;
+
+    /// Converts the given [usize] to this width, if it fits.
+    fn from_usize(value: usize) -> Option<Self>;
+
+    /// Returns the next value, or `None` if this is [IndexWidth::MAX].
+    fn checked_increment(self) -> Option<Self>;
+
+    /// Returns the next value, wrapping around to [IndexWidth::ZERO] after
+    /// [IndexWidth::MAX].
+    fn wrapping_increment(self) -> Self;
+}
+
+macro_rules! impl_index_width {
+    ($($width:ty),+) => {
+        $(
+            impl IndexWidth for $width {
+                const ZERO: Self = 0;
+                const MAX: Self = <$width>::MAX;
+
+                fn to_usize(self) -> usize {
+                    // Saturates on targets whose `usize` is narrower, where the
+                    // excess slots could not be stored anyway.
+                    usize::try_from(self).unwrap_or(usize::MAX)
+                }
+
+                fn from_usize(value: usize) -> Option<Self> {
+                    Self::try_from(value).ok()
+                }
+
+                fn checked_increment(self) -> Option<Self> {
+                    self.checked_add(1)
+                }
+
+                fn wrapping_increment(self) -> Self {
+                    self.wrapping_add(1)
+                }
+            }
+        )+
+    };
+}
 
-/// An index type used to access an [super::Arena].
+impl_index_width!(u8, u16, u32);
+
+/// An index type used to access an [super::Arena], whose position and
+/// generation have the width `I`.
 /// Because implicit copying can lead to hidden sharing of indices, which
 /// violates the model of items in arenas having only one owner, this type is
 /// not [Copy].
+///
+/// The generation is the number of times the slot had been reused when this
+/// index was allocated, so that it can be detected as stale once its slot is
+/// handed out again.
 #[derive(PartialEq, Eq, PartialOrd, Ord)]
-pub struct Index<T>(IndexInner, PhantomData<T>);
+pub struct Index<T, I: IndexWidth = u16>(I, I, PhantomData<T>);
 
-impl<T: Debug> Debug for Index<T> {
+impl<T: Debug, I: IndexWidth> Debug for Index<T, I> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        write!(f, "Index({})", self.0)
+        write!(f, "Index({:?}#{:?})", self.0, self.1)
     }
 }
 
-impl<T> Clone for Index<T> {
+impl<T, I: IndexWidth> Clone for Index<T, I> {
     fn clone(&self) -> Self {
-        let Self(inner, _) = self;
-        Index(*inner, PhantomData)
+        let Self(inner, generation, _) = self;
+        Index(*inner, *generation, PhantomData)
     }
 }
 
-impl Index<()> {
+impl<I: IndexWidth> Index<(), I> {
     #[inline(always)]
-    pub const fn transmute<T>(self) -> Index<T> {
-        let Self(inner, _) = self;
-        Index(inner, PhantomData)
+    pub const fn transmute<T>(self) -> Index<T, I> {
+        let Self(inner, generation, _) = self;
+        Index(inner, generation, PhantomData)
     }
 }
 
-impl<T> Index<T> {
+impl<T, I: IndexWidth> Index<T, I> {
     #[inline(always)]
-    pub const fn new(index: IndexInner) -> Self {
-        Self(index, PhantomData)
+    pub const fn new(index: I, generation: I) -> Self {
+        Self(index, generation, PhantomData)
     }
 
     #[inline(always)]
-    pub const fn erase(self) -> Index<()> {
-        let Self(inner, _) = self;
-        Index(inner, PhantomData)
+    pub const fn erase(self) -> Index<(), I> {
+        let Self(inner, generation, _) = self;
+        Index(inner, generation, PhantomData)
     }
 
-    #[cfg(test)]
-    pub const fn increment_by(&mut self, inc: u16) {
-        self.0 += inc;
+    /// The position of the slot this index points to.
+    #[inline(always)]
+    pub const fn inner(&self) -> I {
+        self.0
+    }
+
+    /// The generation of the slot at the time this index was allocated.
+    #[inline(always)]
+    pub const fn generation(&self) -> I {
+        self.1
     }
 }
 
-impl<T> From<Index<T>> for IndexInner {
-    fn from(value: Index<T>) -> Self {
-        value.0
+impl<T> Index<T> {
+    #[cfg(test)]
+    pub const fn increment_by(&mut self, inc: u16) {
+        self.0 += inc;
     }
 }
 
-impl<T> From<Index<T>> for usize {
-    fn from(value: Index<T>) -> Self {
-        let Index(inner, _) = value;
-        usize::from(inner)
+impl<T, I: IndexWidth> From<Index<T, I>> for usize {
+    fn from(value: Index<T, I>) -> Self {
+        let Index(inner, _, _) = value;
+        inner.to_usize()
     }
 }
@@ -1,32 +1,183 @@
+pub mod arena_box;
 pub mod arena_impl;
+pub mod brand;
 pub mod chain;
 pub mod chain_iter;
+pub mod compact;
+pub mod debug;
 pub mod equality;
 pub mod error;
 pub mod extension;
 pub mod handler;
+pub mod hash;
 pub mod index;
+pub mod node;
+pub mod ordering;
+pub mod reachability;
+pub mod shared;
+pub mod stats;
+pub mod transaction;
+pub mod transfer;
+pub mod traversal;
 pub mod tuple;
 mod tuple_macros;
+pub mod validation;
+pub mod visit;
+pub mod work_stack;
 
+use error::ArenaError;
 use error::ArenaResult;
 use index::Index;
+use index::IndexWidth;
+use stats::ArenaStats;
 
-pub trait ArenaItem: Ord + 'static {}
-impl<T> ArenaItem for T where T: Ord + 'static {}
+pub trait ArenaItem: Ord + Clone + 'static {}
+impl<T> ArenaItem for T where T: Ord + Clone + 'static {}
 
 /// A trait to represent a simple arena, where items can be inserted, appended
 /// (allocated), deleted (taken), and queried for occupied status based on an
 /// [Index].
+///
+/// An [Index] remembers the generation of the slot it was allocated in, so
+/// that using it after the slot has been taken and reused by another
+/// allocation fails with [error::ArenaError::StaleIndex].
+///
+/// Each [Index] has the [IndexWidth] `I`, so the arena can hold at most
+/// `I::MAX` items, after which [Arena::alloc] returns
+/// [error::ArenaError::LimitReached].
 #[allow(dead_code)]
-pub trait Arena<T: ArenaItem> {
+pub trait Arena<T: ArenaItem, I: IndexWidth = u16> {
     fn size(&self) -> usize;
 
-    fn alloc(&self, value: T) -> ArenaResult<Index<T>>;
+    /// Allocates `value` into a free slot, returning its [Index].
+    fn alloc(&self, value: T) -> ArenaResult<Index<T, I>> {
+        self.alloc_or_return(value)
+            .map_err(|(error, _)| error)
+    }
 
-    fn take(&self, index: Index<T>) -> ArenaResult<T>;
+    /// Allocates `value` as in [Arena::alloc], but gives `value` back along
+    /// with the error on failure, so that anything it owns in other arenas can
+    /// still be released.
+    fn alloc_or_return(&self, value: T)
+    -> Result<Index<T, I>, (ArenaError, T)>;
 
-    fn has_slot(&self, index: Index<T>) -> ArenaResult<bool>;
+    fn take(&self, index: Index<T, I>) -> ArenaResult<T>;
 
-    fn insert(&self, index: Index<T>, value: T) -> ArenaResult<()>;
+    fn has_slot(&self, index: Index<T, I>) -> ArenaResult<bool>;
+
+    fn insert(&self, index: Index<T, I>, value: T) -> ArenaResult<()>;
+
+    /// Calls `f` exactly once with a reference to the item at the given
+    /// `index`, without moving it out of its slot.
+    ///
+    /// While `f` runs, the arena may still be read through
+    /// [Arena::with_ref], but any other access to it returns
+    /// [error::ArenaError::AlreadyBorrowed].
+    fn with_ref(
+        &self,
+        index: Index<T, I>,
+        f: &mut dyn FnMut(&T),
+    ) -> ArenaResult<()>;
+
+    /// Calls `f` exactly once with a mutable reference to the item at the
+    /// given `index`, without moving it out of its slot.
+    ///
+    /// While `f` runs, any access to the arena returns
+    /// [error::ArenaError::AlreadyBorrowed].
+    fn with_mut(
+        &self,
+        index: Index<T, I>,
+        f: &mut dyn FnMut(&mut T),
+    ) -> ArenaResult<()>;
+
+    /// Moves every item down into the lowest free slots, so that the items
+    /// occupy a contiguous run of slots from the start of the arena.
+    ///
+    /// Each moved item leaves behind a forwarding entry, through which
+    /// [Arena::forward] maps its old [Index] to its new one. Until then, its old
+    /// [Index] returns [ArenaError::StaleIndex]. Until [Arena::truncate] has
+    /// removed the forwarding entries, items can still be read and mutated in
+    /// place, but any other change to the arena returns
+    /// [ArenaError::Compacting].
+    ///
+    /// Returns [ArenaError::TransactionOpen] if a transaction is open. If an
+    /// item cannot be moved, it is left in its slot, and the items moved before
+    /// it stay forwarded until [Arena::truncate] is called.
+    fn compact(&self) -> ArenaResult<()>;
+
+    /// Returns the [Index] which the item at the given `index` has been moved
+    /// to by [Arena::compact], or `index` itself if the item was not moved.
+    fn forward(&self, index: Index<T, I>) -> ArenaResult<Index<T, I>>;
+
+    /// Discards all forwarding entries left by [Arena::compact], and releases
+    /// the storage of every slot after the last full one. An [Index] to a
+    /// released slot, or one which was never forwarded, returns
+    /// [ArenaError::StaleIndex] or [ArenaError::IndexOutOfBounds] from then
+    /// on, even once its slot is used again. Afterwards, the arena can be
+    /// changed again.
+    ///
+    /// Returns [ArenaError::TransactionOpen] if a transaction is open.
+    fn truncate(&self) -> ArenaResult<()>;
+
+    /// Opens a transaction, after which every item allocated by
+    /// [Arena::alloc] or [Arena::insert] is kept only if [Arena::commit] is
+    /// called, and is taken out again by [Arena::rollback].
+    ///
+    /// An item which was already in the arena is cloned the first time it is
+    /// mutated by [Arena::with_mut] or taken by [Arena::take] during the
+    /// transaction, and the original stays in its slot until the transaction
+    /// is closed, so that [Arena::rollback] can put it back.
+    ///
+    /// Returns [ArenaError::TransactionOpen] if a transaction is already open,
+    /// or [ArenaError::Compacting] if the arena has not been truncated since
+    /// it was compacted.
+    fn begin(&self) -> ArenaResult<()>;
+
+    /// Closes the open transaction, keeping every item allocated or mutated
+    /// during it, and dropping every item taken during it. Does nothing if no
+    /// transaction is open.
+    fn commit(&self) -> ArenaResult<()>;
+
+    /// Commits the open transaction as in [Arena::commit], but only once
+    /// `prepared` has succeeded while this arena is held, leaving the
+    /// transaction open if either fails. Once the arena is held, committing
+    /// cannot fail, so that a tuple can hold every one of its arenas before
+    /// committing any of them.
+    fn commit_with(
+        &self,
+        prepared: &mut dyn FnMut() -> ArenaResult<()>,
+    ) -> ArenaResult<()>;
+
+    /// Closes the open transaction, taking out and dropping every item
+    /// allocated during it which is still in the arena, and putting back every
+    /// item mutated or taken during it as it was before. Does nothing if no
+    /// transaction is open.
+    ///
+    /// Any [Index] held by the dropped items is not followed, so items in
+    /// other arenas which they point to must be rolled back as well.
+    fn rollback(&self) -> ArenaResult<()>;
+
+    /// Calls `f` with the [Index] and a reference to the item of each full
+    /// slot, in order of position.
+    ///
+    /// While `f` runs, the arena may still be read through [Arena::has_slot]
+    /// and [Arena::with_ref], but any other access to it returns
+    /// [error::ArenaError::AlreadyBorrowed].
+    fn for_each_occupied(
+        &self,
+        f: &mut dyn FnMut(Index<T, I>, &T),
+    ) -> ArenaResult<()>;
+
+    /// Returns the [Index] of the first full slot at or after the given
+    /// `position`, or `None` if there is none, so that the full slots can be
+    /// visited one at a time without borrowing the arena in between (see
+    /// [extension::Occupied]).
+    fn next_occupied(
+        &self,
+        position: usize,
+    ) -> ArenaResult<Option<Index<T, I>>>;
+
+    /// Returns the usage statistics of this arena since it was created, which
+    /// can be read at any time, even during an access to one of its items.
+    fn stats(&self) -> ArenaStats;
 }
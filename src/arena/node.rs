@@ -0,0 +1,254 @@
+//! The shallow view of an arena item which the traversals of
+//! [traversal](super::traversal) are written against, and which
+//! `#[derive(ArenaHandler)]` implements.
+//!
+//! A traversal only needs to know, of each item it reaches, which indices the
+//! item holds, and how to put a copy of it in place. Those indices are handed
+//! around as an index sum `P`: an enum with one variant for each arena of the
+//! tuple, as generated by `#[derive(ArenaHandler)]`, which [IndexSum]
+//! dispatches back to the arena its index points into.
+
+use core::any::Any;
+use core::cmp::Ordering;
+use core::fmt;
+use core::fmt::Formatter;
+use core::hash::Hasher;
+
+use super::Arena;
+use super::ArenaItem;
+use super::error::ArenaError;
+use super::error::ArenaResult;
+use super::index::Index;
+use super::index::IndexWidth;
+use super::tuple::RightTuple;
+use super::validation::Link;
+
+/// The most indices that a traversal expects a single item to hold, including
+/// those of a [Chain](super::chain::Chain) cell held by value.
+///
+/// A derived type which can hold more fails to compile as soon as it is
+/// traversed. An item of a hand-written type which holds more is treated as
+/// too deeply nested, and traversing it returns
+/// [ArenaError::DepthLimitExceeded].
+pub const MAX_CHILDREN: usize = 8;
+
+/// Returns the greatest of `counts`, or 0 if there are none, as the
+/// [ArenaNode::CHILDREN] of a derived enum with a count for each variant.
+pub const fn most_children(counts: &[usize]) -> usize {
+    match counts {
+        [] => 0,
+        [first, rest @ ..] => {
+            let rest = most_children(rest);
+            if *first > rest { *first } else { rest }
+        }
+    }
+}
+
+/// Returns `children`, failing to compile if it is more than [MAX_CHILDREN],
+/// as the [ArenaNode::CHILDREN] of a derived type.
+pub const fn check_children(children: usize) -> usize {
+    assert!(
+        children <= MAX_CHILDREN,
+        "an arena item can hold at most `MAX_CHILDREN` indices",
+    );
+    children
+}
+
+/// An index sum which has a variant for [Index]es of `T` with width `I`.
+#[diagnostic::on_unimplemented(
+    message = "the index sum `{Self}` has no arena of `{T}` with index width `{I}`",
+    label = "this points into an arena of `{T}`",
+    note = "list `{T}: {I}` in the `#[arena(arenas(..))]` attribute which generates `{Self}`"
+)]
+pub trait Holds<T, I: IndexWidth = u16>: Sized {
+    /// Wraps `index` in its variant.
+    fn wrap(index: Index<T, I>) -> Self;
+
+    /// Returns the [Index] in the variant for `T`, or gives `self` back if it
+    /// holds another.
+    fn unwrap(self) -> Result<Index<T, I>, Self>;
+}
+
+/// Returns the [Index] held by `sum` in the variant for `T`.
+///
+/// Only ever called on an index sum which came back from mapping an [Index] of
+/// `T`, so another variant means the mapping went wrong, and is treated as a
+/// stale index.
+pub fn unwrap_index<P, T, I>(sum: P) -> ArenaResult<Index<T, I>>
+where
+    P: Holds<T, I>,
+    I: IndexWidth,
+{
+    sum.unwrap()
+        .map_err(|_| ArenaError::StaleIndex)
+}
+
+/// An operation on the item which an index sum points to, generic over the
+/// type of that item, as called by [IndexSum::dispatch].
+pub trait IndexOp<P> {
+    /// What the operation returns.
+    type Output;
+
+    /// Applies the operation to the item at `index` in `arena`.
+    fn apply<T, I>(
+        &mut self,
+        arena: &dyn Arena<T, I>,
+        index: Index<T, I>,
+    ) -> Self::Output
+    where
+        T: ArenaNode<P>,
+        I: IndexWidth,
+        P: Holds<T, I>;
+}
+
+/// An operation on a pair of items of the same type, one from each of two
+/// tuples of arenas, as called by [IndexSum::dispatch_pair].
+pub trait IndexPairOp<P> {
+    /// What the operation returns.
+    type Output;
+
+    /// Applies the operation to the item at `this` in `this_arena` and the
+    /// item at `other` in `other_arena`.
+    fn apply<T, I>(
+        &mut self,
+        this_arena: &dyn Arena<T, I>,
+        this: Index<T, I>,
+        other_arena: &dyn Arena<T, I>,
+        other: Index<T, I>,
+    ) -> Self::Output
+    where
+        T: ArenaNode<P>,
+        I: IndexWidth,
+        P: Holds<T, I>;
+}
+
+/// An enum of the [Index]es into each arena of a tuple, as generated by
+/// `#[derive(ArenaHandler)]`.
+pub trait IndexSum: Sized + Clone {
+    /// The [ArenaHandler::Indices](super::handler::ArenaHandler::Indices) of
+    /// the tuple.
+    type Indices: RightTuple;
+    /// The [ArenaHandler::DynArenas](super::handler::ArenaHandler::DynArenas)
+    /// of the tuple.
+    type DynArenas<'a>: RightTuple + Copy;
+
+    /// Calls `op` with the [Index] held by `self` and the arena of `arenas`
+    /// which it points into.
+    fn dispatch<'a, O: IndexOp<Self>>(
+        self,
+        arenas: &Self::DynArenas<'a>,
+        op: &mut O,
+    ) -> O::Output;
+
+    /// Calls `op` with the [Index]es held by `self` and `other`, along with the
+    /// arenas of `this_arenas` and `other_arenas` which they point into, or
+    /// returns [None] if they point into different arenas.
+    fn dispatch_pair<'a, 'b, O: IndexPairOp<Self>>(
+        self,
+        other: Self,
+        this_arenas: &Self::DynArenas<'a>,
+        other_arenas: &Self::DynArenas<'b>,
+        op: &mut O,
+    ) -> Option<O::Output>;
+}
+
+/// An item which a traversal can walk through, handing each [Index] it holds
+/// around as the index sum `P`.
+///
+/// Implemented by `#[derive(ArenaHandler)]`, and by hand for the
+/// [Chain](super::chain::Chain) and [Counted](super::shared::Counted) items
+/// which derived types point to.
+pub trait ArenaNode<P>: ArenaItem + Clone {
+    /// How an item which holds an [Index] of this type owns its slot, unless
+    /// [ArenaNode::link_at] says otherwise for the item in it.
+    const LINK: Link = Link::Owned;
+
+    /// The most indices which an item of this type can hold at once, counting
+    /// those of the items it holds by value.
+    const CHILDREN: usize;
+
+    /// Returns an item without indices to stand in for a copy of an item of
+    /// this type until it is filled in, if this type has one.
+    ///
+    /// A type without a placeholder is copied eagerly, as soon as an item
+    /// pointing to it is copied, so every recursive type needs one.
+    fn placeholder() -> Option<Self>;
+
+    /// Calls `f` with each [Index] which this item holds, in order.
+    fn for_each_child(
+        &self,
+        f: &mut dyn FnMut(P) -> ArenaResult<()>,
+    ) -> ArenaResult<()>;
+
+    /// Replaces each [Index] which this item holds, in the same order as
+    /// [ArenaNode::for_each_child], with the one which `f` returns for it.
+    fn map_children(
+        &mut self,
+        f: &mut dyn FnMut(P) -> ArenaResult<P>,
+    ) -> ArenaResult<()>;
+
+    /// Feeds everything about this item but the items it points to into
+    /// `state`.
+    fn shallow_hash(&self, state: &mut dyn Hasher);
+
+    /// Returns true if this item and `other` agree in everything but the items
+    /// they point to.
+    fn shallow_eq(&self, other: &Self) -> bool;
+
+    /// Compares this item with `other` in everything but the items they point
+    /// to, returning [Ordering::Equal] exactly when [ArenaNode::shallow_eq]
+    /// returns true.
+    fn shallow_cmp(&self, other: &Self) -> Ordering;
+
+    /// Writes everything about this item but the items it points to, such as
+    /// the name of its variant, as a single line.
+    fn shallow_fmt(&self, f: &mut Formatter<'_>) -> fmt::Result;
+
+    /// Returns the item which a [Visitor](super::visit::Visitor) is shown in
+    /// place of this one: the item itself, or the item it wraps.
+    fn as_node(&self) -> &dyn Any {
+        self
+    }
+
+    /// Returns the item which a [VisitorMut](super::visit::VisitorMut) may
+    /// rewrite in place of this one, as in [ArenaNode::as_node].
+    fn as_node_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    /// Returns a copy of this item, still holding the same indices, to be
+    /// stored in a new slot.
+    fn shallow_clone(&self) -> Self {
+        self.clone()
+    }
+
+    /// Removes the owner holding `index`, taking the item out of `arena` and
+    /// returning it if it has no owners left.
+    fn release<I: IndexWidth>(
+        arena: &dyn Arena<Self, I>,
+        index: Index<Self, I>,
+    ) -> ArenaResult<Option<Self>> {
+        arena.take(index).map(Some)
+    }
+
+    /// Returns how the owners of the item at `index` hold its slot, which is
+    /// [ArenaNode::LINK] unless the item counts its own owners, as a variant
+    /// with an `#[arena(owners)]` field does.
+    fn link_at<I: IndexWidth>(
+        arena: &dyn Arena<Self, I>,
+        index: &Index<Self, I>,
+    ) -> ArenaResult<Link> {
+        let _ = (arena, index);
+        Ok(Self::LINK)
+    }
+
+    /// Adds an owner to the item at `index`, returning the [Index] for the new
+    /// owner, or returns [None] if the item has a single owner.
+    fn retain<I: IndexWidth>(
+        arena: &dyn Arena<Self, I>,
+        index: &Index<Self, I>,
+    ) -> Option<ArenaResult<Index<Self, I>>> {
+        let _ = (arena, index);
+        None
+    }
+}
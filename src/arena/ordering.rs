@@ -0,0 +1,103 @@
+use core::cmp::Ordering;
+use core::fmt;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+#[cfg(any(test, not(feature = "no-panic")))]
+use core::hash::Hash;
+#[cfg(any(test, not(feature = "no-panic")))]
+use core::hash::Hasher;
+
+use super::debug::ArenaDebug;
+use super::equality::ArenaEq;
+use super::error::ArenaResult;
+use super::handler::ArenaHandler;
+#[cfg(any(test, not(feature = "no-panic")))]
+use super::hash::ArenaHash;
+use super::tuple::DynArenasOf;
+
+/// A trait to define a total order on arena-allocated types, by what their
+/// indices point to rather than by the indices themselves.
+#[allow(unused)]
+pub trait ArenaOrd: ArenaEq {
+    /// Compares `this`, whose indices ([super::Index]) point to values in
+    /// `this_arenas`, with `other`, whose indices point to values in
+    /// `other_arenas`.
+    ///
+    /// Returns `Ok(Ordering::Equal)` exactly when [ArenaEq::try_eq_in] returns
+    /// `Ok(true)`, and an error if an index cannot be followed, or if the items
+    /// are nested too deeply to be compared.
+    fn try_cmp_in<'a, 'b>(
+        this: &Self,
+        other: &Self,
+        this_arenas: &DynArenasOf<'a, Self>,
+        other_arenas: &DynArenasOf<'b, Self>,
+    ) -> ArenaResult<Ordering>;
+
+    /// Returns this item along with its arenas, as a value which can be
+    /// sorted, deduplicated, or used as the key of an ordered map, by what it
+    /// holds.
+    ///
+    /// Returns an error if the item cannot be compared with itself by
+    /// [ArenaOrd::try_cmp_in], so that only items which can be ordered are
+    /// wrapped.
+    fn by_content<'s, 'a>(
+        &'s self,
+        arenas: &'s DynArenasOf<'a, Self>,
+    ) -> ArenaResult<ByContent<'s, 'a, Self>> {
+        Self::try_cmp_in(self, self, arenas, arenas)?;
+        Ok(ByContent { item: self, arenas })
+    }
+}
+
+/// An item along with the arenas its indices point to, as returned by
+/// [ArenaOrd::by_content], which is compared by [ArenaOrd::try_cmp_in] and
+/// hashed by [ArenaHash::try_hash_in].
+///
+/// Every index of the item is expected to stay followable for as long as it
+/// is wrapped. Comparing or hashing an item whose arenas were changed under
+/// it panics, rather than giving an order which is not total; for this reason
+/// the standard traits are not implemented under the `no-panic` feature.
+pub struct ByContent<'s, 'a, T: ArenaHandler> {
+    item: &'s T,
+    arenas: &'s DynArenasOf<'a, T>,
+}
+
+#[cfg(any(test, not(feature = "no-panic")))]
+impl<T: ArenaOrd> PartialEq for ByContent<'_, '_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+#[cfg(any(test, not(feature = "no-panic")))]
+impl<T: ArenaOrd> Eq for ByContent<'_, '_, T> {}
+
+#[cfg(any(test, not(feature = "no-panic")))]
+impl<T: ArenaOrd> PartialOrd for ByContent<'_, '_, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(any(test, not(feature = "no-panic")))]
+impl<T: ArenaOrd> Ord for ByContent<'_, '_, T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        T::try_cmp_in(self.item, other.item, self.arenas, other.arenas)
+            .expect("[ByContent::cmp]: items should have been compared")
+    }
+}
+
+#[cfg(any(test, not(feature = "no-panic")))]
+impl<T: ArenaOrd + ArenaHash> Hash for ByContent<'_, '_, T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.item
+            .try_hash_in(self.arenas, state)
+            .expect("[ByContent::hash]: item should have been hashed")
+    }
+}
+
+impl<T: ArenaDebug> Debug for ByContent<'_, '_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.item.fmt_in(self.arenas, f)
+    }
+}
@@ -0,0 +1,178 @@
+//! Mark-and-sweep checking of a whole tuple of arenas, to find the items which
+//! no root owns and the indices which point at nothing.
+
+use core::any;
+use core::ptr;
+
+use super::Arena;
+use super::ArenaItem;
+use super::error::ArenaError;
+use super::error::ArenaResult;
+use super::handler::ArenaHandler;
+use super::index::Index;
+use super::index::IndexWidth;
+use super::tuple::DynArenasOf;
+use super::tuple::RightTuple;
+use crate::alloc_types::BTreeSet;
+use crate::alloc_types::Vec;
+
+/// The position of a slot in one of the arenas of a tuple, named by the type
+/// of the items in that arena.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[allow(unused)]
+pub struct SlotRef {
+    /// The name of the type of the items in the arena.
+    pub item: &'static str,
+    /// The position of the slot in the arena.
+    pub position: usize,
+}
+
+/// The outcome of [check_reachability].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[allow(unused)]
+pub struct Reachability {
+    /// The full slots which cannot be reached from any root, in the order of
+    /// the arenas in the tuple and then by position.
+    pub leaked: Vec<SlotRef>,
+    /// The slots which are pointed to by an [Index] reached from a root, but
+    /// which are empty or have been reused since, in the order they were
+    /// reached.
+    pub dangling: Vec<SlotRef>,
+}
+
+impl Reachability {
+    /// Returns true if and only if nothing is leaked and nothing is dangling.
+    #[allow(unused)]
+    pub fn is_sound(&self) -> bool {
+        self.leaked.is_empty() && self.dangling.is_empty()
+    }
+}
+
+/// The slots reached so far while walking from the roots of a
+/// [check_reachability], which an [ArenaHandler::mark_in] adds to.
+#[derive(Debug, Default)]
+pub struct Marks {
+    /// The address of each arena paired with the position of a reached slot.
+    marked: BTreeSet<(usize, usize)>,
+    dangling: Vec<SlotRef>,
+}
+
+/// Returns an address which tells apart the arenas of a tuple.
+pub fn arena_address<T: ArenaItem, I: IndexWidth>(
+    arena: &dyn Arena<T, I>,
+) -> usize {
+    ptr::from_ref(arena).cast::<()>().addr()
+}
+
+impl Marks {
+    /// Marks the slot at `index` in `arena` as reached, returning true if its
+    /// item has not been reached before, so that the caller should go on to
+    /// mark the indices held by that item.
+    ///
+    /// An `index` whose slot is empty or has been reused is recorded as
+    /// dangling rather than returned as an error.
+    pub fn mark<T: ArenaItem, I: IndexWidth>(
+        &mut self,
+        arena: &dyn Arena<T, I>,
+        index: &Index<T, I>,
+    ) -> ArenaResult<bool> {
+        let position = index.inner().to_usize();
+        let occupied = match arena.has_slot(index.clone()) {
+            Ok(occupied) => occupied,
+            Err(
+                ArenaError::IndexOutOfBounds
+                | ArenaError::StaleIndex
+                | ArenaError::ExpectedFullSlot,
+            ) => false,
+            Err(error) => return Err(error),
+        };
+        if !occupied {
+            let item = any::type_name::<T>();
+            self.dangling
+                .push(SlotRef { item, position });
+            return Ok(false);
+        }
+        Ok(self
+            .marked
+            .insert((arena_address(arena), position)))
+    }
+
+    /// Returns true if and only if the slot at `position` in `arena` has been
+    /// marked.
+    fn is_marked<T: ArenaItem, I: IndexWidth>(
+        &self,
+        arena: &dyn Arena<T, I>,
+        position: usize,
+    ) -> bool {
+        self.marked
+            .contains(&(arena_address(arena), position))
+    }
+}
+
+/// A [RightTuple] of dynamically-dispatched [Arena] references, such as an
+/// [ArenaHandler::DynArenas], whose full slots can be swept for items which
+/// were not marked.
+#[allow(unused)]
+pub trait TupleSweep: RightTuple {
+    /// Pushes each full slot of each arena in this tuple which is not in
+    /// `marks` onto `leaked`.
+    fn sweep(
+        &self,
+        marks: &Marks,
+        leaked: &mut Vec<SlotRef>,
+    ) -> ArenaResult<()>;
+}
+
+impl TupleSweep for () {
+    fn sweep(&self, _: &Marks, _: &mut Vec<SlotRef>) -> ArenaResult<()> {
+        Ok(())
+    }
+}
+
+impl<T, I, TS> TupleSweep for (&dyn Arena<T, I>, TS)
+where
+    T: ArenaItem,
+    I: IndexWidth,
+    TS: TupleSweep,
+{
+    fn sweep(
+        &self,
+        marks: &Marks,
+        leaked: &mut Vec<SlotRef>,
+    ) -> ArenaResult<()> {
+        let (head, tail) = self;
+        head.for_each_occupied(&mut |index, _| {
+            let position = index.inner().to_usize();
+            if !marks.is_marked(*head, position) {
+                let item = any::type_name::<T>();
+                leaked.push(SlotRef { item, position });
+            }
+        })?;
+        tail.sweep(marks, leaked)
+    }
+}
+
+/// Walks every [Index] reachable from the `roots` through their
+/// [ArenaHandler]s, and then reports each full slot in `arenas` which was not
+/// reached as leaked, along with each reached [Index] whose slot is empty as
+/// dangling.
+///
+/// The `roots` themselves are not in any arena. An item reached more than once
+/// is only walked the first time.
+#[allow(unused)]
+pub fn check_reachability<'a, T>(
+    roots: &[T],
+    arenas: &DynArenasOf<'a, T>,
+) -> ArenaResult<Reachability>
+where
+    T: ArenaHandler,
+    DynArenasOf<'a, T>: TupleSweep,
+{
+    let mut marks = Marks::default();
+    roots
+        .iter()
+        .try_for_each(|root| root.mark_in(arenas, &mut marks))?;
+    let mut leaked = Vec::new();
+    arenas.sweep(&marks, &mut leaked)?;
+    Ok(Reachability { leaked, dangling: marks.dangling })
+}
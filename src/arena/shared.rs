@@ -0,0 +1,173 @@
+//! Reference-counted items, which several owners can point to at once.
+//!
+//! An [Index] is not [Copy], so that each item in an arena has a single owner.
+//! An item which is wrapped in a [Counted] is the exception: every owner holds
+//! an [Index] to the same slot, which counts them, and the item is only taken
+//! out once the last owner releases it.
+
+use core::any::Any;
+use core::cmp::Ordering;
+use core::fmt;
+use core::fmt::Formatter;
+use core::hash::Hasher;
+
+use super::Arena;
+use super::ArenaItem;
+use super::error::ArenaError;
+use super::error::ArenaResult;
+use super::extension::Inspect;
+use super::index::Index;
+use super::index::IndexWidth;
+use super::node::ArenaNode;
+use super::validation::Link;
+
+/// An item along with the number of owners which point to it.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Counted<T> {
+    count: usize,
+    item: T,
+}
+
+impl<T> Counted<T> {
+    /// Returns the number of owners which point to this item.
+    #[allow(unused)]
+    pub const fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns a reference to the item.
+    pub const fn get(&self) -> &T {
+        &self.item
+    }
+
+    /// Returns a mutable reference to the item.
+    #[allow(unused)]
+    pub const fn get_mut(&mut self) -> &mut T {
+        &mut self.item
+    }
+}
+
+/// Allocates `item` into `arena` with a single owner, returning the [Index] of
+/// that owner, or gives back `item` on failure.
+#[allow(unused)]
+pub fn share<T, I>(
+    arena: &dyn Arena<Counted<T>, I>,
+    item: T,
+) -> Result<Index<Counted<T>, I>, (ArenaError, T)>
+where
+    T: ArenaItem,
+    I: IndexWidth,
+{
+    arena
+        .alloc_or_return(Counted { count: 1, item })
+        .map_err(|(error, counted)| (error, counted.item))
+}
+
+/// Adds an owner to the item at `index`, returning the [Index] for the new
+/// owner.
+pub fn retain<T, I>(
+    arena: &dyn Arena<Counted<T>, I>,
+    index: &Index<Counted<T>, I>,
+) -> ArenaResult<Index<Counted<T>, I>>
+where
+    T: ArenaItem,
+    I: IndexWidth,
+{
+    arena.inspect_mut(index.clone(), |counted| {
+        counted.count = counted.count.saturating_add(1);
+    })?;
+    Ok(index.clone())
+}
+
+/// Removes the owner holding `index` from its item, taking the item out of
+/// `arena` and returning it if that was the last owner.
+pub fn release<T, I>(
+    arena: &dyn Arena<Counted<T>, I>,
+    index: Index<Counted<T>, I>,
+) -> ArenaResult<Option<T>>
+where
+    T: ArenaItem,
+    I: IndexWidth,
+{
+    let count = arena.inspect_mut(index.clone(), |counted| {
+        counted.count = counted.count.saturating_sub(1);
+        counted.count
+    })?;
+    if count > 0 {
+        return Ok(None);
+    }
+    arena
+        .take(index)
+        .map(|counted| Some(counted.item))
+}
+
+/// A counted item is copied eagerly, since it is copied only once however many
+/// owners point to it, and the copy starts out with a single owner.
+impl<T, P> ArenaNode<P> for Counted<T>
+where
+    T: ArenaNode<P>,
+{
+    const LINK: Link = Link::Counted;
+    const CHILDREN: usize = T::CHILDREN;
+
+    fn placeholder() -> Option<Self> {
+        None
+    }
+
+    fn for_each_child(
+        &self,
+        f: &mut dyn FnMut(P) -> ArenaResult<()>,
+    ) -> ArenaResult<()> {
+        self.item.for_each_child(f)
+    }
+
+    fn map_children(
+        &mut self,
+        f: &mut dyn FnMut(P) -> ArenaResult<P>,
+    ) -> ArenaResult<()> {
+        self.item.map_children(f)
+    }
+
+    fn shallow_hash(&self, state: &mut dyn Hasher) {
+        self.item.shallow_hash(state)
+    }
+
+    fn shallow_eq(&self, other: &Self) -> bool {
+        self.item.shallow_eq(&other.item)
+    }
+
+    fn shallow_cmp(&self, other: &Self) -> Ordering {
+        self.item.shallow_cmp(&other.item)
+    }
+
+    fn shallow_fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.item.shallow_fmt(f)
+    }
+
+    fn as_node(&self) -> &dyn Any {
+        self.item.as_node()
+    }
+
+    fn as_node_mut(&mut self) -> &mut dyn Any {
+        self.item.as_node_mut()
+    }
+
+    fn shallow_clone(&self) -> Self {
+        Self { count: 1, item: self.item.shallow_clone() }
+    }
+
+    fn release<I: IndexWidth>(
+        arena: &dyn Arena<Self, I>,
+        index: Index<Self, I>,
+    ) -> ArenaResult<Option<Self>> {
+        let item = release(arena, index)?;
+        Ok(item.map(|item| Self { count: 0, item }))
+    }
+
+    fn retain<I: IndexWidth>(
+        arena: &dyn Arena<Self, I>,
+        index: &Index<Self, I>,
+    ) -> Option<ArenaResult<Index<Self, I>>> {
+        Some(retain(arena, index))
+    }
+}
@@ -0,0 +1,311 @@
+//! Usage statistics of arenas, for sizing arenas with a fixed capacity.
+
+use core::any;
+use core::cell::Cell;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+
+use super::Arena;
+use super::ArenaItem;
+use super::error::ArenaError;
+use super::index::IndexWidth;
+use super::tuple::RightTuple;
+
+/// A snapshot of the usage of an [Arena] since it was created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[allow(unused)]
+pub struct ArenaStats {
+    /// The number of items currently held by the arena.
+    pub occupied: usize,
+    /// The largest number of items the arena can hold at once.
+    pub capacity: usize,
+    /// The largest number of items the arena has held at once.
+    pub high_water: usize,
+    /// The number of successful calls to [Arena::alloc].
+    pub allocs: usize,
+    /// The number of successful calls to [Arena::take], along with the items
+    /// taken out by [Arena::rollback].
+    pub takes: usize,
+    /// The number of failed calls to [Arena::alloc].
+    pub failures: AllocFailures,
+}
+
+/// The number of failed calls to [Arena::alloc], split by the [ArenaError]
+/// they returned.
+///
+/// Each count is a `C`, which is a plain [usize] in an [ArenaStats], and a
+/// [Counter] inside the [ArenaCounters] of an arena.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[allow(unused)]
+pub struct AllocFailures<C = usize> {
+    pub index_out_of_bounds: C,
+    pub limit_reached: C,
+    pub expected_free_slot: C,
+    pub expected_full_slot: C,
+    pub stale_index: C,
+    pub already_borrowed: C,
+    pub transaction_open: C,
+    pub compacting: C,
+    pub depth_limit_exceeded: C,
+}
+
+impl<C> AllocFailures<C> {
+    /// Returns the count of failures which returned the given `error`.
+    pub const fn get(&self, error: ArenaError) -> &C {
+        match error {
+            ArenaError::IndexOutOfBounds => &self.index_out_of_bounds,
+            ArenaError::LimitReached => &self.limit_reached,
+            ArenaError::ExpectedFreeSlot => &self.expected_free_slot,
+            ArenaError::ExpectedFullSlot => &self.expected_full_slot,
+            ArenaError::StaleIndex => &self.stale_index,
+            ArenaError::AlreadyBorrowed => &self.already_borrowed,
+            ArenaError::TransactionOpen => &self.transaction_open,
+            ArenaError::Compacting => &self.compacting,
+            ArenaError::DepthLimitExceeded => &self.depth_limit_exceeded,
+        }
+    }
+
+    /// Applies `f` to every count, keeping the error each one belongs to.
+    fn map<D>(&self, f: impl Fn(&C) -> D) -> AllocFailures<D> {
+        AllocFailures {
+            index_out_of_bounds: f(&self.index_out_of_bounds),
+            limit_reached: f(&self.limit_reached),
+            expected_free_slot: f(&self.expected_free_slot),
+            expected_full_slot: f(&self.expected_full_slot),
+            stale_index: f(&self.stale_index),
+            already_borrowed: f(&self.already_borrowed),
+            transaction_open: f(&self.transaction_open),
+            compacting: f(&self.compacting),
+            depth_limit_exceeded: f(&self.depth_limit_exceeded),
+        }
+    }
+}
+
+impl AllocFailures {
+    /// Returns the number of failures which returned the given `error`.
+    #[allow(unused)]
+    pub const fn count(&self, error: ArenaError) -> usize {
+        *self.get(error)
+    }
+
+    /// Returns the total number of failures.
+    #[allow(unused)]
+    pub const fn total(&self) -> usize {
+        self.index_out_of_bounds
+            .saturating_add(self.limit_reached)
+            .saturating_add(self.expected_free_slot)
+            .saturating_add(self.expected_full_slot)
+            .saturating_add(self.stale_index)
+            .saturating_add(self.already_borrowed)
+            .saturating_add(self.transaction_open)
+            .saturating_add(self.compacting)
+            .saturating_add(self.depth_limit_exceeded)
+    }
+}
+
+/// A single counter of an [ArenaCounters], which can be updated through a
+/// shared reference.
+///
+/// A [Cell] is used by arenas which stay in one context, and an [AtomicUsize]
+/// only by a [SpinArena](super::arena_impl::spin_arena::SpinArena), so that
+/// the other arenas also build for targets without atomic read-modify-write
+/// instructions.
+pub trait Counter {
+    /// A counter holding zero.
+    const ZERO: Self;
+
+    /// Returns the current count.
+    fn get(&self) -> usize;
+
+    /// Sets the count to `value`.
+    fn set(&self, value: usize);
+
+    /// Adds one to the count, saturating at [usize::MAX], and returns the new
+    /// count.
+    fn increment(&self) -> usize;
+
+    /// Subtracts one from the count, saturating at zero.
+    fn decrement(&self);
+
+    /// Raises the count to `value`, if it is lower.
+    fn raise_to(&self, value: usize);
+}
+
+impl Counter for Cell<usize> {
+    const ZERO: Self = Cell::new(0);
+
+    fn get(&self) -> usize {
+        Cell::get(self)
+    }
+
+    fn set(&self, value: usize) {
+        Cell::set(self, value)
+    }
+
+    fn increment(&self) -> usize {
+        let count = self.get().saturating_add(1);
+        self.set(count);
+        count
+    }
+
+    fn decrement(&self) {
+        self.set(self.get().saturating_sub(1))
+    }
+
+    fn raise_to(&self, value: usize) {
+        self.set(self.get().max(value))
+    }
+}
+
+impl Counter for AtomicUsize {
+    const ZERO: Self = AtomicUsize::new(0);
+
+    fn get(&self) -> usize {
+        self.load(Ordering::Relaxed)
+    }
+
+    fn set(&self, value: usize) {
+        self.store(value, Ordering::Relaxed)
+    }
+
+    fn increment(&self) -> usize {
+        let update = |count: usize| count.checked_add(1);
+        match self.fetch_update(Ordering::Relaxed, Ordering::Relaxed, update) {
+            Ok(count) => count.saturating_add(1),
+            Err(count) => count,
+        }
+    }
+
+    fn decrement(&self) {
+        let update = |count: usize| count.checked_sub(1);
+        let _ = self.fetch_update(Ordering::Relaxed, Ordering::Relaxed, update);
+    }
+
+    fn raise_to(&self, value: usize) {
+        self.fetch_max(value, Ordering::Relaxed);
+    }
+}
+
+/// The counters behind an [ArenaStats], which can be updated through a shared
+/// reference without borrowing the arena's state, so that even an access
+/// which fails with [ArenaError::AlreadyBorrowed] is counted.
+#[derive(Debug, Default)]
+pub struct ArenaCounters<C = Cell<usize>> {
+    occupied: C,
+    high_water: C,
+    allocs: C,
+    takes: C,
+    failures: AllocFailures<C>,
+}
+
+impl<C: Counter> ArenaCounters<C> {
+    /// Creates counters which are all zero.
+    pub const fn new() -> Self {
+        Self {
+            occupied: C::ZERO,
+            high_water: C::ZERO,
+            allocs: C::ZERO,
+            takes: C::ZERO,
+            failures: AllocFailures {
+                index_out_of_bounds: C::ZERO,
+                limit_reached: C::ZERO,
+                expected_free_slot: C::ZERO,
+                expected_full_slot: C::ZERO,
+                stale_index: C::ZERO,
+                already_borrowed: C::ZERO,
+                transaction_open: C::ZERO,
+                compacting: C::ZERO,
+                depth_limit_exceeded: C::ZERO,
+            },
+        }
+    }
+
+    /// Records an item being placed into a free slot, by [Arena::alloc] if
+    /// `allocated` is true and by [Arena::insert] otherwise.
+    pub fn record_fill(&self, allocated: bool) {
+        let occupied = self.occupied.increment();
+        self.high_water.raise_to(occupied);
+        if allocated {
+            self.allocs.increment();
+        }
+    }
+
+    /// Records an item being removed by [Arena::take].
+    pub fn record_take(&self) {
+        self.occupied.decrement();
+        self.takes.increment();
+    }
+
+    /// Records a call to [Arena::alloc] which failed with `error`.
+    pub fn record_failure(&self, error: ArenaError) {
+        self.failures.get(error).increment();
+    }
+
+    /// Records every item being dropped at once, when an arena is reset. The
+    /// totals and the high-water mark are kept.
+    pub fn record_clear(&self) {
+        self.occupied.set(0);
+    }
+
+    /// Returns the number of items currently held by the arena.
+    pub fn occupied(&self) -> usize {
+        self.occupied.get()
+    }
+
+    /// Reads the counters into an [ArenaStats] for an arena of the given
+    /// `capacity`.
+    pub fn snapshot(&self, capacity: usize) -> ArenaStats {
+        ArenaStats {
+            occupied: self.occupied.get(),
+            capacity,
+            high_water: self.high_water.get(),
+            allocs: self.allocs.get(),
+            takes: self.takes.get(),
+            failures: self.failures.map(C::get),
+        }
+    }
+}
+
+/// A [RightTuple] of dynamically-dispatched [Arena] references, such as an
+/// [ArenaHandler::DynArenas](super::handler::ArenaHandler::DynArenas), whose
+/// [ArenaStats] can be gathered together.
+#[allow(unused)]
+pub trait TupleStats: RightTuple {
+    /// A [RightTuple] of [ArenaStats] of the same length as this tuple.
+    type Stats: RightTuple;
+
+    /// Returns the [ArenaStats] of each arena in this tuple, in order.
+    fn stats(&self) -> Self::Stats;
+
+    /// Calls `f` with the name of the item type and the [ArenaStats] of each
+    /// arena in this tuple, in order, for example to log them.
+    fn for_each_stats(&self, f: &mut dyn FnMut(&'static str, ArenaStats));
+}
+
+impl TupleStats for () {
+    type Stats = ();
+
+    fn stats(&self) -> Self::Stats {}
+
+    fn for_each_stats(&self, _: &mut dyn FnMut(&'static str, ArenaStats)) {}
+}
+
+impl<T, I, TS> TupleStats for (&dyn Arena<T, I>, TS)
+where
+    T: ArenaItem,
+    I: IndexWidth,
+    TS: TupleStats,
+{
+    type Stats = (ArenaStats, TS::Stats);
+
+    fn stats(&self) -> Self::Stats {
+        let (head, tail) = self;
+        (head.stats(), tail.stats())
+    }
+
+    fn for_each_stats(&self, f: &mut dyn FnMut(&'static str, ArenaStats)) {
+        let (head, tail) = self;
+        f(any::type_name::<T>(), head.stats());
+        tail.for_each_stats(f)
+    }
+}
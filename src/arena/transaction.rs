@@ -0,0 +1,91 @@
+//! Transactions across a whole tuple of arenas, so that building an item
+//! which spans several arenas either succeeds or leaves no trace.
+
+use super::Arena;
+use super::ArenaItem;
+use super::error::ArenaResult;
+use super::index::IndexWidth;
+use super::tuple::RightTuple;
+
+/// A [RightTuple] of dynamically-dispatched [Arena] references, such as an
+/// [ArenaHandler::DynArenas](super::handler::ArenaHandler::DynArenas), whose
+/// arenas can take part in one transaction.
+#[allow(unused)]
+pub trait TupleTransaction: RightTuple {
+    /// Calls [Arena::begin] on each arena in this tuple.
+    fn begin(&self) -> ArenaResult<()>;
+
+    /// Commits the open transaction of each arena in this tuple, holding every
+    /// one of them with [Arena::commit_with] before committing any, so that
+    /// if an arena is borrowed, none is committed and the error is returned.
+    fn commit(&self) -> ArenaResult<()>;
+
+    /// Calls [Arena::rollback] on each arena in this tuple, returning the
+    /// first error only once every arena has been rolled back.
+    fn rollback(&self) -> ArenaResult<()>;
+}
+
+impl TupleTransaction for () {
+    fn begin(&self) -> ArenaResult<()> {
+        Ok(())
+    }
+
+    fn commit(&self) -> ArenaResult<()> {
+        Ok(())
+    }
+
+    fn rollback(&self) -> ArenaResult<()> {
+        Ok(())
+    }
+}
+
+impl<T, I, TS> TupleTransaction for (&dyn Arena<T, I>, TS)
+where
+    T: ArenaItem,
+    I: IndexWidth,
+    TS: TupleTransaction,
+{
+    fn begin(&self) -> ArenaResult<()> {
+        let (head, tail) = self;
+        head.begin()?;
+        tail.begin()
+            .inspect_err(|_| _ = head.rollback())
+    }
+
+    fn commit(&self) -> ArenaResult<()> {
+        let (head, tail) = self;
+        head.commit_with(&mut || tail.commit())
+    }
+
+    fn rollback(&self) -> ArenaResult<()> {
+        let (head, tail) = self;
+        let result = head.rollback();
+        result.and(tail.rollback())
+    }
+}
+
+/// Runs `f` inside a transaction across every arena in `arenas`, so that the
+/// items it allocates, mutates and takes are all kept as they are if it
+/// succeeds, and are all put back as they were if it fails.
+///
+/// Committing can only fail if an arena is borrowed, in which case none of the
+/// arenas has been committed, and each of them which is not borrowed is rolled
+/// back.
+#[allow(unused)]
+pub fn transaction<A, U>(
+    arenas: &A,
+    f: impl FnOnce(&A) -> ArenaResult<U>,
+) -> ArenaResult<U>
+where
+    A: TupleTransaction,
+{
+    arenas.begin()?;
+    let result = f(arenas).and_then(|value| {
+        arenas.commit()?;
+        Ok(value)
+    });
+    if result.is_err() {
+        let _ = arenas.rollback();
+    }
+    result
+}
@@ -0,0 +1,75 @@
+//! Copying of items from one tuple of arenas into a different one, such as
+//! from growable arenas on a host into the fixed-capacity arenas of a device.
+
+use super::Arena;
+use super::ArenaItem;
+use super::error::ArenaError;
+use super::index::Index;
+use super::index::IndexWidth;
+use super::reachability::arena_address;
+use crate::alloc_types::BTreeMap;
+
+/// The error of [ArenaHandler::try_move_to], which tells whether the item was
+/// moved, and gives back either the source item `S` or its copy `D`.
+///
+/// [ArenaHandler::try_move_to]: super::handler::ArenaHandler::try_move_to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(unused)]
+pub enum MoveError<S, D = S> {
+    /// The item could not be copied, and is given back, still in the source
+    /// arenas, which hold nothing of the failed copy.
+    NotCopied(ArenaError, S),
+    /// The item was copied, and its copy is given back, but it could not be
+    /// fully dropped from the source arenas, which may still hold some of its
+    /// slots.
+    NotReleased(ArenaError, D),
+}
+
+impl<S, D> MoveError<S, D> {
+    /// Returns the error which stopped the move.
+    #[allow(unused)]
+    pub const fn error(&self) -> ArenaError {
+        match self {
+            Self::NotCopied(error, _) | Self::NotReleased(error, _) => *error,
+        }
+    }
+}
+
+/// The shared items copied so far by an [ArenaHandler::transfer_in], so that
+/// an item which several owners point to in the source arenas is copied only
+/// once, and is pointed to by as many owners in the destination arenas.
+///
+/// [ArenaHandler::transfer_in]: super::handler::ArenaHandler::transfer_in
+#[derive(Debug, Default)]
+pub struct Transfers {
+    /// The address of each source arena paired with the position of a copied
+    /// slot, mapped to the position and generation of its copy.
+    copied: BTreeMap<(usize, usize), (usize, usize)>,
+}
+
+impl Transfers {
+    /// Returns the [Index] of the copy of the item at `index` in `source`, if
+    /// it has been copied before.
+    pub fn copied<T: ArenaItem, I: IndexWidth>(
+        &self,
+        source: &dyn Arena<T, I>,
+        index: &Index<T, I>,
+    ) -> Option<Index<T, I>> {
+        let key = (arena_address(source), index.inner().to_usize());
+        let &(position, generation) = self.copied.get(&key)?;
+        Some(Index::new(I::from_usize(position)?, I::from_usize(generation)?))
+    }
+
+    /// Records that the item at `index` in `source` has been copied to the
+    /// slot at `copy`.
+    pub fn record<T: ArenaItem, I: IndexWidth>(
+        &mut self,
+        source: &dyn Arena<T, I>,
+        index: &Index<T, I>,
+        copy: &Index<T, I>,
+    ) {
+        let key = (arena_address(source), index.inner().to_usize());
+        let value = (copy.inner().to_usize(), copy.generation().to_usize());
+        self.copied.insert(key, value);
+    }
+}
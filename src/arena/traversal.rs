@@ -0,0 +1,1272 @@
+//! The traversals behind a derived [ArenaHandler](super::handler::ArenaHandler),
+//! written once against [ArenaNode] for every tuple of arenas, and each driven
+//! by a [WorkStack] of pending indices rather than by recursion, so that a
+//! long or deeply nested item cannot overflow the machine stack.
+//!
+//! The indices held by each item are pushed last first, so that its first
+//! child is visited straight away. Since a [Chain](super::chain::Chain) cell
+//! holds its head before its tail, the stack grows with the nesting of an
+//! item, but not with the length of its chains.
+//!
+//! Each traversal takes the capacity of its stack as `DEPTH`, which the
+//! derives fill in from `#[arena(depth = ..)]`, or with
+//! [TRAVERSAL_DEPTH](super::work_stack::TRAVERSAL_DEPTH) by default.
+
+use core::cmp::Ordering;
+use core::fmt;
+use core::fmt::Formatter;
+use core::hash::Hasher;
+use core::mem;
+
+use super::Arena;
+use super::ArenaItem;
+use super::error::ArenaError;
+use super::error::ArenaResult;
+use super::extension::Inspect;
+use super::index::Index;
+use super::index::IndexWidth;
+use super::node::ArenaNode;
+use super::node::Holds;
+use super::node::IndexOp;
+use super::node::IndexPairOp;
+use super::node::IndexSum;
+use super::node::MAX_CHILDREN;
+use super::reachability::Marks;
+use super::transfer::Transfers;
+use super::validation::Link;
+use super::validation::SlotId;
+use super::validation::Validator;
+use super::visit::Flow;
+use super::visit::Folder;
+use super::visit::Visitor;
+use super::visit::VisitorMut;
+use super::work_stack::WorkStack;
+
+/// How many items without a placeholder may be copied eagerly inside one
+/// another before a copy gives up with [ArenaError::DepthLimitExceeded].
+const EAGER_DEPTH: usize = 4;
+
+/// Keeps the first error met in `result`.
+fn record(result: &mut ArenaResult<()>, error: ArenaError) {
+    *result = mem::replace(result, Ok(())).and(Err(error));
+}
+
+/// Helper function to push each [Index] held by `item` onto `stack`, as made
+/// into a step by `step`, last first, so that they are popped in order.
+fn push_children<P, T, U, const DEPTH: usize>(
+    item: &T,
+    stack: &mut WorkStack<U, DEPTH>,
+    mut step: impl FnMut(P) -> U,
+) -> ArenaResult<()>
+where
+    T: ArenaNode<P>,
+{
+    let mut children = WorkStack::<P, MAX_CHILDREN>::new();
+    item.for_each_child(&mut |child| children.push(child))?;
+    while let Some(child) = children.pop() {
+        stack.push(step(child))?;
+    }
+    Ok(())
+}
+
+/// Helper function to write `item` into the slot at `index`, giving `item`
+/// back along with the error if the slot cannot be reached.
+fn write<T, I>(
+    arena: &dyn Arena<T, I>,
+    index: Index<T, I>,
+    item: T,
+) -> Result<(), (ArenaError, T)>
+where
+    T: ArenaItem,
+    I: IndexWidth,
+{
+    let mut item = Some(item);
+    let written = arena.inspect_mut(index, |slot| {
+        if let Some(item) = item.take() {
+            *slot = item
+        }
+    });
+    match (written, item) {
+        (Err(error), Some(item)) => Err((error, item)),
+        _ => Ok(()),
+    }
+}
+
+/// Returns true if the item at an index holds no indices itself.
+struct IsLeaf;
+
+impl<P> IndexOp<P> for IsLeaf {
+    type Output = bool;
+
+    fn apply<T, I>(
+        &mut self,
+        arena: &dyn Arena<T, I>,
+        index: Index<T, I>,
+    ) -> bool
+    where
+        T: ArenaNode<P>,
+        I: IndexWidth,
+        P: Holds<T, I>,
+    {
+        let leaf = arena.inspect(index, |item| {
+            let mut leaf = true;
+            let _ = item.for_each_child(&mut |_| {
+                leaf = false;
+                Ok(())
+            });
+            leaf
+        });
+        leaf == Ok(true)
+    }
+}
+
+/// Releases the items at pending indices, and everything they own.
+struct Dropper<'a, P: IndexSum, const DEPTH: usize> {
+    arenas: P::DynArenas<'a>,
+    stack: WorkStack<P, DEPTH>,
+    result: ArenaResult<()>,
+}
+
+impl<'a, P: IndexSum, const DEPTH: usize> Dropper<'a, P, DEPTH> {
+    fn new(arenas: &P::DynArenas<'a>) -> Self {
+        Self { arenas: *arenas, stack: WorkStack::new(), result: Ok(()) }
+    }
+
+    /// Releases each item which `item` points to that holds no indices
+    /// itself, so that the ends of chains never wait on the stack, and pushes
+    /// the others.
+    fn push_children<T: ArenaNode<P>>(&mut self, item: &T) {
+        let mut children = WorkStack::<P, MAX_CHILDREN>::new();
+        if let Err(error) =
+            item.for_each_child(&mut |child| children.push(child))
+        {
+            record(&mut self.result, error);
+        }
+        let arenas = self.arenas;
+        while let Some(child) = children.pop() {
+            if child
+                .clone()
+                .dispatch(&arenas, &mut IsLeaf)
+            {
+                child.dispatch(&arenas, self);
+            } else if let Err(error) = self.stack.push(child) {
+                record(&mut self.result, error);
+            }
+        }
+    }
+
+    /// Releases every pending item, returning the first error met.
+    fn run(mut self) -> ArenaResult<()> {
+        let arenas = self.arenas;
+        while let Some(pending) = self.stack.pop() {
+            pending.dispatch(&arenas, &mut self);
+        }
+        self.result
+    }
+}
+
+impl<P: IndexSum, const DEPTH: usize> IndexOp<P> for Dropper<'_, P, DEPTH> {
+    type Output = ();
+
+    fn apply<T, I>(&mut self, arena: &dyn Arena<T, I>, index: Index<T, I>)
+    where
+        T: ArenaNode<P>,
+        I: IndexWidth,
+        P: Holds<T, I>,
+    {
+        match T::release(arena, index) {
+            Ok(Some(item)) => self.push_children(&item),
+            Ok(None) => {}
+            Err(error) => record(&mut self.result, error),
+        }
+    }
+}
+
+/// Drops `item` from `arenas`, releasing everything it owns.
+///
+/// Every item which can still be reached is released before returning, even
+/// after an error, except for those which would not fit on the stack, which
+/// are leaked with [ArenaError::DepthLimitExceeded].
+pub fn drop_item<P, T, const DEPTH: usize>(
+    item: T,
+    arenas: &P::DynArenas<'_>,
+) -> ArenaResult<()>
+where
+    P: IndexSum,
+    T: ArenaNode<P>,
+{
+    let mut dropper = Dropper::<P, DEPTH>::new(arenas);
+    dropper.push_children(&item);
+    dropper.run()
+}
+
+/// Releases the item at `index` from `arenas` as in [drop_item].
+fn drop_index<P: IndexSum, const DEPTH: usize>(
+    index: P,
+    arenas: &P::DynArenas<'_>,
+) -> ArenaResult<()> {
+    let mut dropper = Dropper::<P, DEPTH>::new(arenas);
+    index.dispatch(arenas, &mut dropper);
+    dropper.run()
+}
+
+/// How an item which several owners point to is copied.
+pub enum SharedCopies<'t> {
+    /// Within the same arenas, by adding an owner to it.
+    Retain,
+    /// Into other arenas, by copying it the first time it is reached, as
+    /// recorded in the [Transfers], and adding an owner to that copy each
+    /// later time.
+    Transfer(&'t mut Transfers),
+}
+
+/// A pending step of a [Copier]: the item at the first index in the source
+/// arenas, which is still to be copied into the placeholder at the second in
+/// the destination arenas.
+type CopyStep<P> = (P, P);
+
+/// The steps needed to fill in the copy of a single item, in order.
+type Steps<P> = WorkStack<CopyStep<P>, MAX_CHILDREN>;
+
+/// Copies items from the source arenas into the destination arenas, which may
+/// be the same.
+///
+/// Each item is first copied shallowly, with each [Index] it holds pointing to
+/// an [ArenaNode::placeholder] which a pending step fills in later, or to an
+/// eager copy if its type has none. The copy made so far is therefore always
+/// a whole item, which can be dropped as usual if a later step fails.
+struct Copier<'s, 'd, 't, P: IndexSum, const DEPTH: usize> {
+    source: P::DynArenas<'s>,
+    destination: P::DynArenas<'d>,
+    shared: SharedCopies<'t>,
+    stack: WorkStack<CopyStep<P>, DEPTH>,
+    eager: usize,
+}
+
+impl<P: IndexSum, const DEPTH: usize> Copier<'_, '_, '_, P, DEPTH> {
+    /// Returns a shallow copy of `item` whose indices point into the
+    /// destination arenas, adding a step to `steps` for each placeholder.
+    ///
+    /// If this fails partway, whatever was allocated for the copy is released
+    /// again.
+    fn copy_shallow<T: ArenaNode<P>>(
+        &mut self,
+        item: &T,
+        steps: &mut Steps<P>,
+    ) -> ArenaResult<T> {
+        let mut copy = item.shallow_clone();
+        let mut reserved = 0_usize;
+        let mapped = copy.map_children(&mut |child| {
+            let child = self.reserve(child, steps)?;
+            reserved = reserved.saturating_add(1);
+            Ok(child)
+        });
+        if let Err(error) = mapped {
+            // Only the indices before the one which failed were mapped, and
+            // the rest still point into the source arenas.
+            let destination = self.destination;
+            let mut position = 0_usize;
+            let _ = copy.for_each_child(&mut |child| {
+                if position < reserved {
+                    let _ = drop_index::<P, DEPTH>(child, &destination);
+                }
+                position = position.saturating_add(1);
+                Ok(())
+            });
+            return Err(error);
+        }
+        Ok(copy)
+    }
+
+    /// Returns the [Index] in the destination arenas which stands in for the
+    /// item at `index` in the source arenas.
+    fn reserve(&mut self, index: P, steps: &mut Steps<P>) -> ArenaResult<P> {
+        let (source, destination) = (self.source, self.destination);
+        let mut reserve = Reserve { copier: self, steps };
+        index
+            .clone()
+            .dispatch_pair(index, &source, &destination, &mut reserve)
+            .unwrap_or(Err(ArenaError::StaleIndex))
+    }
+
+    /// Returns the [Index] of a copy of the item at `index`, which is
+    /// either a new placeholder, a new owner of a shared copy, or an eager
+    /// copy.
+    fn reserve_in<T, I>(
+        &mut self,
+        source_arena: &dyn Arena<T, I>,
+        index: Index<T, I>,
+        destination_arena: &dyn Arena<T, I>,
+        steps: &mut Steps<P>,
+    ) -> ArenaResult<Index<T, I>>
+    where
+        T: ArenaNode<P>,
+        I: IndexWidth,
+        P: Holds<T, I>,
+    {
+        let mut counted = false;
+        match &self.shared {
+            SharedCopies::Retain => {
+                if let Some(retained) = T::retain(destination_arena, &index) {
+                    return retained;
+                }
+            }
+            SharedCopies::Transfer(transfers) => {
+                counted = T::link_at(source_arena, &index)? == Link::Counted;
+                if counted
+                    && let Some(copy) = transfers.copied(source_arena, &index)
+                {
+                    return T::retain(destination_arena, &copy)
+                        .unwrap_or(Ok(copy));
+                }
+            }
+        }
+        // A counted item is copied eagerly, so that its copy can gain owners
+        // as soon as it is recorded.
+        let copy = match T::placeholder().filter(|_| !counted) {
+            Some(placeholder) => {
+                let copy = destination_arena.alloc(placeholder)?;
+                let step = (P::wrap(index.clone()), P::wrap(copy.clone()));
+                if let Err(error) = steps.push(step) {
+                    let _ = destination_arena.take(copy);
+                    return Err(error);
+                }
+                copy
+            }
+            None => self.copy_eagerly(
+                source_arena,
+                index.clone(),
+                destination_arena,
+                steps,
+            )?,
+        };
+        if let SharedCopies::Transfer(transfers) = &mut self.shared
+            && counted
+        {
+            transfers.record(source_arena, &index, &copy);
+        }
+        Ok(copy)
+    }
+
+    /// Copies the item at `index` shallowly into a new slot of
+    /// `destination_arena`, for a type without a placeholder.
+    fn copy_eagerly<T, I>(
+        &mut self,
+        source_arena: &dyn Arena<T, I>,
+        index: Index<T, I>,
+        destination_arena: &dyn Arena<T, I>,
+        steps: &mut Steps<P>,
+    ) -> ArenaResult<Index<T, I>>
+    where
+        T: ArenaNode<P>,
+        I: IndexWidth,
+    {
+        if self.eager >= EAGER_DEPTH {
+            return Err(ArenaError::DepthLimitExceeded);
+        }
+        let item = source_arena.inspect(index, T::clone)?;
+        self.eager = self.eager.saturating_add(1);
+        let copy = self.copy_shallow(&item, steps);
+        self.eager = self.eager.saturating_sub(1);
+        match destination_arena.alloc_or_return(copy?) {
+            Ok(copy) => Ok(copy),
+            Err((error, copy)) => {
+                let _ = drop_item::<P, T, DEPTH>(copy, &self.destination);
+                Err(error)
+            }
+        }
+    }
+
+    /// Pushes `steps` onto the stack, so that the first is run next.
+    fn push_steps(&mut self, mut steps: Steps<P>) -> ArenaResult<()> {
+        while let Some(step) = steps.pop() {
+            self.stack.push(step)?;
+        }
+        Ok(())
+    }
+
+    /// Runs every pending step, and then returns `copy` if they all succeeded,
+    /// or else drops it and returns the first error met.
+    fn finish<T: ArenaNode<P>>(
+        mut self,
+        copy: T,
+        steps: Steps<P>,
+    ) -> ArenaResult<T> {
+        let (source, destination) = (self.source, self.destination);
+        let mut result = self.push_steps(steps);
+        while result.is_ok()
+            && let Some((index, placeholder)) = self.stack.pop()
+        {
+            result = index
+                .dispatch_pair(
+                    placeholder,
+                    &source,
+                    &destination,
+                    &mut Fill { copier: &mut self },
+                )
+                .unwrap_or(Err(ArenaError::StaleIndex));
+        }
+        match result {
+            Ok(()) => Ok(copy),
+            Err(error) => {
+                // The failure to copy is reported rather than any failure to
+                // release the partial copy.
+                let _ = drop_item::<P, T, DEPTH>(copy, &destination);
+                Err(error)
+            }
+        }
+    }
+}
+
+/// Calls [Copier::reserve_in] on the arenas which an index points into.
+struct Reserve<'c, 's, 'd, 't, P: IndexSum, const DEPTH: usize> {
+    copier: &'c mut Copier<'s, 'd, 't, P, DEPTH>,
+    steps: &'c mut Steps<P>,
+}
+
+impl<P: IndexSum, const DEPTH: usize> IndexPairOp<P>
+    for Reserve<'_, '_, '_, '_, P, DEPTH>
+{
+    type Output = ArenaResult<P>;
+
+    fn apply<T, I>(
+        &mut self,
+        source_arena: &dyn Arena<T, I>,
+        index: Index<T, I>,
+        destination_arena: &dyn Arena<T, I>,
+        _: Index<T, I>,
+    ) -> ArenaResult<P>
+    where
+        T: ArenaNode<P>,
+        I: IndexWidth,
+        P: Holds<T, I>,
+    {
+        self.copier
+            .reserve_in(source_arena, index, destination_arena, self.steps)
+            .map(P::wrap)
+    }
+}
+
+/// Copies the item at a pending index into its placeholder.
+struct Fill<'c, 's, 'd, 't, P: IndexSum, const DEPTH: usize> {
+    copier: &'c mut Copier<'s, 'd, 't, P, DEPTH>,
+}
+
+impl<P: IndexSum, const DEPTH: usize> IndexPairOp<P>
+    for Fill<'_, '_, '_, '_, P, DEPTH>
+{
+    type Output = ArenaResult<()>;
+
+    fn apply<T, I>(
+        &mut self,
+        source_arena: &dyn Arena<T, I>,
+        index: Index<T, I>,
+        destination_arena: &dyn Arena<T, I>,
+        placeholder: Index<T, I>,
+    ) -> ArenaResult<()>
+    where
+        T: ArenaNode<P>,
+        I: IndexWidth,
+        P: Holds<T, I>,
+    {
+        let item = source_arena.inspect(index, T::clone)?;
+        let mut steps = Steps::new();
+        let copy = self
+            .copier
+            .copy_shallow(&item, &mut steps)?;
+        if let Err((error, copy)) = write(destination_arena, placeholder, copy)
+        {
+            // The copy could not be linked into the item being built, so it
+            // is released on its own.
+            let _ = drop_item::<P, T, DEPTH>(copy, &self.copier.destination);
+            return Err(error);
+        }
+        self.copier.push_steps(steps)
+    }
+}
+
+/// Copies `item` from the `source` arenas into the `destination` arenas, which
+/// may be the same, releasing everything allocated for the copy if it fails
+/// partway.
+pub fn copy_item<P, T, const DEPTH: usize>(
+    item: &T,
+    source: &P::DynArenas<'_>,
+    destination: &P::DynArenas<'_>,
+    shared: SharedCopies<'_>,
+) -> ArenaResult<T>
+where
+    P: IndexSum,
+    T: ArenaNode<P>,
+{
+    let mut copier = Copier::<P, DEPTH> {
+        source: *source,
+        destination: *destination,
+        shared,
+        stack: WorkStack::new(),
+        eager: 0,
+    };
+    let mut steps = Steps::new();
+    let copy = copier.copy_shallow(item, &mut steps)?;
+    copier.finish(copy, steps)
+}
+
+/// Forwards the index at which an item was before compaction.
+struct Forward;
+
+impl<P> IndexOp<P> for Forward {
+    type Output = ArenaResult<P>;
+
+    fn apply<T, I>(
+        &mut self,
+        arena: &dyn Arena<T, I>,
+        index: Index<T, I>,
+    ) -> ArenaResult<P>
+    where
+        T: ArenaNode<P>,
+        I: IndexWidth,
+        P: Holds<T, I>,
+    {
+        arena.forward(index).map(P::wrap)
+    }
+}
+
+/// Remaps the items at pending indices after compaction, writing each back to
+/// its slot only if `write` is true.
+struct Remapper<'a, P: IndexSum, const DEPTH: usize> {
+    arenas: P::DynArenas<'a>,
+    stack: WorkStack<P, DEPTH>,
+    write: bool,
+}
+
+impl<P: IndexSum, const DEPTH: usize> Remapper<'_, P, DEPTH> {
+    /// Forwards each [Index] held by the shallow `item`, pushing the items
+    /// they point to onto the stack.
+    fn forward_children<T: ArenaNode<P>>(
+        &mut self,
+        item: &mut T,
+    ) -> ArenaResult<()> {
+        let arenas = self.arenas;
+        item.map_children(&mut |child| child.dispatch(&arenas, &mut Forward))?;
+        push_children(item, &mut self.stack, |child| child)
+    }
+}
+
+impl<P: IndexSum, const DEPTH: usize> IndexOp<P> for Remapper<'_, P, DEPTH> {
+    type Output = ArenaResult<()>;
+
+    fn apply<T, I>(
+        &mut self,
+        arena: &dyn Arena<T, I>,
+        index: Index<T, I>,
+    ) -> ArenaResult<()>
+    where
+        T: ArenaNode<P>,
+        I: IndexWidth,
+        P: Holds<T, I>,
+    {
+        let mut item = arena.inspect(index.clone(), T::clone)?;
+        self.forward_children(&mut item)?;
+        if !self.write {
+            return Ok(());
+        }
+        arena.inspect_mut(index, |slot| *slot = item)
+    }
+}
+
+/// Rewrites each [Index] held by `item`, and by every item it points to, to
+/// the one given by [Arena::forward] after its arena was compacted.
+///
+/// A shared item is remapped once for each of its owners, which leaves it
+/// unchanged after the first time, since forwarding an [Index] which was not
+/// moved returns it as it is.
+pub fn remap_item<P, T, const DEPTH: usize>(
+    item: &mut T,
+    arenas: &P::DynArenas<'_>,
+) -> ArenaResult<()>
+where
+    P: IndexSum,
+    T: ArenaNode<P>,
+{
+    let mut remapper = Remapper::<P, DEPTH> {
+        arenas: *arenas,
+        stack: WorkStack::new(),
+        write: true,
+    };
+    remapper.forward_children(item)?;
+    while let Some(pending) = remapper.stack.pop() {
+        pending.dispatch(arenas, &mut remapper)?;
+    }
+    Ok(())
+}
+
+/// Walks `item`, and every item it points to, as [remap_item] does, but
+/// without rewriting anything, returning the first error which remapping them
+/// would meet.
+pub fn check_remap_item<P, T, const DEPTH: usize>(
+    item: &T,
+    arenas: &P::DynArenas<'_>,
+) -> ArenaResult<()>
+where
+    P: IndexSum,
+    T: ArenaNode<P>,
+{
+    let mut remapper = Remapper::<P, DEPTH> {
+        arenas: *arenas,
+        stack: WorkStack::new(),
+        write: false,
+    };
+    remapper.forward_children(&mut item.clone())?;
+    while let Some(pending) = remapper.stack.pop() {
+        pending.dispatch(arenas, &mut remapper)?;
+    }
+    Ok(())
+}
+
+/// Marks the items at pending indices.
+struct Marker<'m, P, const DEPTH: usize> {
+    marks: &'m mut Marks,
+    stack: WorkStack<P, DEPTH>,
+}
+
+impl<P, const DEPTH: usize> IndexOp<P> for Marker<'_, P, DEPTH> {
+    type Output = ArenaResult<()>;
+
+    fn apply<T, I>(
+        &mut self,
+        arena: &dyn Arena<T, I>,
+        index: Index<T, I>,
+    ) -> ArenaResult<()>
+    where
+        T: ArenaNode<P>,
+        I: IndexWidth,
+        P: Holds<T, I>,
+    {
+        if self.marks.mark(arena, &index)? {
+            let item = arena.inspect(index, T::clone)?;
+            push_children(&item, &mut self.stack, |child| child)?;
+        }
+        Ok(())
+    }
+}
+
+/// Marks each [Index] held by `item`, and by every item it points to which has
+/// not been marked before, for
+/// [check_reachability](super::reachability::check_reachability).
+pub fn mark_item<P, T, const DEPTH: usize>(
+    item: &T,
+    arenas: &P::DynArenas<'_>,
+    marks: &mut Marks,
+) -> ArenaResult<()>
+where
+    P: IndexSum,
+    T: ArenaNode<P>,
+{
+    let mut marker = Marker::<P, DEPTH> { marks, stack: WorkStack::new() };
+    push_children(item, &mut marker.stack, |child| child)?;
+    while let Some(pending) = marker.stack.pop() {
+        pending.dispatch(arenas, &mut marker)?;
+    }
+    Ok(())
+}
+
+/// A pending step of [check_item]: an item to be reached from the slot of its
+/// owner, if it is in one.
+type CheckStep<P> = (Option<SlotId>, P);
+
+/// Reaches the items at pending indices from the slot `parent`.
+struct Checker<'v, P, const DEPTH: usize> {
+    validator: &'v mut Validator,
+    parent: Option<SlotId>,
+    stack: WorkStack<CheckStep<P>, DEPTH>,
+}
+
+impl<P, const DEPTH: usize> IndexOp<P> for Checker<'_, P, DEPTH> {
+    type Output = ArenaResult<()>;
+
+    fn apply<T, I>(
+        &mut self,
+        arena: &dyn Arena<T, I>,
+        index: Index<T, I>,
+    ) -> ArenaResult<()>
+    where
+        T: ArenaNode<P>,
+        I: IndexWidth,
+        P: Holds<T, I>,
+    {
+        // An index which cannot be followed is reported by [Validator::reach]
+        // itself, whatever its link.
+        let link = T::link_at(arena, &index).unwrap_or(T::LINK);
+        let reached = self
+            .validator
+            .reach(self.parent, arena, &index, link)?;
+        if let Some(slot) = reached {
+            let item = arena.inspect(index, T::clone)?;
+            push_children(&item, &mut self.stack, |child| (Some(slot), child))?;
+        }
+        Ok(())
+    }
+}
+
+/// Reports each [Index] held by `item`, and by every item it points to which
+/// is reached for the first time, to `validator`.
+pub fn check_item<P, T, const DEPTH: usize>(
+    item: &T,
+    arenas: &P::DynArenas<'_>,
+    validator: &mut Validator,
+) -> ArenaResult<()>
+where
+    P: IndexSum,
+    T: ArenaNode<P>,
+{
+    let mut checker = Checker::<P, DEPTH> {
+        validator,
+        parent: None,
+        stack: WorkStack::new(),
+    };
+    push_children(item, &mut checker.stack, |child| (None, child))?;
+    while let Some((parent, pending)) = checker.stack.pop() {
+        checker.parent = parent;
+        pending.dispatch(arenas, &mut checker)?;
+    }
+    Ok(())
+}
+
+/// Feeds the items at pending indices into a [Hasher].
+struct HashOp<'h, P, const DEPTH: usize> {
+    state: &'h mut dyn Hasher,
+    stack: WorkStack<P, DEPTH>,
+}
+
+impl<P, const DEPTH: usize> IndexOp<P> for HashOp<'_, P, DEPTH> {
+    type Output = ArenaResult<()>;
+
+    fn apply<T, I>(
+        &mut self,
+        arena: &dyn Arena<T, I>,
+        index: Index<T, I>,
+    ) -> ArenaResult<()>
+    where
+        T: ArenaNode<P>,
+        I: IndexWidth,
+        P: Holds<T, I>,
+    {
+        let item = arena.inspect(index, T::clone)?;
+        item.shallow_hash(self.state);
+        push_children(&item, &mut self.stack, |child| child)
+    }
+}
+
+/// Feeds the structure of `item` into `state`, following every [Index] it
+/// holds into `arenas`, so that items which only differ in where they are
+/// stored hash the same.
+pub fn hash_item<P, T, const DEPTH: usize>(
+    item: &T,
+    arenas: &P::DynArenas<'_>,
+    state: &mut dyn Hasher,
+) -> ArenaResult<()>
+where
+    P: IndexSum,
+    T: ArenaNode<P>,
+{
+    item.shallow_hash(state);
+    let mut hasher = HashOp::<P, DEPTH> { state, stack: WorkStack::new() };
+    push_children(item, &mut hasher.stack, |child| child)?;
+    while let Some(pending) = hasher.stack.pop() {
+        pending.dispatch(arenas, &mut hasher)?;
+    }
+    Ok(())
+}
+
+/// How a [Comparer] tells shallow items apart.
+#[derive(Clone, Copy)]
+enum Comparison {
+    /// By [ArenaNode::shallow_eq], with any difference reported as
+    /// [Ordering::Less].
+    Equality,
+    /// By [ArenaNode::shallow_cmp].
+    Order,
+}
+
+/// Compares the pairs of items at pending indices, one from each side.
+struct Comparer<P, const DEPTH: usize> {
+    comparison: Comparison,
+    stack: WorkStack<(P, P), DEPTH>,
+}
+
+impl<P, const DEPTH: usize> Comparer<P, DEPTH> {
+    /// Compares the shallow items `this` and `other` in everything but the
+    /// items they point to, pushing each pair of those to be compared next if
+    /// they are equal.
+    fn compare<T: ArenaNode<P>>(
+        &mut self,
+        this: &T,
+        other: &T,
+    ) -> ArenaResult<Ordering> {
+        let ordering = match self.comparison {
+            Comparison::Equality if this.shallow_eq(other) => Ordering::Equal,
+            Comparison::Equality => Ordering::Less,
+            Comparison::Order => this.shallow_cmp(other),
+        };
+        if ordering != Ordering::Equal {
+            return Ok(ordering);
+        }
+        let mut these = WorkStack::<P, MAX_CHILDREN>::new();
+        this.for_each_child(&mut |child| these.push(child))?;
+        let mut others = WorkStack::<P, MAX_CHILDREN>::new();
+        other.for_each_child(&mut |child| others.push(child))?;
+        loop {
+            match (these.pop(), others.pop()) {
+                (Some(this), Some(other)) => self.stack.push((this, other))?,
+                (None, None) => return Ok(Ordering::Equal),
+                (None, Some(_)) => return Ok(Ordering::Less),
+                (Some(_), None) => return Ok(Ordering::Greater),
+            }
+        }
+    }
+
+    /// Compares `this` with `other`, and then each pending pair in turn, until
+    /// a pair differs or the stack is empty.
+    fn run<T: ArenaNode<P>>(
+        mut self,
+        this: &T,
+        other: &T,
+        this_arenas: &P::DynArenas<'_>,
+        other_arenas: &P::DynArenas<'_>,
+    ) -> ArenaResult<Ordering>
+    where
+        P: IndexSum,
+    {
+        let mut ordering = self.compare(this, other)?;
+        while ordering == Ordering::Equal
+            && let Some((this, other)) = self.stack.pop()
+        {
+            // Equal items point to items of the same types, so a pair of
+            // different types can only come from a stale index.
+            ordering = this
+                .dispatch_pair(other, this_arenas, other_arenas, &mut self)
+                .unwrap_or(Err(ArenaError::StaleIndex))?;
+        }
+        Ok(ordering)
+    }
+}
+
+impl<P, const DEPTH: usize> IndexPairOp<P> for Comparer<P, DEPTH> {
+    type Output = ArenaResult<Ordering>;
+
+    fn apply<T, I>(
+        &mut self,
+        this_arena: &dyn Arena<T, I>,
+        this: Index<T, I>,
+        other_arena: &dyn Arena<T, I>,
+        other: Index<T, I>,
+    ) -> ArenaResult<Ordering>
+    where
+        T: ArenaNode<P>,
+        I: IndexWidth,
+        P: Holds<T, I>,
+    {
+        let this = this_arena.inspect(this, T::clone)?;
+        let other = other_arena.inspect(other, T::clone)?;
+        self.compare(&this, &other)
+    }
+}
+
+/// Returns `Ok(true)` if `this`, whose indices point into `this_arenas`, and
+/// `other`, whose indices point into `other_arenas`, agree in everything but
+/// where their items are stored, as in
+/// [ArenaEq::try_eq_in](super::equality::ArenaEq::try_eq_in).
+pub fn eq_item<P, T, const DEPTH: usize>(
+    this: &T,
+    other: &T,
+    this_arenas: &P::DynArenas<'_>,
+    other_arenas: &P::DynArenas<'_>,
+) -> ArenaResult<bool>
+where
+    P: IndexSum,
+    T: ArenaNode<P>,
+{
+    let comparer = Comparer::<P, DEPTH> {
+        comparison: Comparison::Equality,
+        stack: WorkStack::new(),
+    };
+    let ordering = comparer.run(this, other, this_arenas, other_arenas)?;
+    Ok(ordering == Ordering::Equal)
+}
+
+/// Compares `this`, whose indices point into `this_arenas`, with `other`,
+/// whose indices point into `other_arenas`, by the items they hold rather
+/// than by where those are stored, as in
+/// [ArenaOrd::try_cmp_in](super::ordering::ArenaOrd::try_cmp_in).
+///
+/// Items are ordered by [ArenaNode::shallow_cmp] first, and then by the items
+/// they point to, in order, so that the first difference met in a walk of both
+/// decides.
+pub fn cmp_item<P, T, const DEPTH: usize>(
+    this: &T,
+    other: &T,
+    this_arenas: &P::DynArenas<'_>,
+    other_arenas: &P::DynArenas<'_>,
+) -> ArenaResult<Ordering>
+where
+    P: IndexSum,
+    T: ArenaNode<P>,
+{
+    let comparer = Comparer::<P, DEPTH> {
+        comparison: Comparison::Order,
+        stack: WorkStack::new(),
+    };
+    comparer.run(this, other, this_arenas, other_arenas)
+}
+
+/// A pending step of [fmt_item]: an item to be written at a depth.
+type FmtStep<P> = (usize, P);
+
+/// Writes the items at pending indices, each on its own line.
+struct Printer<'f, 'w, P, const DEPTH: usize> {
+    f: &'f mut Formatter<'w>,
+    depth: usize,
+    lines: usize,
+    stack: WorkStack<FmtStep<P>, DEPTH>,
+}
+
+impl<P, const DEPTH: usize> Printer<'_, '_, P, DEPTH> {
+    /// Writes a line with `write`, indented by the current depth.
+    fn line(
+        &mut self,
+        write: impl FnOnce(&mut Formatter<'_>) -> fmt::Result,
+    ) -> fmt::Result {
+        if self.lines > 0 {
+            self.f.write_str("\n")?;
+        }
+        self.lines = self.lines.saturating_add(1);
+        let indent = self.depth.saturating_mul(2);
+        write!(self.f, "{:indent$}", "")?;
+        write(self.f)
+    }
+
+    /// Writes `error` on a line of its own, in place of an item.
+    fn error(&mut self, error: ArenaError) -> fmt::Result {
+        self.line(|f| write!(f, "<{error:?}>"))
+    }
+
+    /// Writes the line of `item`, and pushes the items it points to, one level
+    /// deeper.
+    ///
+    /// The cells of a chain are not written themselves, so that the items in
+    /// a chain are written as siblings.
+    fn write<T: ArenaNode<P>>(&mut self, item: &T) -> fmt::Result {
+        if T::LINK != Link::Tail {
+            self.line(|f| item.shallow_fmt(f))?;
+            self.depth = self.depth.saturating_add(1);
+        }
+        let mut children = WorkStack::<P, MAX_CHILDREN>::new();
+        if let Err(error) =
+            item.for_each_child(&mut |child| children.push(child))
+        {
+            return self.error(error);
+        }
+        while let Some(child) = children.pop() {
+            if let Err(error) = self.stack.push((self.depth, child)) {
+                return self.error(error);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<P, const DEPTH: usize> IndexOp<P> for Printer<'_, '_, P, DEPTH> {
+    type Output = fmt::Result;
+
+    fn apply<T, I>(
+        &mut self,
+        arena: &dyn Arena<T, I>,
+        index: Index<T, I>,
+    ) -> fmt::Result
+    where
+        T: ArenaNode<P>,
+        I: IndexWidth,
+        P: Holds<T, I>,
+    {
+        match arena.inspect(index, T::clone) {
+            Ok(item) => self.write(&item),
+            Err(error) => self.error(error),
+        }
+    }
+}
+
+/// Writes `item` into `f` as an indented tree, following every [Index] it
+/// holds into `arenas`, with a line for each item as given by
+/// [ArenaNode::shallow_fmt].
+///
+/// An [Index] which cannot be followed is written as its error, in place of
+/// the item it points to, so that a broken item can still be shown.
+pub fn fmt_item<P, T, const DEPTH: usize>(
+    item: &T,
+    arenas: &P::DynArenas<'_>,
+    f: &mut Formatter<'_>,
+) -> fmt::Result
+where
+    P: IndexSum,
+    T: ArenaNode<P>,
+{
+    let mut printer =
+        Printer::<P, DEPTH> { f, depth: 0, lines: 0, stack: WorkStack::new() };
+    printer.write(item)?;
+    while let Some((depth, pending)) = printer.stack.pop() {
+        printer.depth = depth;
+        pending.dispatch(arenas, &mut printer)?;
+    }
+    Ok(())
+}
+
+/// What a [Walker] does with each item it reaches.
+trait Hooks<P> {
+    /// Whether the hooks may rewrite items, which are then stored back.
+    const WRITES: bool;
+
+    /// Runs before the items which `item` points to are walked.
+    fn enter<T: ArenaNode<P>>(&mut self, item: &mut T) -> ArenaResult<Flow>;
+
+    /// Runs after the items which `item` points to are walked.
+    fn leave<T: ArenaNode<P>>(&mut self, item: &mut T) -> ArenaResult<()>;
+}
+
+/// The hooks of a [Visitor].
+struct Reading<'v>(&'v mut dyn Visitor);
+
+impl<P> Hooks<P> for Reading<'_> {
+    const WRITES: bool = false;
+
+    fn enter<T: ArenaNode<P>>(&mut self, item: &mut T) -> ArenaResult<Flow> {
+        self.0.pre(item.as_node())
+    }
+
+    fn leave<T: ArenaNode<P>>(&mut self, item: &mut T) -> ArenaResult<()> {
+        self.0.post(item.as_node())
+    }
+}
+
+/// The hooks of a [VisitorMut].
+struct Rewriting<'v>(&'v mut dyn VisitorMut);
+
+impl<P> Hooks<P> for Rewriting<'_> {
+    const WRITES: bool = true;
+
+    fn enter<T: ArenaNode<P>>(&mut self, item: &mut T) -> ArenaResult<Flow> {
+        self.0.pre(item.as_node_mut())
+    }
+
+    fn leave<T: ArenaNode<P>>(&mut self, item: &mut T) -> ArenaResult<()> {
+        self.0.post(item.as_node_mut())
+    }
+}
+
+/// The hooks of a [Folder], which keeps the unfinished value of each item
+/// being walked, innermost last.
+struct Folding<'f, F: Folder, const DEPTH: usize> {
+    folder: &'f mut F,
+    outputs: WorkStack<F::Output, DEPTH>,
+    result: Option<F::Output>,
+}
+
+impl<P, F: Folder, const DEPTH: usize> Hooks<P> for Folding<'_, F, DEPTH> {
+    const WRITES: bool = false;
+
+    fn enter<T: ArenaNode<P>>(&mut self, item: &mut T) -> ArenaResult<Flow> {
+        let output = self.folder.enter(item.as_node())?;
+        self.outputs.push(output)?;
+        Ok(Flow::Continue)
+    }
+
+    fn leave<T: ArenaNode<P>>(&mut self, _: &mut T) -> ArenaResult<()> {
+        let Some(child) = self.outputs.pop() else {
+            return Ok(());
+        };
+        match self.outputs.pop() {
+            Some(mut owner) => {
+                self.folder.combine(&mut owner, child)?;
+                self.outputs.push(owner)
+            }
+            None => {
+                self.result = Some(child);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A pending step of a [Walker]: an item to be entered, or to be left again
+/// once the items it points to have been walked.
+enum WalkStep<P> {
+    Enter(P),
+    Leave(P),
+}
+
+/// Runs [Hooks] on the items at pending indices, before and after the items
+/// they point to.
+struct Walker<H, P, const DEPTH: usize> {
+    hooks: H,
+    leaving: bool,
+    stack: WorkStack<WalkStep<P>, DEPTH>,
+}
+
+impl<P, H: Hooks<P>, const DEPTH: usize> Walker<H, P, DEPTH> {
+    /// Enters `item`, and pushes the steps which follow: entering each item it
+    /// points to, and then leaving `item` again at `leave`, if it is in a
+    /// slot.
+    ///
+    /// The cells of a chain in a slot are neither entered nor left, so that
+    /// only the nesting of items, and not the length of their chains, adds to
+    /// the stack.
+    fn enter<T: ArenaNode<P>>(
+        &mut self,
+        item: &mut T,
+        leave: Option<P>,
+    ) -> ArenaResult<Flow> {
+        if T::LINK == Link::Tail && leave.is_some() {
+            push_children(item, &mut self.stack, WalkStep::Enter)?;
+            return Ok(Flow::Continue);
+        }
+        let flow = self.hooks.enter(item)?;
+        if flow == Flow::Stop {
+            return Ok(flow);
+        }
+        if let Some(leave) = leave {
+            self.stack
+                .push(WalkStep::Leave(leave))?;
+        }
+        if flow == Flow::Continue {
+            push_children(item, &mut self.stack, WalkStep::Enter)?;
+        }
+        Ok(flow)
+    }
+}
+
+impl<P, H: Hooks<P>, const DEPTH: usize> IndexOp<P> for Walker<H, P, DEPTH> {
+    type Output = ArenaResult<Flow>;
+
+    fn apply<T, I>(
+        &mut self,
+        arena: &dyn Arena<T, I>,
+        index: Index<T, I>,
+    ) -> ArenaResult<Flow>
+    where
+        T: ArenaNode<P>,
+        I: IndexWidth,
+        P: Holds<T, I>,
+    {
+        // A counted item is left as it is by a rewrite, since each of its
+        // owners would see the change.
+        if H::WRITES && T::link_at(arena, &index)? == Link::Counted {
+            return Ok(Flow::Continue);
+        }
+        let mut item = arena.inspect(index.clone(), T::clone)?;
+        let flow = match self.leaving {
+            true => self
+                .hooks
+                .leave(&mut item)
+                .map(|()| Flow::Continue),
+            false => self.enter(&mut item, Some(P::wrap(index.clone()))),
+        };
+        // Whatever a hook rewrote is kept, even if it then failed.
+        if H::WRITES {
+            write(arena, index, item).map_err(|(error, _)| error)?;
+        }
+        flow
+    }
+}
+
+/// Runs `hooks` on `item` and on every item it points to in `arenas`, depth
+/// first and in order, returning the hooks once they are done.
+///
+/// The hooks always run on `item` itself, even if it is a chain cell.
+fn walk<P, T, H, const DEPTH: usize>(
+    item: &mut T,
+    arenas: &P::DynArenas<'_>,
+    hooks: H,
+) -> ArenaResult<H>
+where
+    P: IndexSum,
+    T: ArenaNode<P>,
+    H: Hooks<P>,
+{
+    let mut walker = Walker::<H, P, DEPTH> {
+        hooks,
+        leaving: false,
+        stack: WorkStack::new(),
+    };
+    if walker.enter(item, None)? == Flow::Stop {
+        return Ok(walker.hooks);
+    }
+    while let Some(step) = walker.stack.pop() {
+        let index = match step {
+            WalkStep::Enter(index) => {
+                walker.leaving = false;
+                index
+            }
+            WalkStep::Leave(index) => {
+                walker.leaving = true;
+                index
+            }
+        };
+        if index.dispatch(arenas, &mut walker)? == Flow::Stop {
+            return Ok(walker.hooks);
+        }
+    }
+    walker.hooks.leave(item)?;
+    Ok(walker.hooks)
+}
+
+/// Runs the hooks of `visitor` on `item` and on every item it points to, as in
+/// [ArenaVisit::try_visit_in](super::visit::ArenaVisit::try_visit_in).
+pub fn visit_item<P, T, const DEPTH: usize>(
+    item: &T,
+    arenas: &P::DynArenas<'_>,
+    visitor: &mut dyn Visitor,
+) -> ArenaResult<()>
+where
+    P: IndexSum,
+    T: ArenaNode<P>,
+{
+    // The walk takes its item mutably, but never writes anything back through
+    // [Reading], so a shallow copy of `item` stands in for it.
+    walk::<P, T, _, DEPTH>(&mut item.clone(), arenas, Reading(visitor))
+        .map(|_| ())
+}
+
+/// Runs the hooks of `visitor` on `item` and on every item it points to,
+/// other than the [Counted](super::shared::Counted) items it reaches and what
+/// they point to, storing back whatever they rewrite, as in
+/// [ArenaVisitMut::try_visit_mut_in](super::visit::ArenaVisitMut::try_visit_mut_in).
+pub fn visit_item_mut<P, T, const DEPTH: usize>(
+    item: &mut T,
+    arenas: &P::DynArenas<'_>,
+    visitor: &mut dyn VisitorMut,
+) -> ArenaResult<()>
+where
+    P: IndexSum,
+    T: ArenaNode<P>,
+{
+    walk::<P, T, _, DEPTH>(item, arenas, Rewriting(visitor)).map(|_| ())
+}
+
+/// Folds `item` and every item it points to into a single value with
+/// `folder`, as in [ArenaFold::try_fold_in](super::visit::ArenaFold::try_fold_in).
+pub fn fold_item<P, T, F, const DEPTH: usize>(
+    item: &T,
+    arenas: &P::DynArenas<'_>,
+    folder: &mut F,
+) -> ArenaResult<F::Output>
+where
+    P: IndexSum,
+    T: ArenaNode<P>,
+    F: Folder,
+{
+    let folding =
+        Folding::<F, DEPTH> { folder, outputs: WorkStack::new(), result: None };
+    let folding = walk::<P, T, _, DEPTH>(&mut item.clone(), arenas, folding)?;
+    // A walk which succeeds leaves `item` last of all, which sets the result.
+    folding
+        .result
+        .ok_or(ArenaError::DepthLimitExceeded)
+}
@@ -3,6 +3,8 @@
 use super::Arena;
 use super::ArenaItem;
 use super::handler::ArenaHandler;
+use super::index::Index;
+use super::index::IndexWidth;
 
 mod right_tuple {
     pub trait Sealed {}
@@ -25,7 +27,9 @@ pub trait RightTuple: right_tuple::Sealed {}
 impl<T: right_tuple::Sealed> RightTuple for T {}
 
 /// A [RightTuple] of references to (statically dispatched) [Arena]s.
-/// The `T` refers to these references.
+/// The `T` is a [RightTuple] of the [Index] types of these arenas, such as an
+/// [ArenaHandler::Indices], which fixes both their item types and their
+/// index widths.
 /// Encompasses the types:
 /// - `&()` (base case)
 /// - `(&'a impl Arena<_>, &'a ())`
@@ -54,19 +58,20 @@ impl ArenaTuple<()> for &'_ () {
     }
 }
 
-impl<'a, T, A, TS, AS> ArenaTuple<(T, TS)> for &'a (A, AS)
+impl<'a, T, I, A, TS, AS> ArenaTuple<(Index<T, I>, TS)> for &'a (A, AS)
 where
     T: ArenaItem,
-    A: Arena<T>,
+    I: IndexWidth,
+    A: Arena<T, I>,
     TS: RightTuple,
     &'a AS: ArenaTuple<TS>,
 {
     type DynArenaTuple =
-        (&'a dyn Arena<T>, <&'a AS as ArenaTuple<TS>>::DynArenaTuple);
+        (&'a dyn Arena<T, I>, <&'a AS as ArenaTuple<TS>>::DynArenaTuple);
 
     fn to_dyn_arenas(self) -> Self::DynArenaTuple {
         let (head, tail) = self;
-        (head as &dyn Arena<T>, ArenaTuple::<TS>::to_dyn_arenas(tail))
+        (head as &dyn Arena<T, I>, ArenaTuple::<TS>::to_dyn_arenas(tail))
     }
 }
 
@@ -1,37 +1,50 @@
 #[rustfmt::skip]
 #[cfg(doc)]
-use super::{Arena, handler::ArenaHandler, tuple::RightTuple};
+use super::{Arena, handler::ArenaHandler, index::Index, tuple::RightTuple};
 
-/// A way to create a right-associated [RightTuple] of types. Used to implement
-/// the [ArenaHandler::Indices] type, by passing in a nonempty list of types
-/// (separated by commas) for which indices of that type are referred to in the
-/// `Self` type.
+/// A way to create a right-associated [RightTuple] of [Index] types. Used to
+/// implement the [ArenaHandler::Indices] type, by passing in a nonempty list
+/// of types (separated by commas) for which indices of that type are referred
+/// to in the `Self` type.
+///
+/// Each type may be followed by `: width` to give the index width of its
+/// arena, which is otherwise the default of [Index].
 #[macro_export]
 macro_rules! handle_indices {
-    ($type:path, $($types:path),+) => {
-        ($type, handle_indices!($($types),+))
+    (
+        $type:path $(: $width:ty)?,
+        $($types:path $(: $widths:ty)?),+
+    ) => {
+        (
+            $crate::arena::index::Index<$type $(, $width)?>,
+            handle_indices!($($types $(: $widths)?),+),
+        )
     };
-    ($type:path) => {
-        ($type, ())
+    ($type:path $(: $width:ty)?) => {
+        ($crate::arena::index::Index<$type $(, $width)?>, ())
     };
 }
 
 /// A way to create a right-associated [RightTuple] of dynamically-dispatched
 /// [Arena] references. Used to implement the [ArenaHandler::DynArenas] type,
 /// by passing in the same lifetime argument as in [ArenaHandler::DynArenas],
-/// followed by the same nonempty list of types (separated by commas) as in
-/// [handle_indices].
+/// followed by the same nonempty list of types (separated by commas, and each
+/// with an optional width) as in [handle_indices].
 #[macro_export]
 macro_rules! handle_dyn_arenas {
-    ($l:lifetime, $type:path, $($types:path),+) => {
+    (
+        $l:lifetime,
+        $type:path $(: $width:ty)?,
+        $($types:path $(: $widths:ty)?),+
+    ) => {
         (
-            &'a dyn $crate::arena::Arena<$type>,
-            handle_dyn_arenas!($l, $($types),+),
+            &'a dyn $crate::arena::Arena<$type $(, $width)?>,
+            handle_dyn_arenas!($l, $($types $(: $widths)?),+),
         )
     };
-    ($l:lifetime, $type:path) => {
+    ($l:lifetime, $type:path $(: $width:ty)?) => {
         (
-            &'a dyn $crate::arena::Arena<$type>,
+            &'a dyn $crate::arena::Arena<$type $(, $width)?>,
             (),
         )
     };
@@ -0,0 +1,164 @@
+//! Checking that the items reachable from an item form a tree, such as after
+//! building it from untrusted data, where an [Index] may point anywhere.
+//!
+//! Unlike [check_reachability](super::reachability::check_reachability),
+//! which sweeps whole arenas for slots that nothing owns, validation only
+//! looks at what one item reaches, and checks that it would be safe to clone,
+//! compare or drop.
+
+use core::any;
+
+use super::Arena;
+use super::ArenaItem;
+use super::error::ArenaError;
+use super::error::ArenaResult;
+use super::index::Index;
+use super::index::IndexWidth;
+use super::reachability::SlotRef;
+use super::reachability::arena_address;
+use crate::alloc_types::BTreeMap;
+use crate::alloc_types::Vec;
+use crate::alloc_types::btree_map::Entry;
+
+/// Tells apart the slots of the arenas of a tuple, by the address of their
+/// arena and their position in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SlotId {
+    arena: usize,
+    position: usize,
+}
+
+/// How the item holding an [Index] owns the slot it points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Link {
+    /// The item is the only owner of the slot.
+    Owned,
+    /// The item is a [Chain](super::chain::Chain) cell, and the slot holds
+    /// the next cell, which it is the only owner of.
+    Tail,
+    /// The item is one of several owners of a reference-counted slot, as in
+    /// [share](super::shared::share).
+    Counted,
+}
+
+/// The outcome of [ArenaHandler::validate_in].
+///
+/// Each list is in the order in which the problems were met.
+///
+/// [ArenaHandler::validate_in]: super::handler::ArenaHandler::validate_in
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[allow(unused)]
+pub struct Validation {
+    /// The slots pointed to by an [Index] which is past the end of its arena.
+    pub out_of_bounds: Vec<SlotRef>,
+    /// The slots pointed to by an [Index] which are empty, or have been
+    /// reused since.
+    pub vacant: Vec<SlotRef>,
+    /// The slots which are not reference-counted, but are reached from more
+    /// than one owner.
+    pub multiply_owned: Vec<SlotRef>,
+    /// The chain cells whose tail cannot be followed to a
+    /// [Chain::Nil](super::chain::Chain::Nil), because it is out of bounds,
+    /// vacant, or leads back into the same chain.
+    pub unterminated: Vec<SlotRef>,
+    /// The slots which are reached again from one of the items they point to.
+    pub cycles: Vec<SlotRef>,
+}
+
+impl Validation {
+    /// Returns true if and only if no problem was found.
+    #[allow(unused)]
+    pub fn is_valid(&self) -> bool {
+        self.out_of_bounds.is_empty()
+            && self.vacant.is_empty()
+            && self.multiply_owned.is_empty()
+            && self.unterminated.is_empty()
+            && self.cycles.is_empty()
+    }
+}
+
+/// The slots reached so far while validating an item, each with the slot it
+/// was first reached from, which an [ArenaHandler::check_in] adds to.
+///
+/// [ArenaHandler::check_in]: super::handler::ArenaHandler::check_in
+#[derive(Debug, Default)]
+pub struct Validator {
+    reached: BTreeMap<SlotId, (Option<SlotId>, SlotRef)>,
+    validation: Validation,
+}
+
+impl Validator {
+    /// Follows `index` in `arena`, held by the item in the slot `parent`, or
+    /// by the item being validated if there is none, returning the [SlotId] of
+    /// the slot it points to if this is the first time it is reached, so that
+    /// the caller should go on to check the indices held by its item.
+    ///
+    /// Every problem met is recorded rather than returned as an error.
+    pub fn reach<T: ArenaItem, I: IndexWidth>(
+        &mut self,
+        parent: Option<SlotId>,
+        arena: &dyn Arena<T, I>,
+        index: &Index<T, I>,
+        link: Link,
+    ) -> ArenaResult<Option<SlotId>> {
+        let position = index.inner().to_usize();
+        let slot = SlotRef { item: any::type_name::<T>(), position };
+        let occupied = match arena.has_slot(index.clone()) {
+            Ok(occupied) => occupied,
+            Err(ArenaError::IndexOutOfBounds) => {
+                self.validation.out_of_bounds.push(slot);
+                self.unterminated(parent, link);
+                return Ok(None);
+            }
+            Err(ArenaError::StaleIndex | ArenaError::ExpectedFullSlot) => false,
+            Err(error) => return Err(error),
+        };
+        if !occupied {
+            self.validation.vacant.push(slot);
+            self.unterminated(parent, link);
+            return Ok(None);
+        }
+        let id = SlotId { arena: arena_address(arena), position };
+        if let Entry::Vacant(entry) = self.reached.entry(id) {
+            entry.insert((parent, slot));
+            return Ok(Some(id));
+        }
+        if self.is_ancestor(id, parent) {
+            self.validation.cycles.push(slot);
+            self.unterminated(parent, link);
+        } else if link != Link::Counted {
+            self.validation
+                .multiply_owned
+                .push(slot);
+        }
+        Ok(None)
+    }
+
+    /// Returns the problems found so far.
+    pub fn finish(self) -> Validation {
+        self.validation
+    }
+
+    /// Records the chain cell `parent` as unterminated if `link` is its tail.
+    fn unterminated(&mut self, parent: Option<SlotId>, link: Link) {
+        let parent = parent.and_then(|parent| self.reached.get(&parent));
+        if let (Link::Tail, Some(&(_, slot))) = (link, parent) {
+            self.validation.unterminated.push(slot);
+        }
+    }
+
+    /// Returns true if and only if `id` is `slot`, or the slot it was first
+    /// reached from, and so on.
+    fn is_ancestor(&self, id: SlotId, mut slot: Option<SlotId>) -> bool {
+        while let Some(current) = slot {
+            if current == id {
+                return true;
+            }
+            slot = self
+                .reached
+                .get(&current)
+                .and_then(|&(parent, _)| parent);
+        }
+        false
+    }
+}
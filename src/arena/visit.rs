@@ -0,0 +1,137 @@
+//! Visitors and folds over whole arena-allocated items, which are shown each
+//! item they reach, so that an analysis or a rewrite only needs to say what it
+//! does with the items it cares about.
+//!
+//! Every item is shown as a `dyn Any`, since the items of a tuple are of
+//! different types, and a visitor picks out the ones it handles with
+//! `downcast_ref`. The cells of a [Chain](super::chain::Chain) are walked
+//! through without being shown, so the items of a chain are shown as if their
+//! owner held them directly, and a [Counted](super::shared::Counted) item is
+//! shown as the item it wraps, once for each of its owners. A rewrite is not
+//! shown a counted item at all, since it would be rewritten for every owner
+//! at once.
+
+use core::any::Any;
+
+use super::error::ArenaResult;
+use super::handler::ArenaHandler;
+use super::tuple::DynArenasOf;
+
+/// What a visit does after the pre-order hook of an item.
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// Visits the items which this item points to, and then leaves it.
+    Continue,
+    /// Leaves this item without visiting the items it points to.
+    Skip,
+    /// Ends the visit straight away, without leaving any item.
+    Stop,
+}
+
+/// The hooks which an [ArenaVisit] runs on each item it reaches.
+#[allow(unused)]
+pub trait Visitor {
+    /// Runs on `node` before the items it points to are visited.
+    fn pre(&mut self, node: &dyn Any) -> ArenaResult<Flow> {
+        let _ = node;
+        Ok(Flow::Continue)
+    }
+
+    /// Runs on `node` after the items it points to are visited.
+    fn post(&mut self, node: &dyn Any) -> ArenaResult<()> {
+        let _ = node;
+        Ok(())
+    }
+}
+
+/// The hooks which an [ArenaVisitMut] runs on each item it reaches, which may
+/// rewrite the item in place.
+///
+/// A rewritten item is stored back in its slot after each hook, and the items
+/// it points to once its pre-order hook has run are the ones visited next. An
+/// item which is no longer pointed to must be dropped by the hook, which is
+/// free to use the arenas, since none of them is borrowed while it runs.
+#[allow(unused)]
+pub trait VisitorMut {
+    /// Runs on `node` before the items it points to are visited.
+    fn pre(&mut self, node: &mut dyn Any) -> ArenaResult<Flow> {
+        let _ = node;
+        Ok(Flow::Continue)
+    }
+
+    /// Runs on `node` after the items it points to are visited.
+    fn post(&mut self, node: &mut dyn Any) -> ArenaResult<()> {
+        let _ = node;
+        Ok(())
+    }
+}
+
+/// Folds an item into a single value, bottom-up: each item starts a value of
+/// its own, into which the value of each item it points to is combined in
+/// turn.
+#[allow(unused)]
+pub trait Folder {
+    /// The value of a folded item.
+    type Output;
+
+    /// Returns the value of `node` before any item it points to is combined
+    /// into it.
+    fn enter(&mut self, node: &dyn Any) -> ArenaResult<Self::Output>;
+
+    /// Combines the finished value `child` of an item which the owner of
+    /// `owner` points to into it.
+    fn combine(
+        &mut self,
+        owner: &mut Self::Output,
+        child: Self::Output,
+    ) -> ArenaResult<()>;
+}
+
+/// A trait to visit an arena-allocated item, and every item it points to.
+#[allow(unused)]
+pub trait ArenaVisit: ArenaHandler {
+    /// Runs the hooks of `visitor` on this item and on every item it points to
+    /// in `arenas`, depth first and in order.
+    ///
+    /// Returns the first error met by following an index or by a hook, or
+    /// [ArenaError::DepthLimitExceeded](super::error::ArenaError) if the items
+    /// are nested too deeply to be visited.
+    fn try_visit_in(
+        &self,
+        arenas: &DynArenasOf<'_, Self>,
+        visitor: &mut dyn Visitor,
+    ) -> ArenaResult<()>;
+}
+
+/// A trait to visit an arena-allocated item, and every item it points to,
+/// rewriting them in place.
+#[allow(unused)]
+pub trait ArenaVisitMut: ArenaHandler {
+    /// Runs the hooks of `visitor` on this item and on every item it points to
+    /// in `arenas`, as in [ArenaVisit::try_visit_in], except that neither a
+    /// [Counted](super::shared::Counted) item nor anything it points to is
+    /// visited.
+    ///
+    /// The node which points to a counted item is still visited, and may be
+    /// rewritten to point elsewhere, dropping its owner of the counted item.
+    fn try_visit_mut_in(
+        &mut self,
+        arenas: &DynArenasOf<'_, Self>,
+        visitor: &mut dyn VisitorMut,
+    ) -> ArenaResult<()>;
+}
+
+/// A trait to fold an arena-allocated item, along with every item it points
+/// to, into a single value.
+#[allow(unused)]
+pub trait ArenaFold: ArenaHandler {
+    /// Returns the value which `folder` gives this item, after combining the
+    /// values of the items it points to in `arenas` into it, as in
+    /// [ArenaVisit::try_visit_in].
+    fn try_fold_in<F: Folder>(
+        &self,
+        arenas: &DynArenasOf<'_, Self>,
+        folder: &mut F,
+    ) -> ArenaResult<F::Output>;
+}
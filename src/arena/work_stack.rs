@@ -0,0 +1,80 @@
+//! A stack of pending steps of fixed capacity, which drives the traversals of
+//! an [ArenaHandler](super::handler::ArenaHandler) in place of recursion.
+//!
+//! Recursing once per nested item lets a deep enough item overflow the
+//! machine stack, which is small on a microcontroller. Keeping the pending
+//! steps in a [WorkStack] instead bounds the memory a traversal needs, and a
+//! traversal which would need more returns
+//! [ArenaError::DepthLimitExceeded].
+
+use super::error::ArenaError;
+use super::error::ArenaResult;
+
+/// The default capacity of the [WorkStack] of each traversal. A traversal
+/// needs about one pending step for each level of nesting of the item it
+/// walks, however long each level is.
+///
+/// A derived type walks with another capacity if it is given one by
+/// `#[arena(depth = ..)]`.
+pub const TRAVERSAL_DEPTH: usize = 64;
+
+/// A last-in, first-out stack which holds at most `N` items, without
+/// allocating.
+///
+/// The stack lives wherever it is declared, usually on the machine stack, and
+/// takes `N` times the size of `Option<T>`, plus a `usize`. On a 64-bit
+/// target, at the default capacity, a stack of
+/// [PatternIndex](crate::ast::pattern::PatternIndex) steps takes 392 bytes,
+/// and a stack of pairs of them, as comparing and copying use, takes 776.
+#[derive(Debug)]
+pub struct WorkStack<T, const N: usize = TRAVERSAL_DEPTH> {
+    items: [Option<T>; N],
+    len: usize,
+}
+
+impl<T, const N: usize> Default for WorkStack<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> WorkStack<T, N> {
+    /// Creates an empty stack.
+    pub const fn new() -> Self {
+        Self { items: [const { None }; N], len: 0 }
+    }
+
+    /// Pushes `item` onto the stack, or returns
+    /// [ArenaError::DepthLimitExceeded] if it already holds `N` items.
+    pub fn push(&mut self, item: T) -> ArenaResult<()> {
+        let len = self
+            .len
+            .checked_add(1)
+            .ok_or(ArenaError::DepthLimitExceeded)?;
+        let slot = self
+            .items
+            .get_mut(self.len)
+            .ok_or(ArenaError::DepthLimitExceeded)?;
+        *slot = Some(item);
+        self.len = len;
+        Ok(())
+    }
+
+    /// Removes and returns the item pushed last, if any.
+    pub fn pop(&mut self) -> Option<T> {
+        self.len = self.len.checked_sub(1)?;
+        self.items.get_mut(self.len)?.take()
+    }
+
+    /// Returns the number of items on the stack.
+    #[allow(unused)]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if and only if the stack holds no items.
+    #[allow(unused)]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
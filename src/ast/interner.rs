@@ -0,0 +1,592 @@
+//! Hash-consing of [Pattern]s, so that structurally identical subpatterns are
+//! stored once as a [SharedPattern] however many times they are built, and
+//! equal leaves once as a [Pattern::Repeated].
+
+use core::hash::Hasher;
+
+use super::pattern::DefaultWidths;
+use super::pattern::Leaf;
+use super::pattern::Pattern;
+use super::pattern::PatternChain;
+use super::pattern::PatternIndex;
+use super::pattern::PatternWidths;
+use super::pattern::SharedPattern;
+use super::pattern::TimedStep;
+use super::pattern::TimedStepChain;
+use super::traversal::PatternArenas;
+use crate::alloc_types::BTreeMap;
+use crate::alloc_types::Vec;
+use crate::arena::Arena;
+use crate::arena::chain::Chain;
+use crate::arena::equality::ArenaEq;
+use crate::arena::error::ArenaResult;
+use crate::arena::extension::Inspect;
+use crate::arena::handler::ArenaHandler;
+use crate::arena::hash::ArenaHash;
+use crate::arena::index::Index;
+use crate::arena::index::IndexWidth;
+use crate::arena::node::ArenaNode;
+use crate::arena::node::MAX_CHILDREN;
+use crate::arena::shared::retain;
+use crate::arena::shared::share;
+use crate::arena::tuple::DynArenasOf;
+use crate::arena::work_stack::TRAVERSAL_DEPTH;
+use crate::arena::work_stack::WorkStack;
+
+/// A 64-bit FNV-1a [Hasher], which needs neither allocation nor a source of
+/// randomness.
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// The [Index] of a [SharedPattern] with the given [PatternWidths].
+type SharedIndex<W> = Index<SharedPattern<W>, <W as PatternWidths>::Shared>;
+
+/// The [Index] of a [Pattern] with the given [PatternWidths].
+type LeafIndex<W> = Index<Pattern<W>, <W as PatternWidths>::Pattern>;
+
+/// The leaves which [Interner::share_leaves] pointed elsewhere while rewriting
+/// a single item: the [Index] each was held by, and the [Index] of the
+/// [Pattern::Repeated] slot which gained an owner in its place.
+type Rewrites<W> = WorkStack<(LeafIndex<W>, LeafIndex<W>), MAX_CHILDREN>;
+
+/// What [Interner::intern] did with the pattern it was given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(unused)]
+pub enum Interned {
+    /// The pattern was given back as it was, because it is already shared, or
+    /// is a leaf, which is shared by the [Index] its owner holds instead; see
+    /// [Interner::intern_leaf].
+    Unshared,
+    /// No structurally identical pattern was stored, so the pattern was moved
+    /// into a new slot of the arena of [SharedPattern]s, or the leaf into a
+    /// new [Pattern::Repeated] slot.
+    Stored,
+    /// A structurally identical pattern was already stored, so the pattern was
+    /// dropped from its arenas, and the stored one gained an owner instead.
+    Deduplicated,
+}
+
+/// A table of the [SharedPattern]s stored by interning, looked up by the hash
+/// of their structure, and of the [Pattern::Repeated] leaves, looked up by
+/// their [Leaf].
+///
+/// The table does not own the patterns it stores: each is kept alive only by
+/// the [Pattern::Shared] nodes returned by [Interner::intern], or the owners
+/// of the [Index] returned by [Interner::intern_leaf], and dropping the last
+/// of them with [ArenaHandler::drop_in] releases it as usual. Entries whose
+/// slot has since been released or reused are found by the generation of
+/// their [Index], and are removed when next looked up.
+///
+/// Each stored pattern is filed under the hash it had when it was stored, so
+/// it must not change afterwards. [ArenaVisitMut](crate::arena::visit::ArenaVisitMut)
+/// never rewrites a [SharedPattern] or a [Pattern::Repeated] leaf, but it is a
+/// logic error to change one, or anything it points to, through
+/// [Inspect::inspect_mut].
+///
+/// It is also a logic error to intern patterns from arenas other than those
+/// the interner was first used with, or to keep using the interner after the
+/// arena of [SharedPattern]s has been compacted; see [Interner::clear].
+///
+/// [Interner::intern_tree] walks a pattern with a [WorkStack] of capacity
+/// `DEPTH`, holding one frame for each level of nesting of the pattern.
+#[derive(Debug)]
+pub struct Interner<
+    W: PatternWidths = DefaultWidths,
+    const DEPTH: usize = TRAVERSAL_DEPTH,
+> {
+    table: BTreeMap<u64, Vec<SharedIndex<W>>>,
+    leaves: BTreeMap<Leaf, LeafIndex<W>>,
+    deduplicated: usize,
+}
+
+impl<W: PatternWidths, const DEPTH: usize> Default for Interner<W, DEPTH> {
+    fn default() -> Self {
+        Self {
+            table: BTreeMap::new(),
+            leaves: BTreeMap::new(),
+            deduplicated: 0,
+        }
+    }
+}
+
+impl<W: PatternWidths> Interner<W> {
+    /// Creates an interner which has not stored any pattern yet, with the
+    /// default capacity of its [WorkStack]; an interner of another `DEPTH`
+    /// is created by [Default::default].
+    #[allow(unused)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<W: PatternWidths, const DEPTH: usize> Interner<W, DEPTH> {
+    /// Returns the number of patterns which have been deduplicated so far.
+    #[allow(unused)]
+    pub fn deduplicated(&self) -> usize {
+        self.deduplicated
+    }
+
+    /// Forgets every stored pattern, without releasing any of them, so that
+    /// patterns interned afterwards are stored again.
+    #[allow(unused)]
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.leaves.clear();
+    }
+
+    /// Returns a [Pattern::Shared] node for `pattern`, which must have been
+    /// created in `arenas`, pointing to a structurally identical pattern
+    /// which is already stored if there is one, or else to `pattern` itself,
+    /// moved into a new shared slot. Leaves, [Pattern::Shared] nodes and
+    /// [Pattern::Repeated] nodes are given back as they are.
+    ///
+    /// Only `pattern` as a whole is looked up, so its subpatterns are only
+    /// shared if they were interned before it was built; see
+    /// [Interner::intern_tree].
+    ///
+    /// If `pattern` cannot be stored, it is dropped from `arenas` before the
+    /// error is returned.
+    #[allow(unused)]
+    pub fn intern<'a>(
+        &mut self,
+        pattern: Pattern<W>,
+        arenas: &DynArenasOf<'a, Pattern<W>>,
+    ) -> ArenaResult<(Pattern<W>, Interned)> {
+        if let Pattern::Note(_)
+        | Pattern::Silence
+        | Pattern::Shared(_)
+        | Pattern::Repeated(..) = pattern
+        {
+            return Ok((pattern, Interned::Unshared));
+        }
+        let (_, (_, (_, (_, (shared_arena, ()))))) = *arenas;
+
+        let mut state = Fnv::default();
+        // Patterns which are equal by [ArenaEq] hash the same, since hashing
+        // follows every index as comparing does.
+        let hashed = pattern.try_hash_in(arenas, &mut state);
+        if let Err(error) = hashed {
+            // The hashing failure is reported rather than any failure to
+            // release the pattern.
+            let _ = pattern.try_drop_in(arenas);
+            return Err(error);
+        }
+        let candidates = self
+            .table
+            .entry(state.finish())
+            .or_default();
+        candidates
+            .retain(|index| shared_arena.has_slot(index.clone()) == Ok(true));
+        let mut existing = None;
+        for index in candidates.iter() {
+            let equal = shared_arena
+                .inspect(index.clone(), |shared| {
+                    ArenaEq::try_eq_in(shared.get(), &pattern, arenas, arenas)
+                })
+                .and_then(|equal| equal);
+            match equal {
+                Ok(true) => {
+                    existing = Some(index.clone());
+                    break;
+                }
+                Ok(false) => {}
+                Err(error) => {
+                    // As with hashing, the comparison failure is reported
+                    // rather than any failure to release the pattern.
+                    let _ = pattern.try_drop_in(arenas);
+                    return Err(error);
+                }
+            }
+        }
+
+        if let Some(index) = existing {
+            pattern.try_drop_in(arenas)?;
+            let index = retain(shared_arena, &index)?;
+            self.deduplicated = self.deduplicated.saturating_add(1);
+            return Ok((Pattern::Shared(index), Interned::Deduplicated));
+        }
+        match share(shared_arena, pattern) {
+            Ok(index) => {
+                candidates.push(index.clone());
+                Ok((Pattern::Shared(index), Interned::Stored))
+            }
+            Err((error, pattern)) => {
+                let _ = pattern.try_drop_in(arenas);
+                Err(error)
+            }
+        }
+    }
+
+    /// Returns the [Index] of a [Pattern::Repeated] slot holding `leaf`,
+    /// adding an owner to the one which is already stored if there is one, or
+    /// else storing `leaf` in a new slot of `arenas`, so that equal leaves
+    /// take a single slot however many times they are repeated.
+    #[allow(unused)]
+    pub fn intern_leaf<'a>(
+        &mut self,
+        leaf: Leaf,
+        arenas: &DynArenasOf<'a, Pattern<W>>,
+    ) -> ArenaResult<(LeafIndex<W>, Interned)> {
+        let (pattern_arena, _) = *arenas;
+        if let Some(index) = self.retain_leaf(&leaf, pattern_arena)? {
+            return Ok((index, Interned::Deduplicated));
+        }
+        let index = pattern_arena.alloc(Pattern::Repeated(leaf.clone(), 1))?;
+        self.leaves.insert(leaf, index.clone());
+        Ok((index, Interned::Stored))
+    }
+
+    /// Points every leaf of `pattern` to a [Pattern::Repeated] slot as in
+    /// [Interner::intern_leaf], and interns every subpattern from the leaves
+    /// up, rewriting each in place, and then interns `pattern` itself as in
+    /// [Interner::intern].
+    ///
+    /// If a subpattern cannot be interned, `pattern` is dropped from `arenas`
+    /// before the error is returned.
+    #[allow(unused)]
+    pub fn intern_tree<'a>(
+        &mut self,
+        mut pattern: Pattern<W>,
+        arenas: &DynArenasOf<'a, Pattern<W>>,
+    ) -> ArenaResult<(Pattern<W>, Interned)> {
+        let interned = self
+            .share_leaves(&mut pattern, arenas)
+            .and_then(|()| self.intern_children(&pattern, arenas));
+        if let Err(error) = interned {
+            let _ = pattern.try_drop_in(arenas);
+            return Err(error);
+        }
+        self.intern(pattern, arenas)
+    }
+
+    /// Helper function to add an owner to the stored [Pattern::Repeated] slot
+    /// holding `leaf`, returning its [Index], or to forget it if it has been
+    /// released since.
+    fn retain_leaf(
+        &mut self,
+        leaf: &Leaf,
+        pattern_arena: &dyn Arena<Pattern<W>, W::Pattern>,
+    ) -> ArenaResult<Option<LeafIndex<W>>> {
+        let Some(index) = self.leaves.get(leaf) else {
+            return Ok(None);
+        };
+        let retained = match pattern_arena.has_slot(index.clone()) {
+            Ok(true) => {
+                pattern_arena.inspect_mut(index.clone(), |pattern| {
+                    match pattern {
+                        Pattern::Repeated(stored, owners) if stored == leaf => {
+                            *owners = owners.saturating_add(1);
+                            true
+                        }
+                        _ => false,
+                    }
+                })?
+            }
+            _ => false,
+        };
+        if !retained {
+            self.leaves.remove(leaf);
+            return Ok(None);
+        }
+        self.deduplicated = self.deduplicated.saturating_add(1);
+        Ok(Some(index.clone()))
+    }
+
+    /// Helper function to point each leaf which `pattern` reaches, other than
+    /// through a [Pattern::Shared] node, to a [Pattern::Repeated] slot, as
+    /// described in [Interner::map_leaves].
+    ///
+    /// Each item on the way is shallowly copied out of its slot, has its
+    /// leaves rewritten, and is written back, so that the arena is not
+    /// borrowed meanwhile. The slot each rewritten leaf was in is released
+    /// only once its owner has been written back; if that fails, the owners
+    /// gained for it are released instead, leaving its owner as it was.
+    fn share_leaves<'a>(
+        &mut self,
+        pattern: &mut Pattern<W>,
+        arenas: &DynArenasOf<'a, Pattern<W>>,
+    ) -> ArenaResult<()> {
+        let pattern_arenas = PatternArenas::new(arenas);
+        let mut stack = WorkStack::<PatternIndex<W>, DEPTH>::new();
+        let mut rewrites = Rewrites::<W>::new();
+        let mut children = WorkStack::new();
+        let mapped = self.map_leaves(
+            pattern,
+            pattern_arenas.patterns,
+            &mut rewrites,
+            &mut children,
+        );
+        // Whatever was rewritten before a failure is kept by `pattern`, which
+        // is not in a slot.
+        let released = release_rewrites(&mut rewrites, true, arenas);
+        mapped.and(released)?;
+        while let Some(child) = children.pop() {
+            stack.push(child)?;
+        }
+        while let Some(pending) = stack.pop() {
+            match pending {
+                PatternIndex::Pattern(index) => self.share_leaves_at(
+                    pattern_arenas.patterns,
+                    index,
+                    arenas,
+                    &mut stack,
+                ),
+                PatternIndex::PatternChain(index) => self.share_leaves_at(
+                    pattern_arenas.chains,
+                    index,
+                    arenas,
+                    &mut stack,
+                ),
+                PatternIndex::TimedStep(index) => self.share_leaves_at(
+                    pattern_arenas.timed_steps,
+                    index,
+                    arenas,
+                    &mut stack,
+                ),
+                PatternIndex::TimedStepChain(index) => self.share_leaves_at(
+                    pattern_arenas.timed_step_chains,
+                    index,
+                    arenas,
+                    &mut stack,
+                ),
+                PatternIndex::SharedPattern(_) => Ok(()),
+            }?;
+        }
+        Ok(())
+    }
+
+    /// Helper function to rewrite the leaves of the item at `index` in `arena`,
+    /// and push the other items it points to onto `stack`, as described in
+    /// [Interner::share_leaves].
+    fn share_leaves_at<'a, T, I>(
+        &mut self,
+        arena: &dyn Arena<T, I>,
+        index: Index<T, I>,
+        arenas: &DynArenasOf<'a, Pattern<W>>,
+        stack: &mut WorkStack<PatternIndex<W>, DEPTH>,
+    ) -> ArenaResult<()>
+    where
+        T: ArenaNode<PatternIndex<W>>,
+        I: IndexWidth,
+    {
+        let (pattern_arena, _) = *arenas;
+        let mut item = arena.inspect(index.clone(), T::clone)?;
+        let mut rewrites = Rewrites::<W>::new();
+        let mut children = WorkStack::new();
+        let written = self
+            .map_leaves(&mut item, pattern_arena, &mut rewrites, &mut children)
+            .and_then(|()| arena.inspect_mut(index, |slot| *slot = item));
+        let released = release_rewrites(&mut rewrites, written.is_ok(), arenas);
+        written.and(released)?;
+        while let Some(child) = children.pop() {
+            stack.push(child)?;
+        }
+        Ok(())
+    }
+
+    /// Helper function to point each leaf which `item` holds an [Index] of to
+    /// a [Pattern::Repeated] slot holding it: the one already stored, which
+    /// gains an owner, recording the rewrite in `rewrites`, or else the slot of
+    /// the leaf itself, which is made into one. Every other [Index] held by
+    /// `item`, other than one of a [SharedPattern], is pushed onto `children`.
+    fn map_leaves<T: ArenaNode<PatternIndex<W>>>(
+        &mut self,
+        item: &mut T,
+        pattern_arena: &dyn Arena<Pattern<W>, W::Pattern>,
+        rewrites: &mut Rewrites<W>,
+        children: &mut WorkStack<PatternIndex<W>, MAX_CHILDREN>,
+    ) -> ArenaResult<()> {
+        item.map_children(&mut |child| {
+            let index = match child {
+                PatternIndex::Pattern(index) => index,
+                // A shared pattern is left as it was stored.
+                PatternIndex::SharedPattern(_) => return Ok(child),
+                child => {
+                    children.push(child.clone())?;
+                    return Ok(child);
+                }
+            };
+            let Some(leaf) = pattern_arena.inspect(index.clone(), leaf_of)?
+            else {
+                children.push(PatternIndex::Pattern(index.clone()))?;
+                return Ok(PatternIndex::Pattern(index));
+            };
+            let Some(shared) = self.retain_leaf(&leaf, pattern_arena)? else {
+                pattern_arena.inspect_mut(index.clone(), |slot| {
+                    *slot = Pattern::Repeated(leaf.clone(), 1);
+                })?;
+                self.leaves.insert(leaf, index.clone());
+                return Ok(PatternIndex::Pattern(index));
+            };
+            if let Err(error) = rewrites.push((index, shared.clone())) {
+                let _ = release_leaf(pattern_arena, shared);
+                return Err(error);
+            }
+            Ok(PatternIndex::Pattern(shared))
+        })
+    }
+
+    /// Helper function to intern each subpattern which `pattern` points to,
+    /// from the leaves up, writing the result of each back into its slot.
+    ///
+    /// Each subpattern is left on the stack while its own subpatterns are
+    /// interned, along with the cell of its chain to continue from. It is then
+    /// shallowly copied out of its slot and interned with [Interner::intern],
+    /// so that the arena is not borrowed meanwhile. The copy left in the slot
+    /// is overwritten without being followed, since interning either moved or
+    /// dropped what it points to. If interning fails, the slot is left holding
+    /// [Pattern::Silence], so that dropping its owner does not follow the
+    /// released indices again.
+    fn intern_children<'a>(
+        &mut self,
+        pattern: &Pattern<W>,
+        arenas: &DynArenasOf<'a, Pattern<W>>,
+    ) -> ArenaResult<()> {
+        let pattern_arenas = PatternArenas::new(arenas);
+        let mut stack = WorkStack::<InternFrame<W>, DEPTH>::new();
+        if let Some(cursor) = ChildCursor::of(pattern) {
+            stack.push(InternFrame { index: None, cursor })?;
+        }
+        while let Some(InternFrame { index, cursor }) = stack.pop() {
+            let child = match cursor {
+                ChildCursor::Patterns(Chain::Cons { head, tail }) => {
+                    let tail = pattern_arenas
+                        .chains
+                        .inspect(tail, Chain::clone)?;
+                    let cursor = ChildCursor::Patterns(tail);
+                    stack.push(InternFrame { index, cursor })?;
+                    head
+                }
+                ChildCursor::TimedSteps(Chain::Cons { head, tail }) => {
+                    let tail = pattern_arenas
+                        .timed_step_chains
+                        .inspect(tail, Chain::clone)?;
+                    let cursor = ChildCursor::TimedSteps(tail);
+                    stack.push(InternFrame { index, cursor })?;
+                    pattern_arenas
+                        .timed_steps
+                        .inspect(head, |TimedStep(_, index)| index.clone())?
+                }
+                ChildCursor::Patterns(Chain::Nil)
+                | ChildCursor::TimedSteps(Chain::Nil) => {
+                    if let Some(index) = index {
+                        self.intern_at(index, pattern_arenas.patterns, arenas)?;
+                    }
+                    continue;
+                }
+            };
+            let cursor = pattern_arenas
+                .patterns
+                .inspect(child.clone(), ChildCursor::of)?;
+            if let Some(cursor) = cursor {
+                stack.push(InternFrame { index: Some(child), cursor })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Helper function to intern the subpattern at `index`, whose own
+    /// subpatterns have been interned already, as described in
+    /// [Interner::intern_children].
+    fn intern_at<'a>(
+        &mut self,
+        index: Index<Pattern<W>, W::Pattern>,
+        pattern_arena: &dyn Arena<Pattern<W>, W::Pattern>,
+        arenas: &DynArenasOf<'a, Pattern<W>>,
+    ) -> ArenaResult<()> {
+        let pattern = pattern_arena.inspect(index.clone(), Pattern::clone)?;
+        let (interned, result) = match self.intern(pattern, arenas) {
+            Ok((interned, _)) => (interned, Ok(())),
+            Err(error) => (Pattern::Silence, Err(error)),
+        };
+        pattern_arena.inspect_mut(index, |slot| *slot = interned)?;
+        result
+    }
+}
+
+/// The cell of a chain of subpatterns from which [Interner::intern_children]
+/// continues.
+enum ChildCursor<W: PatternWidths> {
+    Patterns(PatternChain<W>),
+    TimedSteps(TimedStepChain<W>),
+}
+
+impl<W: PatternWidths> ChildCursor<W> {
+    /// Returns a cursor at the start of the subpatterns of `pattern`, if it
+    /// has any.
+    fn of(pattern: &Pattern<W>) -> Option<Self> {
+        match pattern {
+            Pattern::Cat(chain)
+            | Pattern::Seq(chain)
+            | Pattern::Stack(chain) => Some(Self::Patterns(chain.clone())),
+            Pattern::TimeCat(chain) => Some(Self::TimedSteps(chain.clone())),
+            Pattern::Note(_)
+            | Pattern::Silence
+            | Pattern::Shared(_)
+            | Pattern::Repeated(..) => None,
+        }
+    }
+}
+
+/// Returns the [Leaf] which `pattern` is, if it is one which is not shared.
+fn leaf_of<W: PatternWidths>(pattern: &Pattern<W>) -> Option<Leaf> {
+    match pattern {
+        Pattern::Note(unit) => Some(Leaf::Note(*unit)),
+        Pattern::Silence => Some(Leaf::Silence),
+        _ => None,
+    }
+}
+
+/// Removes the owner holding `index` from its leaf, which is dropped once it
+/// has no owners left.
+fn release_leaf<W: PatternWidths>(
+    pattern_arena: &dyn Arena<Pattern<W>, W::Pattern>,
+    index: LeafIndex<W>,
+) -> ArenaResult<()> {
+    <Pattern<W> as ArenaNode<PatternIndex<W>>>::release(pattern_arena, index)
+        .map(drop)
+}
+
+/// Releases, for each rewrite in `rewrites`, the slot of the leaf which was
+/// pointed elsewhere if `kept` is true, or else the owner which was gained in
+/// its place, returning the first error met.
+fn release_rewrites<W: PatternWidths>(
+    rewrites: &mut Rewrites<W>,
+    kept: bool,
+    arenas: &DynArenasOf<'_, Pattern<W>>,
+) -> ArenaResult<()> {
+    let (pattern_arena, _) = *arenas;
+    let mut result = Ok(());
+    while let Some((leaf, shared)) = rewrites.pop() {
+        let released = if kept { leaf } else { shared };
+        let released = release_leaf(pattern_arena, released);
+        result = result.and(released);
+    }
+    result
+}
+
+/// A pending step of [Interner::intern_children]: the subpattern at `index`,
+/// or the pattern being interned if there is none, whose subpatterns from
+/// `cursor` on are still to be interned.
+struct InternFrame<W: PatternWidths> {
+    index: Option<Index<Pattern<W>, W::Pattern>>,
+    cursor: ChildCursor<W>,
+}
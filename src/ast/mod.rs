@@ -1,4 +1,4 @@
-pub mod equality;
-pub mod handler;
+pub mod interner;
 pub mod note;
 pub mod pattern;
+pub mod traversal;
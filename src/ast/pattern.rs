@@ -1,25 +1,147 @@
+use core::fmt::Debug;
+
 #[cfg(test)]
 use proptest_derive::Arbitrary;
+use synth_derive::ArenaDebug;
+use synth_derive::ArenaEq;
+use synth_derive::ArenaFold;
+use synth_derive::ArenaHandler;
+use synth_derive::ArenaHash;
+use synth_derive::ArenaOrd;
+use synth_derive::ArenaVisit;
+use synth_derive::ArenaVisitMut;
 
 use super::note::NoteUnit;
 use crate::arena::chain::Chain;
 use crate::arena::index::Index;
+use crate::arena::index::IndexWidth;
+use crate::arena::shared::Counted;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(test, derive(Arbitrary))]
 pub struct TimeUnit(pub u32);
 
+/// The index widths of each of the arenas which a [Pattern] is allocated in,
+/// so that a small pattern can be kept in arenas with narrower indices.
+pub trait PatternWidths:
+    Debug + Clone + Copy + PartialEq + Eq + PartialOrd + Ord + 'static
+{
+    /// The index width of the arena of [Pattern]s.
+    type Pattern: IndexWidth;
+    /// The index width of the arena of [PatternChain]s.
+    type PatternChain: IndexWidth;
+    /// The index width of the arena of [TimedStep]s.
+    type TimedStep: IndexWidth;
+    /// The index width of the arena of [TimedStepChain]s.
+    type TimedStepChain: IndexWidth;
+    /// The index width of the arena of [SharedPattern]s.
+    type Shared: IndexWidth;
+}
+
+/// The [PatternWidths] where every arena has the default index width of
+/// [Index].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DefaultWidths;
+
+impl PatternWidths for DefaultWidths {
+    type Pattern = u16;
+    type PatternChain = u16;
+    type TimedStep = u16;
+    type TimedStepChain = u16;
+    type Shared = u16;
+}
+
+/// A [Chain] of [Pattern]s with the given [PatternWidths].
+pub type PatternChain<W = DefaultWidths> = Chain<
+    Pattern<W>,
+    <W as PatternWidths>::Pattern,
+    <W as PatternWidths>::PatternChain,
+>;
+
+/// A [Chain] of [TimedStep]s with the given [PatternWidths].
+pub type TimedStepChain<W = DefaultWidths> = Chain<
+    TimedStep<W>,
+    <W as PatternWidths>::TimedStep,
+    <W as PatternWidths>::TimedStepChain,
+>;
+
+/// A [Pattern] without subpatterns, which [Pattern::Repeated] stands for.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Leaf {
+    Note(NoteUnit),
+    Silence,
+}
+
+/// A reference-counted [Pattern], which several [Pattern::Shared] nodes can
+/// point to at once.
+pub type SharedPattern<W = DefaultWidths> = Counted<Pattern<W>>;
+
 #[allow(dead_code)]
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct TimedStep(pub TimeUnit, pub Index<Pattern>);
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    ArenaHandler,
+    ArenaEq,
+    ArenaOrd,
+    ArenaHash,
+    ArenaDebug,
+    ArenaVisit,
+    ArenaVisitMut,
+    ArenaFold,
+)]
+#[arena(index = PatternIndex)]
+pub struct TimedStep<W: PatternWidths = DefaultWidths>(
+    pub TimeUnit,
+    pub Index<Pattern<W>, W::Pattern>,
+);
 
 #[allow(dead_code)]
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub enum Pattern {
-    Cat(Chain<Self>),
-    Seq(Chain<Self>),
-    Stack(Chain<Self>),
-    TimeCat(Chain<TimedStep>),
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    ArenaHandler,
+    ArenaEq,
+    ArenaOrd,
+    ArenaHash,
+    ArenaDebug,
+    ArenaVisit,
+    ArenaVisitMut,
+    ArenaFold,
+)]
+#[arena(
+    index = PatternIndex,
+    arenas(
+        Pattern<W>: W::Pattern,
+        PatternChain<W>: W::PatternChain,
+        TimedStep<W>: W::TimedStep,
+        TimedStepChain<W>: W::TimedStepChain,
+        SharedPattern<W>: W::Shared,
+    )
+)]
+pub enum Pattern<W: PatternWidths = DefaultWidths> {
+    Cat(#[arena] PatternChain<W>),
+    Seq(#[arena] PatternChain<W>),
+    Stack(#[arena] PatternChain<W>),
+    TimeCat(#[arena] TimedStepChain<W>),
     Note(NoteUnit),
     Silence,
+    /// A pattern which is stored once however many times it is repeated.
+    /// Cloning this node adds an owner to the pattern rather than copying it,
+    /// and dropping it only drops the pattern once it has no owners left.
+    Shared(Index<SharedPattern<W>, W::Shared>),
+    /// A leaf which every owner points to by the same [Index], along with the
+    /// number of those owners, so that it takes a single slot however many
+    /// times it is repeated. Copying an owner adds one rather than copying the
+    /// leaf, and dropping an owner only drops the leaf once it has no owners
+    /// left. The number of owners is left out of comparisons, hashing and
+    /// formatting.
+    Repeated(Leaf, #[arena(owners)] usize),
 }
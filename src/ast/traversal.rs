@@ -0,0 +1,35 @@
+//! Helpers for the traversals of [Pattern]s which are written by hand rather
+//! than generated by `#[derive(ArenaHandler)]`, such as [interning].
+//!
+//! [interning]: super::interner::Interner
+
+use super::pattern::Pattern;
+use super::pattern::PatternChain;
+use super::pattern::PatternWidths;
+use super::pattern::SharedPattern;
+use super::pattern::TimedStep;
+use super::pattern::TimedStepChain;
+use crate::arena::Arena;
+use crate::arena::tuple::DynArenasOf;
+
+/// The arenas of a [Pattern], taken out of their [DynArenasOf] tuple.
+#[derive(Clone, Copy)]
+pub struct PatternArenas<'a, W: PatternWidths> {
+    pub patterns: &'a dyn Arena<Pattern<W>, W::Pattern>,
+    pub chains: &'a dyn Arena<PatternChain<W>, W::PatternChain>,
+    pub timed_steps: &'a dyn Arena<TimedStep<W>, W::TimedStep>,
+    pub timed_step_chains: &'a dyn Arena<TimedStepChain<W>, W::TimedStepChain>,
+    #[allow(unused)]
+    pub shared: &'a dyn Arena<SharedPattern<W>, W::Shared>,
+}
+
+impl<'a, W: PatternWidths> PatternArenas<'a, W> {
+    /// Names each arena of the given tuple.
+    pub fn new(arenas: &DynArenasOf<'a, Pattern<W>>) -> Self {
+        let (
+            patterns,
+            (chains, (timed_steps, (timed_step_chains, (shared, ())))),
+        ) = *arenas;
+        Self { patterns, chains, timed_steps, timed_step_chains, shared }
+    }
+}
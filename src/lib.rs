@@ -10,6 +10,18 @@
     unused,
     rustdoc::broken_intra_doc_links
 )]
+#![cfg_attr(
+    all(feature = "no-panic", not(test)),
+    deny(
+        clippy::panic,
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::unreachable,
+        clippy::todo,
+        clippy::indexing_slicing,
+        clippy::arithmetic_side_effects
+    )
+)]
 
 /// Used by [alloc_types].
 #[allow(unused_extern_crates)]
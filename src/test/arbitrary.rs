@@ -10,6 +10,8 @@ use crate::alloc_types::Rc;
 use crate::arena::chain::Chain;
 use crate::arena::error::ArenaResult;
 use crate::arena::handler::ArenaHandler;
+use crate::arena::shared::retain;
+use crate::arena::shared::share;
 use crate::arena::tuple::DynArenasOf;
 use crate::ast::note::NoteUnit;
 use crate::ast::pattern::Pattern;
@@ -52,6 +54,10 @@ impl<T: ArenaHandler> ArenasTo<T> {
     }
 }
 
+/// Generates functions which build a pattern in the given arenas.
+///
+/// A function which fails partway does not undo its allocations, so it should
+/// be run inside a [crate::arena::transaction::transaction].
 #[allow(unused)]
 pub fn arb_pattern() -> impl Strategy<Value = ArenasTo<Pattern>> {
     let leaf = prop_oneof![
@@ -69,32 +75,52 @@ pub fn arb_pattern() -> impl Strategy<Value = ArenasTo<Pattern>> {
                 Just(Pattern::Seq as fn(_) -> _),
                 Just(Pattern::Stack as fn(_) -> _),
             ];
-            (prop::collection::vec(inner, 0..10), functions).prop_map(
-                |(xs, f)| {
-                    ArenasTo::new(move |arenas: DynArenasOf<'_, Pattern>| {
-                        let (
-                            pattern_arena,
-                            (
-                                chain_arena,
-                                (
-                                    _timed_step_arena,
-                                    (_timed_step_chain_arena, ()),
-                                ),
-                            ),
-                        ) = arenas;
-                        let chain_cons = |chain, x: ArenasTo<Pattern>| {
-                            ArenaResult::Ok(Chain::Cons {
-                                head: pattern_arena.alloc((x.0)(arenas)?)?,
-                                tail: chain_arena.alloc(chain)?,
-                            })
+            let collections =
+                (prop::collection::vec(inner.clone(), 0..10), functions)
+                    .prop_map(|(xs, f)| {
+                        ArenasTo::new(
+                            move |arenas: DynArenasOf<'_, Pattern>| {
+                                let (pattern_arena, (chain_arena, _)) = arenas;
+                                let chain_cons =
+                                    |chain, x: ArenasTo<Pattern>| {
+                                        ArenaResult::Ok(Chain::Cons {
+                                            head: pattern_arena
+                                                .alloc((x.0)(arenas)?)?,
+                                            tail: chain_arena.alloc(chain)?,
+                                        })
+                                    };
+                                xs.iter()
+                                    .cloned()
+                                    .try_fold(Chain::Nil, chain_cons)
+                                    .map(f)
+                            },
+                        )
+                    });
+            // A pattern shared between up to four nodes of a `Cat`.
+            let shared = (inner, 1..5usize).prop_map(|(x, copies)| {
+                ArenasTo::new(move |arenas: DynArenasOf<'_, Pattern>| {
+                    let (
+                        pattern_arena,
+                        (chain_arena, (_, (_, (shared_arena, ())))),
+                    ) = arenas;
+                    let index = share(shared_arena, (x.0)(arenas)?)
+                        .map_err(|(error, _)| error)?;
+                    let mut chain = Chain::Nil;
+                    for _ in 1..copies {
+                        chain = Chain::Cons {
+                            head: pattern_arena.alloc(Pattern::Shared(
+                                retain(shared_arena, &index)?,
+                            ))?,
+                            tail: chain_arena.alloc(chain)?,
                         };
-                        xs.iter()
-                            .cloned()
-                            .try_fold(Chain::Nil, chain_cons)
-                            .map(f)
-                    })
-                },
-            )
+                    }
+                    Ok(Pattern::Cat(Chain::Cons {
+                        head: pattern_arena.alloc(Pattern::Shared(index))?,
+                        tail: chain_arena.alloc(chain)?,
+                    }))
+                })
+            });
+            prop_oneof![3 => collections, 1 => shared]
         },
     )
 }
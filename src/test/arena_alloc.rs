@@ -1,56 +1,127 @@
+use std::panic;
+use std::thread;
+
 use proptest::test_runner::TestRunner;
 
+use crate::arena::arena_box::ArenaBox;
 use crate::arena::arena_impl::growable_arena::GrowableArena;
+use crate::arena::arena_impl::scapegoat_arena::ScapegoatArena;
+use crate::arena::arena_impl::spin_arena::SpinStaticArena;
+use crate::arena::arena_impl::static_arena::StaticArena;
+use crate::arena::brand::with_brand;
 use crate::arena::chain::Chain;
+use crate::arena::compact::compact_in;
+use crate::arena::debug::ArenaDebug;
 use crate::arena::equality::ArenaEq;
+use crate::arena::error::ArenaError;
+use crate::arena::extension::Inspect;
 use crate::arena::handler::ArenaHandler;
+use crate::arena::index::Index;
+use crate::arena::reachability::SlotRef;
+use crate::arena::reachability::check_reachability;
+use crate::arena::shared::retain;
+use crate::arena::shared::share;
+use crate::arena::transaction::TupleTransaction;
+use crate::arena::transaction::transaction;
 use crate::arena::tuple::ArenaTuple;
 use crate::arena::tuple::DynArenasOf;
+use crate::arena::validation::Validation;
+use crate::arena::work_stack::TRAVERSAL_DEPTH;
+use crate::arena::work_stack::WorkStack;
+use crate::ast::interner::Interned;
+use crate::ast::interner::Interner;
+use crate::ast::note::NoteUnit;
+use crate::ast::note::Number;
+use crate::ast::pattern::Leaf;
 use crate::ast::pattern::Pattern;
+use crate::ast::pattern::PatternChain;
+use crate::ast::pattern::PatternIndex;
+use crate::ast::pattern::PatternWidths;
+use crate::ast::pattern::SharedPattern;
 use crate::ast::pattern::TimedStep;
+use crate::ast::pattern::TimedStepChain;
+use crate::test::arbitrary::ArenasTo;
 use crate::test::arbitrary::arb_pattern;
 
-fn with_growable_arena_tuple<U>(
-    f: impl FnOnce(DynArenasOf<'_, Pattern>) -> U,
-) -> U {
-    let pattern_arena = GrowableArena::<Pattern>::new();
-    let chain_arena = GrowableArena::<Chain<Pattern>>::new();
-    let timed_step_arena = GrowableArena::<TimedStep>::new();
-    let timed_step_chain_arena = GrowableArena::<Chain<TimedStep>>::new();
-    let arena_tuple = (
-        pattern_arena,
-        (chain_arena, (timed_step_arena, (timed_step_chain_arena, ()))),
-    );
-    let dyn_arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
-    f(dyn_arenas)
-}
-
-fn with_regenerated_arenas(f: impl Fn(DynArenasOf<'_, Pattern>, Pattern)) {
-    let mut test_runner = TestRunner::deterministic();
-    let strat = arb_pattern();
-    test_runner
-        .run(&strat, |pat| {
-            with_growable_arena_tuple(|arena_tuple| {
-                f(arena_tuple, (pat.0)(arena_tuple).unwrap());
-            });
-            Ok(())
-        })
-        .unwrap()
+/// The capacity of each fixed-capacity arena used in the tests, which is enough
+/// to hold every pattern generated in a single run of [with_reused_arenas].
+const FIXED_CAPACITY: usize = 16384;
+
+/// The stack size of the threads running each test, which is enough to hold
+/// four fixed-capacity arenas of capacity [FIXED_CAPACITY].
+const TEST_STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// A function which creates a fresh tuple of arenas, and passes it to the
+/// given function.
+type WithArenasFn = fn(&mut dyn FnMut(DynArenasOf<'_, Pattern>));
+
+/// Defines a [WithArenasFn] of the given name, which creates its arenas with
+/// [pattern_arena_tuple] from the remaining arguments.
+macro_rules! with_arena_tuple {
+    ($name:ident, $($arenas:tt)+) => {
+        fn $name(f: &mut dyn FnMut(DynArenasOf<'_, Pattern>)) {
+            let arena_tuple = pattern_arena_tuple!($($arenas)+);
+            f(ArenaTuple::to_dyn_arenas(&arena_tuple))
+        }
+    };
 }
 
-fn with_reused_arenas(f: impl Fn(DynArenasOf<'_, Pattern>, Pattern)) {
-    let mut test_runner = TestRunner::deterministic();
-    let strat = arb_pattern();
-    with_growable_arena_tuple(|arena_tuple| {
+with_arena_tuple!(with_growable_arena_tuple, GrowableArena);
+with_arena_tuple!(with_scapegoat_arena_tuple, ScapegoatArena, FIXED_CAPACITY);
+with_arena_tuple!(with_static_arena_tuple, StaticArena, FIXED_CAPACITY);
+with_arena_tuple!(with_spin_arena_tuple, SpinStaticArena, FIXED_CAPACITY);
+
+/// Runs the given function on a thread with a stack of size
+/// [TEST_STACK_SIZE], propagating any panic.
+fn on_large_stack(f: impl FnOnce() + Send + 'static) {
+    let handle = thread::Builder::new()
+        .stack_size(TEST_STACK_SIZE)
+        .spawn(f)
+        .unwrap();
+    if let Err(panic) = handle.join() {
+        panic::resume_unwind(panic)
+    }
+}
+
+/// Builds the given pattern in a transaction, so that a failure leaves the
+/// arenas as they were.
+fn build(
+    pattern: &ArenasTo<Pattern>,
+    arenas: DynArenasOf<'_, Pattern>,
+) -> Pattern {
+    transaction(&arenas, |arenas| (pattern.0)(*arenas)).unwrap()
+}
+
+fn with_regenerated_arenas(with_arenas: WithArenasFn, f: TesterFn) {
+    on_large_stack(move || {
+        let mut test_runner = TestRunner::deterministic();
+        let strat = arb_pattern();
         test_runner
             .run(&strat, |pat| {
-                f(arena_tuple, (pat.0)(arena_tuple).unwrap());
+                with_arenas(&mut |arena_tuple| {
+                    f(arena_tuple, build(&pat, arena_tuple));
+                });
                 Ok(())
             })
             .unwrap()
     })
 }
 
+fn with_reused_arenas(with_arenas: WithArenasFn, f: TesterFn) {
+    on_large_stack(move || {
+        let mut test_runner = TestRunner::deterministic();
+        let strat = arb_pattern();
+        with_arenas(&mut |arena_tuple| {
+            test_runner
+                .run(&strat, |pat| {
+                    f(arena_tuple, build(&pat, arena_tuple));
+                    Ok(())
+                })
+                .unwrap()
+        })
+    })
+}
+
 type TesterFn = fn(DynArenasOf<'_, Pattern>, Pattern);
 const DO_NOTHING: TesterFn = |_, _| ();
 const DROP_PATTERN: TesterFn =
@@ -58,7 +129,22 @@ const DROP_PATTERN: TesterFn =
 const CLONE_AND_CHECK_EQUAL: TesterFn = |arena_tuple, pattern| {
     let cloned = pattern.clone_in(&arena_tuple);
     let equals = ArenaEq::eq_in(&pattern, &cloned, &arena_tuple, &arena_tuple);
-    assert!(equals, "Pattern {pattern:?} and cloned {cloned:?} are distinct")
+    assert!(
+        equals,
+        "Pattern\n{}\nand cloned\n{}\nare distinct",
+        pattern.debug_in(&arena_tuple),
+        cloned.debug_in(&arena_tuple),
+    );
+    for pattern in [&pattern, &cloned] {
+        let validation = pattern
+            .validate_in(&arena_tuple)
+            .unwrap();
+        assert!(
+            validation.is_valid(),
+            "Pattern\n{}\nis invalid: {validation:?}",
+            pattern.debug_in(&arena_tuple),
+        );
+    }
 };
 const CLONE_AND_DROP_AND_CHECK_EQUAL: TesterFn = |arena_tuple, pattern| {
     let cloned = pattern.clone_in(&arena_tuple);
@@ -68,95 +154,1008 @@ const CLONE_AND_DROP_AND_CHECK_EQUAL: TesterFn = |arena_tuple, pattern| {
     cloned_2.drop_in(&arena_tuple);
     let equals =
         ArenaEq::eq_in(&cloned_3, &pattern, &arena_tuple, &arena_tuple);
-    assert!(equals, "Pattern {pattern:?} and cloned {cloned_3:?} are distinct")
+    assert!(
+        equals,
+        "Pattern\n{}\nand cloned\n{}\nare distinct",
+        pattern.debug_in(&arena_tuple),
+        cloned_3.debug_in(&arena_tuple),
+    )
 };
-const CLONE_AND_DROP_AND_CHECK_SIZES_EQUAL: TesterFn =
+const CLONE_AND_DROP_AND_CHECK_NOTHING_LEAKED: TesterFn =
     |arena_tuple, pattern| {
-        let arena_sizes = || {
-            [
-                arena_tuple.0.size(),
-                arena_tuple.1.0.size(),
-                arena_tuple.1.1.0.size(),
-                arena_tuple.1.1.1.0.size(),
-            ]
+        // Patterns left behind by earlier runs on reused arenas are already
+        // leaked, so the leaked slots are only checked to be unchanged.
+        let check = |roots: &[&Pattern]| {
+            let roots: Vec<_> = roots
+                .iter()
+                .map(|&root| root.clone())
+                .collect();
+            let reachability =
+                check_reachability(&roots, &arena_tuple).unwrap();
+            assert_eq!(reachability.dangling, [], "Some indices are dangling");
+            reachability.leaked
         };
-        let sizes = arena_sizes();
+        let leaked = check(&[&pattern]);
         let pattern_2 = pattern.clone_in(&arena_tuple);
-        let sizes_2 = arena_sizes();
-        let size_diff_1 =
-            std::array::from_fn::<_, 4, _>(|i| sizes_2[i] - sizes[i]);
         let pattern_3 = pattern.clone_in(&arena_tuple);
-        let sizes_3 = arena_sizes();
-        let size_diff_2 =
-            std::array::from_fn::<_, 4, _>(|i| sizes_3[i] - sizes_2[i]);
         assert_eq!(
-            size_diff_1, size_diff_2,
-            "Cloning twice should increase the number of elements by \
-             the same amount"
+            check(&[&pattern, &pattern_2, &pattern_3]),
+            leaked,
+            "Cloning should only allocate slots reachable from the clone"
         );
         pattern_3.drop_in(&arena_tuple);
-        let sizes_4 = arena_sizes();
         assert_eq!(
-            sizes_4, sizes_2,
-            "Cloning then dropping should preserve the number of elements"
+            check(&[&pattern, &pattern_2]),
+            leaked,
+            "Cloning then dropping should leave no slots behind"
         );
         pattern_2.drop_in(&arena_tuple);
-        let sizes_5 = arena_sizes();
         assert_eq!(
-            sizes_5, sizes,
-            "Cloning then dropping twice should preserve the number of elements"
+            check(&[&pattern]),
+            leaked,
+            "Cloning then dropping twice should leave no slots behind"
         );
         // To check that the behaviour of the arena is preserved even while
         // the arena is filled with elements, we do not drop the provided
         // `pattern`.
     };
+const DROP_AND_COMPACT_AND_CHECK_EQUAL: TesterFn = |arena_tuple, pattern| {
+    let reference = pattern.clone_in(&arena_tuple);
+    let cloned = reference.clone_in(&arena_tuple);
+    // Dropping the oldest copy leaves free slots below the two clones.
+    pattern.drop_in(&arena_tuple);
+    let mut roots = [reference, cloned];
+    compact_in(&mut roots, &arena_tuple).unwrap();
+    let [reference, cloned] = roots;
+    let equals =
+        ArenaEq::eq_in(&cloned, &reference, &arena_tuple, &arena_tuple);
+    assert!(
+        equals,
+        "Pattern\n{}\nand\n{}\ndiffer once compacted",
+        reference.debug_in(&arena_tuple),
+        cloned.debug_in(&arena_tuple),
+    );
+    let validation = cloned
+        .validate_in(&arena_tuple)
+        .unwrap();
+    assert!(validation.is_valid(), "Compaction left {validation:?}");
+    let size = arena_tuple.0.size();
+    let index = arena_tuple
+        .0
+        .alloc(Pattern::Silence)
+        .unwrap();
+    assert_eq!(usize::from(index.clone()), size, "Compaction left a gap");
+    arena_tuple.0.take(index).unwrap();
+    cloned.drop_in(&arena_tuple);
+    reference.drop_in(&arena_tuple);
+};
+const COPY_TO_OTHER_ARENAS_AND_MOVE_BACK: TesterFn = |arena_tuple, pattern| {
+    with_growable_arena_tuple(&mut |other_tuple| {
+        let copy = pattern
+            .try_copy_to(&arena_tuple, &other_tuple)
+            .unwrap();
+        let equals =
+            ArenaEq::eq_in(&pattern, &copy, &arena_tuple, &other_tuple);
+        assert!(
+            equals,
+            "Pattern\n{}\nand copied\n{}\nare distinct",
+            pattern.debug_in(&arena_tuple),
+            copy.debug_in(&other_tuple),
+        );
+        let reachability =
+            check_reachability(std::slice::from_ref(&copy), &other_tuple)
+                .unwrap();
+        assert!(reachability.is_sound(), "{reachability:?}");
+        let validation = copy.validate_in(&other_tuple).unwrap();
+        assert!(validation.is_valid(), "Copying left {validation:?}");
+
+        let moved = copy
+            .try_move_to(&other_tuple, &arena_tuple)
+            .unwrap();
+        let reachability =
+            check_reachability::<Pattern>(&[], &other_tuple).unwrap();
+        assert!(reachability.is_sound(), "Moving left {reachability:?}");
+        let equals =
+            ArenaEq::eq_in(&pattern, &moved, &arena_tuple, &arena_tuple);
+        assert!(
+            equals,
+            "Pattern\n{}\nand moved\n{}\nare distinct",
+            pattern.debug_in(&arena_tuple),
+            moved.debug_in(&arena_tuple),
+        );
+        moved.drop_in(&arena_tuple);
+    });
+};
+
+/// Generates the property tests for arenas created by the given
+/// [WithArenasFn], in a module of the given name.
+macro_rules! arena_alloc_tests {
+    ($name:ident, $with_arenas:expr) => {
+        mod $name {
+            use super::*;
+
+            #[test]
+            fn can_allocate_once() {
+                with_regenerated_arenas($with_arenas, DO_NOTHING)
+            }
+
+            #[test]
+            fn can_allocate_multiple() {
+                with_reused_arenas($with_arenas, DO_NOTHING)
+            }
+
+            #[test]
+            fn can_allocate_then_deallocate_once() {
+                with_regenerated_arenas($with_arenas, DROP_PATTERN)
+            }
+
+            #[test]
+            fn can_allocate_then_deallocate_multiple() {
+                with_reused_arenas($with_arenas, DROP_PATTERN)
+            }
+
+            #[test]
+            fn can_clone_and_result_is_equal_once() {
+                with_regenerated_arenas($with_arenas, CLONE_AND_CHECK_EQUAL)
+            }
+
+            #[test]
+            fn can_clone_and_result_is_equal_multiple() {
+                with_reused_arenas($with_arenas, CLONE_AND_CHECK_EQUAL)
+            }
+
+            #[test]
+            fn can_clone_and_drop_many_times_and_result_stays_equal_once() {
+                with_regenerated_arenas(
+                    $with_arenas,
+                    CLONE_AND_DROP_AND_CHECK_EQUAL,
+                );
+            }
+
+            #[test]
+            fn can_clone_and_drop_many_times_and_result_stays_equal_multiple() {
+                with_reused_arenas(
+                    $with_arenas,
+                    CLONE_AND_DROP_AND_CHECK_EQUAL,
+                );
+            }
+
+            #[test]
+            fn can_clone_and_drop_and_nothing_is_leaked_once() {
+                with_regenerated_arenas(
+                    $with_arenas,
+                    CLONE_AND_DROP_AND_CHECK_NOTHING_LEAKED,
+                );
+            }
+
+            #[test]
+            fn can_clone_and_drop_and_nothing_is_leaked_multiple() {
+                with_reused_arenas(
+                    $with_arenas,
+                    CLONE_AND_DROP_AND_CHECK_NOTHING_LEAKED,
+                );
+            }
+
+            #[test]
+            fn can_compact_and_result_is_equal_once() {
+                with_regenerated_arenas(
+                    $with_arenas,
+                    DROP_AND_COMPACT_AND_CHECK_EQUAL,
+                );
+            }
+
+            #[test]
+            fn can_compact_and_result_is_equal_multiple() {
+                with_reused_arenas(
+                    $with_arenas,
+                    DROP_AND_COMPACT_AND_CHECK_EQUAL,
+                );
+            }
+
+            #[test]
+            fn can_copy_to_other_arenas_and_move_back_once() {
+                with_regenerated_arenas(
+                    $with_arenas,
+                    COPY_TO_OTHER_ARENAS_AND_MOVE_BACK,
+                );
+            }
+
+            #[test]
+            fn can_copy_to_other_arenas_and_move_back_multiple() {
+                with_reused_arenas(
+                    $with_arenas,
+                    COPY_TO_OTHER_ARENAS_AND_MOVE_BACK,
+                );
+            }
+        }
+    };
+}
+
+arena_alloc_tests!(growable, with_growable_arena_tuple);
+arena_alloc_tests!(scapegoat, with_scapegoat_arena_tuple);
+arena_alloc_tests!(static_array, with_static_arena_tuple);
+arena_alloc_tests!(spin, with_spin_arena_tuple);
 
 #[test]
-fn can_allocate_in_growable_arenas_once() {
-    with_regenerated_arenas(DO_NOTHING)
+fn failed_clone_releases_partial_allocations() {
+    let arena_tuple = pattern_arena_tuple!(StaticArena, [4, 8, 1, 1, 1]);
+    let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let (pattern_arena, (chain_arena, _)) = arenas;
+    // Cat [silence, silence, silence] fills three of the four pattern slots.
+    let mut chain = Chain::Nil;
+    for _ in 0..3 {
+        let tail = chain_arena.alloc(chain).unwrap();
+        let head = pattern_arena
+            .alloc(Pattern::Silence)
+            .unwrap();
+        chain = Chain::Cons { head, tail };
+    }
+    let pattern = Pattern::Cat(chain);
+    let sizes = || (pattern_arena.size(), chain_arena.size());
+    let before = sizes();
+    assert_eq!(pattern.try_clone_in(&arenas), Err(ArenaError::LimitReached));
+    assert_eq!(sizes(), before, "A failed clone should not leak");
+    assert_eq!(pattern.try_drop_in(&arenas), Ok(()));
+    assert_eq!(sizes(), (0, 0));
 }
 
 #[test]
-fn can_allocate_in_growable_arenas_multiple() {
-    with_reused_arenas(DO_NOTHING)
+fn failed_build_in_a_transaction_leaves_arenas_empty() {
+    let arena_tuple = pattern_arena_tuple!(StaticArena, [2, 8, 1, 1, 1]);
+    let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let build_cat = |arenas: &DynArenasOf<'_, Pattern>| {
+        let (pattern_arena, (chain_arena, _)) = *arenas;
+        let mut chain = Chain::Nil;
+        for _ in 0..3 {
+            let tail = chain_arena.alloc(chain)?;
+            let head = pattern_arena.alloc(Pattern::Silence)?;
+            chain = Chain::Cons { head, tail };
+        }
+        Ok(Pattern::Cat(chain))
+    };
+    assert_eq!(transaction(&arenas, build_cat), Err(ArenaError::LimitReached));
+    assert_eq!((arenas.0.size(), arenas.1.0.size()), (0, 0));
+    let cat = transaction(&arenas, |arenas| {
+        let (pattern_arena, (chain_arena, _)) = *arenas;
+        let tail = chain_arena.alloc(Chain::Nil)?;
+        let head = pattern_arena.alloc(Pattern::Silence)?;
+        Ok(Pattern::Cat(Chain::Cons { head, tail }))
+    });
+    assert!(cat.is_ok());
+    assert_eq!((arenas.0.size(), arenas.1.0.size()), (1, 1));
 }
 
 #[test]
-fn can_allocate_then_deallocate_once() {
-    with_regenerated_arenas(DROP_PATTERN)
+fn commit_holds_every_arena_before_committing_any() {
+    let arena_tuple = pattern_arena_tuple!(GrowableArena);
+    let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let (pattern_arena, (chain_arena, _)) = arenas;
+    let kept = chain_arena.alloc(Chain::Nil).unwrap();
+    arenas.begin().unwrap();
+    let tail = chain_arena.alloc(Chain::Nil).unwrap();
+    let head = pattern_arena
+        .alloc(Pattern::Silence)
+        .unwrap();
+    let cat = Pattern::Cat(Chain::Cons { head, tail });
+
+    // The arena of chains is borrowed while the arenas are committed, so the
+    // arena of patterns before it is not committed either.
+    let committed = chain_arena
+        .inspect(kept.clone(), |_| arenas.commit())
+        .unwrap();
+    assert_eq!(committed, Err(ArenaError::AlreadyBorrowed));
+    arenas.rollback().unwrap();
+    assert_eq!((pattern_arena.size(), chain_arena.size()), (0, 1));
+    let Pattern::Cat(Chain::Cons { head, .. }) = cat else { unreachable!() };
+    assert_eq!(pattern_arena.take(head), Err(ArenaError::ExpectedFullSlot));
+
+    // Once nothing is borrowed, committing keeps everything.
+    let cat = transaction(&arenas, |arenas| {
+        let (pattern_arena, (chain_arena, _)) = *arenas;
+        let tail = chain_arena.alloc(Chain::Nil)?;
+        let head = pattern_arena.alloc(Pattern::Silence)?;
+        Ok(Pattern::Cat(Chain::Cons { head, tail }))
+    })
+    .unwrap();
+    assert_eq!((pattern_arena.size(), chain_arena.size()), (1, 2));
+    cat.drop_in(&arenas);
+    chain_arena.take(kept).unwrap();
 }
 
 #[test]
-fn can_allocate_then_deallocate_multiple() {
-    with_reused_arenas(DROP_PATTERN)
+fn failed_rebuild_in_a_transaction_restores_dropped_patterns() {
+    with_growable_arena_tuple(&mut |arenas| {
+        let (_, (_, (_, (_, (shared_arena, ()))))) = arenas;
+        let bar = drum_bar(&arenas);
+        let shared = Pattern::Shared(share(shared_arena, bar).unwrap());
+        let owner = shared.clone_in(&arenas);
+        let expected = owner.debug_in(&arenas).to_string();
+        // Dropping both owners takes out every slot of the bar, and lowers the
+        // count of the shared item on the way.
+        let rebuild = |arenas: &DynArenasOf<'_, Pattern>| {
+            owner.clone().try_drop_in(arenas)?;
+            shared.clone().try_drop_in(arenas)?;
+            Err::<(), _>(ArenaError::LimitReached)
+        };
+        assert_eq!(
+            transaction(&arenas, rebuild),
+            Err(ArenaError::LimitReached)
+        );
+        assert_eq!(owner.debug_in(&arenas).to_string(), expected);
+        let Pattern::Shared(index) = &shared else { unreachable!() };
+        let count = shared_arena.inspect(index.clone(), |c| c.count());
+        assert_eq!(count, Ok(2));
+        let roots = [owner.clone(), shared.clone()];
+        let reachability = check_reachability(&roots, &arenas).unwrap();
+        assert!(reachability.is_sound(), "{reachability:?}");
+        owner.drop_in(&arenas);
+        shared.drop_in(&arenas);
+        let reachability = check_reachability::<Pattern>(&[], &arenas).unwrap();
+        assert!(reachability.is_sound(), "{reachability:?}");
+    });
+}
+
+/// Index widths for small patterns, where only the pattern chains need more
+/// than 255 slots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct TinyWidths;
+
+impl PatternWidths for TinyWidths {
+    type Pattern = u8;
+    type PatternChain = u16;
+    type TimedStep = u8;
+    type TimedStepChain = u8;
+    type Shared = u8;
 }
 
 #[test]
-fn can_clone_and_result_is_equal_once() {
-    with_regenerated_arenas(CLONE_AND_CHECK_EQUAL)
+fn patterns_can_be_kept_in_arenas_with_narrow_indices() {
+    type Tiny = Pattern<TinyWidths>;
+    let pattern_arena = GrowableArena::<Tiny, u8>::new();
+    let chain_arena = GrowableArena::<PatternChain<TinyWidths>>::new();
+    let timed_step_arena = StaticArena::<TimedStep<TinyWidths>, 1, u8>::new();
+    let timed_step_chain_arena =
+        StaticArena::<TimedStepChain<TinyWidths>, 1, u8>::new();
+    let shared_arena = StaticArena::<SharedPattern<TinyWidths>, 1, u8>::new();
+    let arena_tuple = (
+        pattern_arena,
+        (
+            chain_arena,
+            (timed_step_arena, (timed_step_chain_arena, (shared_arena, ()))),
+        ),
+    );
+    let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let (pattern_arena, (chain_arena, _)) = arenas;
+    // A clone of this pattern fits into the pattern arena's 255 slots, but a
+    // second clone does not.
+    let mut chain = Chain::Nil;
+    for _ in 0..100 {
+        let tail = chain_arena.alloc(chain).unwrap();
+        let head = pattern_arena
+            .alloc(Tiny::Silence)
+            .unwrap();
+        chain = Chain::Cons { head, tail };
+    }
+    let pattern = Tiny::Stack(chain);
+    let cloned = pattern.try_clone_in(&arenas).unwrap();
+    assert!(ArenaEq::eq_in(&pattern, &cloned, &arenas, &arenas));
+    let sizes = || (pattern_arena.size(), chain_arena.size());
+    assert_eq!(sizes(), (200, 200));
+    assert_eq!(pattern.try_clone_in(&arenas), Err(ArenaError::LimitReached));
+    assert_eq!(sizes(), (200, 200), "A failed clone should not leak");
+    assert_eq!(pattern.try_drop_in(&arenas), Ok(()));
+    assert_eq!(cloned.try_drop_in(&arenas), Ok(()));
+    assert_eq!((pattern_arena.size(), chain_arena.size()), (0, 0));
 }
 
 #[test]
-fn can_clone_and_result_is_equal_multiple() {
-    with_reused_arenas(CLONE_AND_CHECK_EQUAL)
+fn reachability_reports_leaked_and_dangling_slots() {
+    let arena_tuple = pattern_arena_tuple!(GrowableArena);
+    let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let (pattern_arena, (chain_arena, _)) = arenas;
+    let cat = |head| {
+        let tail = chain_arena.alloc(Chain::Nil).unwrap();
+        Pattern::Cat(Chain::Cons { head, tail })
+    };
+    let kept = cat(pattern_arena
+        .alloc(Pattern::Silence)
+        .unwrap());
+    let lost = pattern_arena
+        .alloc(Pattern::Silence)
+        .unwrap();
+    let dropped = pattern_arena
+        .alloc(Pattern::Silence)
+        .unwrap();
+    let dangling = cat(dropped.clone());
+    pattern_arena.take(dropped).unwrap();
+    let reachability =
+        check_reachability(&[kept.clone(), dangling], &arenas).unwrap();
+    let slot = |item, position| SlotRef { item, position };
+    let pattern_name = std::any::type_name::<Pattern>();
+    assert_eq!(reachability.leaked, [slot(pattern_name, usize::from(lost))]);
+    assert_eq!(reachability.dangling, [slot(pattern_name, 2)]);
+    assert!(!reachability.is_sound());
+    let reachability = check_reachability(&[kept], &arenas).unwrap();
+    assert_eq!(reachability.leaked.len(), 2, "{reachability:?}");
+    assert!(reachability.dangling.is_empty());
 }
 
 #[test]
-fn can_clone_and_drop_many_times_and_result_stays_equal_once() {
-    with_regenerated_arenas(CLONE_AND_DROP_AND_CHECK_EQUAL);
+fn arena_boxes_release_their_items_when_dropped() {
+    let arena_tuple = pattern_arena_tuple!(GrowableArena);
+    let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let sizes = || (arenas.0.size(), arenas.1.0.size());
+    let stack = {
+        let (pattern_arena, (chain_arena, _)) = arenas;
+        let tail = chain_arena.alloc(Chain::Nil).unwrap();
+        let head = pattern_arena
+            .alloc(Pattern::Silence)
+            .unwrap();
+        ArenaBox::new(Pattern::Stack(Chain::Cons { head, tail }), &arenas)
+    };
+    let cloned = stack.clone();
+    assert_eq!(sizes(), (2, 2));
+    assert!(ArenaEq::eq_in(&*stack, &*cloned, &arenas, &arenas));
+    drop(cloned);
+    assert_eq!(sizes(), (1, 1));
+    let tried = stack.try_clone().unwrap();
+    assert_eq!(tried.try_drop(), Ok(()));
+    assert_eq!(sizes(), (1, 1));
+    // Giving up ownership leaves the pattern in its arenas.
+    let pattern = stack.into_inner();
+    assert_eq!(sizes(), (1, 1));
+    drop(ArenaBox::new(pattern, &arenas));
+    assert_eq!(sizes(), (0, 0));
 }
 
 #[test]
-fn can_clone_and_drop_many_times_and_result_stays_equal_multiple() {
-    with_reused_arenas(CLONE_AND_DROP_AND_CHECK_EQUAL);
+fn shared_patterns_are_stored_once_and_counted() {
+    let arena_tuple = pattern_arena_tuple!(GrowableArena);
+    let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let (pattern_arena, (chain_arena, (_, (_, (shared_arena, ()))))) = arenas;
+    let tail = chain_arena.alloc(Chain::Nil).unwrap();
+    let head = pattern_arena
+        .alloc(Pattern::Silence)
+        .unwrap();
+    let riff = Pattern::Seq(Chain::Cons { head, tail });
+    let shared = Pattern::Shared(share(shared_arena, riff).unwrap());
+    let repeats: Vec<_> = (0..16)
+        .map(|_| shared.clone_in(&arenas))
+        .collect();
+    let sizes =
+        || (pattern_arena.size(), chain_arena.size(), shared_arena.size());
+    assert_eq!(
+        sizes(),
+        (1, 1, 1),
+        "Cloning a shared pattern should not copy it"
+    );
+    let Pattern::Shared(index) = &shared else { unreachable!() };
+    let count = || {
+        shared_arena
+            .inspect(index.clone(), |c| c.count())
+            .unwrap()
+    };
+    assert_eq!(count(), 17);
+    let reachability = check_reachability(&repeats, &arenas).unwrap();
+    assert!(reachability.is_sound(), "{reachability:?}");
+    for repeat in repeats {
+        assert!(ArenaEq::eq_in(&repeat, &shared, &arenas, &arenas));
+        repeat.drop_in(&arenas);
+    }
+    assert_eq!(count(), 1);
+    shared.drop_in(&arenas);
+    assert_eq!(sizes(), (0, 0, 0));
+}
+
+/// Builds one bar of a drum pattern, of a kick, a rest, a snare and a rest.
+fn drum_bar(arenas: &DynArenasOf<'_, Pattern>) -> Pattern {
+    let (pattern_arena, (chain_arena, _)) = *arenas;
+    let kick = Pattern::Note(NoteUnit::Number(Number(36)));
+    let snare = Pattern::Note(NoteUnit::Number(Number(38)));
+    let mut chain = Chain::Nil;
+    for step in [kick, Pattern::Silence, snare, Pattern::Silence]
+        .into_iter()
+        .rev()
+    {
+        let head = pattern_arena.alloc(step).unwrap();
+        let tail = chain_arena.alloc(chain).unwrap();
+        chain = Chain::Cons { head, tail };
+    }
+    Pattern::Seq(chain)
 }
 
 #[test]
-fn can_clone_and_drop_and_arena_sizes_are_unchanged_once() {
-    with_regenerated_arenas(CLONE_AND_DROP_AND_CHECK_SIZES_EQUAL);
+fn interned_patterns_are_stored_once_and_released_when_dropped() {
+    // Sixteen bars built separately would need 80 patterns and 80 chain cells.
+    let arena_tuple = pattern_arena_tuple!(ScapegoatArena, 32);
+    let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let (pattern_arena, (chain_arena, (_, (_, (shared_arena, ()))))) = arenas;
+    let sizes =
+        || (pattern_arena.size(), chain_arena.size(), shared_arena.size());
+
+    let mut interner = Interner::new();
+    let mut chain = Chain::Nil;
+    for bar in 0..16 {
+        let (interned, outcome) = interner
+            .intern_tree(drum_bar(&arenas), &arenas)
+            .unwrap();
+        let expected =
+            if bar == 0 { Interned::Stored } else { Interned::Deduplicated };
+        assert_eq!(outcome, expected, "Interning bar {bar}");
+        let head = pattern_arena.alloc(interned).unwrap();
+        let tail = chain_arena.alloc(chain).unwrap();
+        chain = Chain::Cons { head, tail };
+    }
+    let (pattern, outcome) = interner
+        .intern_tree(Pattern::Cat(chain), &arenas)
+        .unwrap();
+    assert_eq!(outcome, Interned::Stored);
+    // Fifteen bars, the second rest of the first bar, and the four leaves of
+    // each later bar, leaving the three leaves of the stored bar and a
+    // [Pattern::Shared] node for each bar.
+    assert_eq!(interner.deduplicated(), 76);
+    assert_eq!(sizes(), (19, 20, 2));
+    let reachability =
+        check_reachability(std::slice::from_ref(&pattern), &arenas).unwrap();
+    assert!(reachability.is_sound(), "{reachability:?}");
+
+    let (leaf, outcome) = interner
+        .intern(Pattern::Silence, &arenas)
+        .unwrap();
+    assert_eq!((leaf, outcome), (Pattern::Silence, Interned::Unshared));
+
+    pattern.drop_in(&arenas);
+    assert_eq!(sizes(), (0, 0, 0));
+
+    // The stored bar was released, so it is stored again rather than reused.
+    let (bar, outcome) = interner
+        .intern_tree(drum_bar(&arenas), &arenas)
+        .unwrap();
+    assert_eq!(outcome, Interned::Stored);
+    bar.drop_in(&arenas);
+    assert_eq!(sizes(), (0, 0, 0));
 }
 
 #[test]
-fn can_clone_and_drop_and_arena_sizes_are_unchanged_multiple() {
-    with_reused_arenas(CLONE_AND_DROP_AND_CHECK_SIZES_EQUAL);
+fn repeated_leaves_share_a_slot_so_a_drum_pattern_fits_in_a_smaller_arena() {
+    // Four to the floor, over four bars, each kick followed by a rest.
+    let steps = || {
+        (0..32).map(|step| match step % 2 {
+            0 => Leaf::Note(NoteUnit::Number(Number(36))),
+            _ => Leaf::Silence,
+        })
+    };
+    let arena_tuple = pattern_arena_tuple!(ScapegoatArena, [2, 32, 1, 1, 1]);
+    let small = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let (pattern_arena, (chain_arena, (_, (_, (shared_arena, ()))))) = small;
+    let sizes =
+        || (pattern_arena.size(), chain_arena.size(), shared_arena.size());
+
+    // Built from interned leaves, the pattern takes a slot for the kick and
+    // one for the rest.
+    let mut interner = Interner::new();
+    let mut chain = Chain::Nil;
+    for leaf in steps() {
+        let (head, _) = interner
+            .intern_leaf(leaf, &small)
+            .unwrap();
+        let tail = chain_arena.alloc(chain).unwrap();
+        chain = Chain::Cons { head, tail };
+    }
+    assert_eq!(interner.deduplicated(), 30);
+    let pattern = Pattern::Seq(chain);
+    assert_eq!(sizes(), (2, 32, 0));
+    assert!(
+        pattern
+            .validate_in(&small)
+            .unwrap()
+            .is_valid()
+    );
+    let reachability =
+        check_reachability(std::slice::from_ref(&pattern), &small).unwrap();
+    assert!(reachability.is_sound(), "{reachability:?}");
+    pattern.drop_in(&small);
+    assert_eq!(sizes(), (0, 0, 0));
+
+    // Built with a slot for each step, the same pattern does not fit until
+    // its leaves are interned.
+    with_growable_arena_tuple(&mut |large| {
+        let (pattern_arena, (chain_arena, _)) = large;
+        let mut chain = Chain::Nil;
+        for leaf in steps() {
+            let step = match leaf {
+                Leaf::Note(unit) => Pattern::Note(unit),
+                Leaf::Silence => Pattern::Silence,
+            };
+            let head = pattern_arena.alloc(step).unwrap();
+            let tail = chain_arena.alloc(chain).unwrap();
+            chain = Chain::Cons { head, tail };
+        }
+        let pattern = Pattern::Seq(chain);
+        let error = pattern
+            .try_copy_to(&large, &small)
+            .unwrap_err();
+        assert_eq!(error, ArenaError::LimitReached);
+        assert_eq!(sizes(), (0, 0, 0));
+
+        let (pattern, _) = Interner::new()
+            .intern_tree(pattern, &large)
+            .unwrap();
+        assert_eq!(pattern_arena.size(), 2);
+        let moved = pattern
+            .try_move_to(&large, &small)
+            .unwrap();
+        assert_eq!(sizes(), (2, 32, 1));
+        let reachability =
+            check_reachability(std::slice::from_ref(&moved), &small).unwrap();
+        assert!(reachability.is_sound(), "{reachability:?}");
+        moved.drop_in(&small);
+        assert_eq!(sizes(), (0, 0, 0));
+    });
+}
+
+#[test]
+fn interning_reports_a_stored_pattern_which_cannot_be_compared() {
+    let arena_tuple = pattern_arena_tuple!(ScapegoatArena, 32);
+    let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let (pattern_arena, (chain_arena, (_, (_, (shared_arena, ()))))) = arenas;
+    let sizes =
+        || (pattern_arena.size(), chain_arena.size(), shared_arena.size());
+
+    let mut interner = Interner::new();
+    let (stored, outcome) = interner
+        .intern_tree(drum_bar(&arenas), &arenas)
+        .unwrap();
+    assert_eq!(outcome, Interned::Stored);
+    let Pattern::Shared(index) = &stored else {
+        panic!("Expected a shared pattern, got {stored:?}");
+    };
+    let kick = shared_arena
+        .inspect(index.clone(), |shared| match shared.get() {
+            Pattern::Seq(Chain::Cons { head, .. }) => head.clone(),
+            pattern => panic!("Expected a sequence, got {pattern:?}"),
+        })
+        .unwrap();
+    pattern_arena.take(kick).unwrap();
+
+    // The bar hashes as the stored one did, but cannot be compared with it,
+    // so it is released rather than stored again.
+    let before = sizes();
+    let error = interner
+        .intern_tree(drum_bar(&arenas), &arenas)
+        .unwrap_err();
+    assert_eq!(error, ArenaError::StaleIndex);
+    assert_eq!(sizes(), before);
+}
+
+#[test]
+fn patterns_can_be_moved_into_smaller_arenas_keeping_shared_nodes_shared() {
+    with_growable_arena_tuple(&mut |source| {
+        let (pattern_arena, (chain_arena, (_, (_, (shared_arena, ()))))) =
+            source;
+        let bar = drum_bar(&source);
+        let shared = share(shared_arena, bar).unwrap();
+        let mut chain = Chain::Nil;
+        for _ in 0..3 {
+            let repeat =
+                Pattern::Shared(retain(shared_arena, &shared).unwrap());
+            let head = pattern_arena.alloc(repeat).unwrap();
+            let tail = chain_arena.alloc(chain).unwrap();
+            chain = Chain::Cons { head, tail };
+        }
+        Pattern::Shared(shared).drop_in(&source);
+        let pattern = Pattern::Cat(chain);
+
+        let arena_tuple = pattern_arena_tuple!(ScapegoatArena, 8);
+        let destination = ArenaTuple::to_dyn_arenas(&arena_tuple);
+        let (pattern_arena, (chain_arena, (_, (_, (shared_arena, ()))))) =
+            destination;
+
+        let moved = pattern
+            .try_move_to(&source, &destination)
+            .unwrap();
+        let reachability = check_reachability::<Pattern>(&[], &source).unwrap();
+        assert!(reachability.is_sound(), "Moving left {reachability:?}");
+        let sizes =
+            || (pattern_arena.size(), chain_arena.size(), shared_arena.size());
+        assert_eq!(sizes(), (7, 7, 1), "The bar should be copied once");
+        let reachability =
+            check_reachability(std::slice::from_ref(&moved), &destination)
+                .unwrap();
+        assert!(reachability.is_sound(), "{reachability:?}");
+
+        // A copy which does not fit gives nothing back but the error.
+        let error = moved
+            .try_copy_to(&destination, &destination)
+            .unwrap_err();
+        assert_eq!(error, ArenaError::LimitReached);
+        assert_eq!(sizes(), (7, 7, 1));
+        moved.drop_in(&destination);
+        assert_eq!(sizes(), (0, 0, 0));
+    });
+}
+
+#[test]
+fn branded_arenas_clone_compare_and_move_their_own_patterns() {
+    on_large_stack(|| {
+        with_growable_arena_tuple(&mut |host| {
+            with_scapegoat_arena_tuple(&mut |device| {
+                branded_round_trip(host, device)
+            })
+        })
+    })
+}
+
+/// Builds a pattern in the `host` arenas, and then copies and moves it into the
+/// `device` arenas, all through the branded API.
+fn branded_round_trip(
+    host: DynArenasOf<'_, Pattern>,
+    device: DynArenasOf<'_, Pattern>,
+) {
+    let sizes =
+        |arenas: DynArenasOf<'_, Pattern>| (arenas.0.size(), arenas.1.0.size());
+    with_brand(&host, |host| {
+        with_brand(&device, |device| {
+            let bar = host
+                .build(|arenas| Ok(drum_bar(arenas)))
+                .unwrap();
+            let cloned = host.try_clone(&bar).unwrap();
+            assert_eq!(host.try_eq(&bar, &cloned), Ok(true));
+            host.try_drop(cloned).unwrap();
+
+            let copy = host.try_copy_to(&bar, &device).unwrap();
+            assert_eq!(host.try_eq_across(&bar, &device, &copy), Ok(true));
+            assert_eq!(device.try_eq_across(&copy, &host, &bar), Ok(true));
+            let moved = host.try_move_to(bar, &device).unwrap();
+            assert_eq!(sizes(*host.arenas()), (0, 0));
+            assert_eq!(device.try_eq(&copy, &moved), Ok(true));
+            device.try_drop(copy).unwrap();
+            device.try_drop(moved).unwrap();
+            assert_eq!(sizes(*device.arenas()), (0, 0));
+        })
+    });
+}
+
+/// The stack size of the threads running the traversal tests, which is far too
+/// small to recurse once per chain cell of a long pattern.
+const SMALL_STACK_SIZE: usize = 64 * 1024;
+
+/// Builds `Cat [note, note, ...]` of `length` notes.
+fn long_cat(arenas: &DynArenasOf<'_, Pattern>, length: u16) -> Pattern {
+    let (pattern_arena, (chain_arena, _)) = *arenas;
+    let mut chain = Chain::Nil;
+    for number in 0..length {
+        let note = Pattern::Note(NoteUnit::Number(Number(number)));
+        let head = pattern_arena.alloc(note).unwrap();
+        let tail = chain_arena.alloc(chain).unwrap();
+        chain = Chain::Cons { head, tail };
+    }
+    Pattern::Cat(chain)
+}
+
+#[test]
+fn long_patterns_are_traversed_on_a_small_stack() {
+    let handle = thread::Builder::new()
+        .stack_size(SMALL_STACK_SIZE)
+        .spawn(|| {
+            with_growable_arena_tuple(&mut |arenas| {
+                let (pattern_arena, (chain_arena, _)) = arenas;
+                let sizes = || (pattern_arena.size(), chain_arena.size());
+                let mut pattern = Pattern::Cat(Chain::Nil);
+                for _ in 0..100 {
+                    let bar = long_cat(&arenas, 100);
+                    let head = pattern_arena.alloc(bar).unwrap();
+                    let Pattern::Cat(tail) = pattern else { unreachable!() };
+                    let tail = chain_arena.alloc(tail).unwrap();
+                    pattern = Pattern::Cat(Chain::Cons { head, tail });
+                }
+                let before = sizes();
+                assert_eq!(before, (10_100, 10_100));
+
+                let cloned = pattern.try_clone_in(&arenas).unwrap();
+                assert_eq!(
+                    ArenaEq::try_eq_in(&pattern, &cloned, &arenas, &arenas),
+                    Ok(true)
+                );
+                let roots = [pattern.clone(), cloned.clone()];
+                let reachability = check_reachability(&roots, &arenas).unwrap();
+                assert!(reachability.is_sound());
+                let (interned, _) = Interner::new()
+                    .intern(cloned, &arenas)
+                    .unwrap();
+                interned.try_drop_in(&arenas).unwrap();
+                assert_eq!(sizes(), before);
+                pattern.try_drop_in(&arenas).unwrap();
+                assert_eq!(sizes(), (0, 0));
+            })
+        })
+        .unwrap();
+    if let Err(panic) = handle.join() {
+        panic::resume_unwind(panic)
+    }
+}
+
+#[test]
+fn compaction_which_cannot_remap_a_root_moves_nothing() {
+    with_growable_arena_tuple(&mut |arenas| {
+        let (pattern_arena, (chain_arena, _)) = arenas;
+        let leaked = pattern_arena
+            .alloc(Pattern::Silence)
+            .unwrap();
+        let kick = Pattern::Note(NoteUnit::Number(Number(36)));
+        let head = pattern_arena
+            .alloc(kick.clone())
+            .unwrap();
+        let tail = chain_arena.alloc(Chain::Nil).unwrap();
+        let sound = Pattern::Seq(Chain::Cons { head, tail });
+        let gone = pattern_arena
+            .alloc(Pattern::Silence)
+            .unwrap();
+        pattern_arena
+            .take(gone.clone())
+            .unwrap();
+        let tail = chain_arena.alloc(Chain::Nil).unwrap();
+        let dangling = Pattern::Seq(Chain::Cons { head: gone, tail });
+        // Freeing the first slot would move the kick down into it.
+        pattern_arena.take(leaked).unwrap();
+
+        let mut roots = [sound.clone(), dangling];
+        let error = compact_in(&mut roots, &arenas).unwrap_err();
+        assert_eq!(error, ArenaError::ExpectedFullSlot);
+        let [unmoved, dangling] = roots;
+        assert_eq!(unmoved, sound, "The sound root was rewritten");
+        let Pattern::Seq(Chain::Cons { head, tail }) = dangling else {
+            panic!("Expected a sequence, got {dangling:?}");
+        };
+        assert_eq!(
+            pattern_arena.inspect(head, Pattern::clone),
+            Err(ArenaError::ExpectedFullSlot)
+        );
+        chain_arena.take(tail).unwrap();
+
+        // The arenas were left as they were, so they can still be changed,
+        // and compacted once the dangling root is gone.
+        let silence = pattern_arena
+            .alloc(Pattern::Silence)
+            .unwrap();
+        pattern_arena.take(silence).unwrap();
+        let mut roots = [sound];
+        compact_in(&mut roots, &arenas).unwrap();
+        let [moved] = roots;
+        let Pattern::Seq(Chain::Cons { head, .. }) = &moved else {
+            panic!("Expected a sequence, got {moved:?}");
+        };
+        assert_eq!(usize::from(head.clone()), 0);
+        assert_eq!(
+            pattern_arena.inspect(head.clone(), Pattern::clone),
+            Ok(kick)
+        );
+        moved.drop_in(&arenas);
+    });
+}
+
+#[test]
+fn too_deeply_nested_patterns_fail_to_clone_without_leaking() {
+    with_growable_arena_tuple(&mut |arenas| {
+        let (pattern_arena, (chain_arena, _)) = arenas;
+        let sizes = || (pattern_arena.size(), chain_arena.size());
+        let mut pattern = Pattern::Silence;
+        for _ in 0..TRAVERSAL_DEPTH * 2 {
+            let head = pattern_arena.alloc(pattern).unwrap();
+            let tail = chain_arena.alloc(Chain::Nil).unwrap();
+            pattern = Pattern::Cat(Chain::Cons { head, tail });
+        }
+        let before = sizes();
+        assert_eq!(
+            pattern.try_clone_in(&arenas),
+            Err(ArenaError::DepthLimitExceeded)
+        );
+        assert_eq!(sizes(), before, "A failed clone should not leak");
+        assert_eq!(
+            ArenaEq::try_eq_in(&pattern, &pattern, &arenas, &arenas),
+            Err(ArenaError::DepthLimitExceeded)
+        );
+        assert_eq!(pattern.try_drop_in(&arenas), Ok(()));
+        assert_eq!(sizes(), (0, 0));
+    });
+}
+
+#[test]
+fn work_stacks_of_pattern_steps_take_their_documented_size() {
+    let size =
+        |step: usize| TRAVERSAL_DEPTH * step + core::mem::size_of::<usize>();
+    type Step = PatternIndex;
+    assert_eq!(size_of::<WorkStack<Step>>(), size(size_of::<Option<Step>>()));
+    assert_eq!(
+        size_of::<WorkStack<(Step, Step)>>(),
+        size(size_of::<Option<(Step, Step)>>())
+    );
+    if cfg!(target_pointer_width = "64") {
+        assert_eq!(size_of::<WorkStack<Step>>(), 392);
+        assert_eq!(size_of::<WorkStack<(Step, Step)>>(), 776);
+    }
+}
+
+#[test]
+fn validation_reports_each_kind_of_malformed_pattern() {
+    with_growable_arena_tuple(&mut |arenas| {
+        let (pattern_arena, (chain_arena, (_, (_, (shared_arena, ()))))) =
+            arenas;
+        let slot = |item, position| SlotRef { item, position };
+        let patterns = core::any::type_name::<Pattern>();
+        let chains = core::any::type_name::<PatternChain>();
+        let cat = |heads: &[Index<Pattern>]| {
+            let mut chain = Chain::Nil;
+            for head in heads.iter().rev() {
+                let tail = chain_arena.alloc(chain).unwrap();
+                chain = Chain::Cons { head: head.clone(), tail };
+            }
+            Pattern::Cat(chain)
+        };
+
+        let shared = share(shared_arena, Pattern::Silence).unwrap();
+        let heads = [
+            pattern_arena
+                .alloc(Pattern::Shared(retain(shared_arena, &shared).unwrap()))
+                .unwrap(),
+            pattern_arena
+                .alloc(Pattern::Shared(shared))
+                .unwrap(),
+        ];
+        let validation = cat(&heads)
+            .validate_in(&arenas)
+            .unwrap();
+        assert!(validation.is_valid(), "{validation:?}");
+
+        let vacant = pattern_arena
+            .alloc(Pattern::Silence)
+            .unwrap();
+        pattern_arena
+            .take(vacant.clone())
+            .unwrap();
+        let out_of_bounds = Index::new(1000, 0);
+        let validation =
+            cat(&[vacant.clone(), out_of_bounds]).validate_in(&arenas);
+        assert_eq!(
+            validation,
+            Ok(Validation {
+                out_of_bounds: vec![slot(patterns, 1000)],
+                vacant: vec![slot(patterns, vacant.inner().into())],
+                ..Validation::default()
+            })
+        );
+
+        let twice = pattern_arena
+            .alloc(Pattern::Silence)
+            .unwrap();
+        let validation = cat(&[twice.clone(), twice.clone()])
+            .validate_in(&arenas)
+            .unwrap();
+        let position = usize::from(twice.clone());
+        assert_eq!(validation.multiply_owned, [slot(patterns, position)]);
+
+        let looped = chain_arena.alloc(Chain::Nil).unwrap();
+        let cell = Chain::Cons { head: twice.clone(), tail: looped.clone() };
+        chain_arena
+            .inspect_mut(looped.clone(), |slot| *slot = cell)
+            .unwrap();
+        let head = pattern_arena
+            .alloc(Pattern::Silence)
+            .unwrap();
+        let pattern = Pattern::Cat(Chain::Cons { head, tail: looped.clone() });
+        let validation = pattern.validate_in(&arenas).unwrap();
+        let position = usize::from(looped);
+        assert_eq!(validation.cycles, [slot(chains, position)]);
+        assert_eq!(validation.unterminated, [slot(chains, position)]);
+
+        let nested = pattern_arena
+            .alloc(Pattern::Silence)
+            .unwrap();
+        let pattern = cat(std::slice::from_ref(&nested));
+        pattern_arena
+            .inspect_mut(nested.clone(), |slot| *slot = pattern.clone())
+            .unwrap();
+        let validation = pattern.validate_in(&arenas).unwrap();
+        assert_eq!(validation.cycles, [slot(patterns, usize::from(nested))]);
+    });
 }
@@ -0,0 +1,522 @@
+use std::cell::Cell;
+use std::panic;
+use std::rc::Rc;
+use std::thread;
+
+use crate::arena::Arena;
+use crate::arena::arena_impl::growable_arena::GAMap;
+use crate::arena::arena_impl::growable_arena::GrowableArena;
+use crate::arena::arena_impl::helpers::IndexableMap;
+use crate::arena::arena_impl::helpers::IndexableMapArena;
+use crate::arena::arena_impl::helpers::Slot;
+use crate::arena::arena_impl::scapegoat_arena::ScapegoatArena;
+use crate::arena::arena_impl::spin_arena::SpinGrowableArena;
+use crate::arena::arena_impl::spin_arena::SpinStaticArena;
+use crate::arena::arena_impl::static_arena::StaticArena;
+use crate::arena::error::ArenaError;
+use crate::arena::error::ArenaResult;
+use crate::arena::extension::Inspect;
+use crate::arena::extension::Occupied;
+use crate::arena::index::Index;
+use crate::arena::stats::ArenaStats;
+use crate::arena::stats::TupleStats;
+use crate::arena::tuple::ArenaTuple;
+
+#[test]
+fn freed_slots_are_reused_by_alloc() {
+    let arena = GrowableArena::<u32>::new();
+    let first = arena.alloc(1).unwrap();
+    let second = arena.alloc(2).unwrap();
+    assert_eq!(arena.take(first.clone()), Ok(1));
+    let third = arena.alloc(3).unwrap();
+    assert_eq!(usize::from(third), usize::from(first));
+    assert_eq!(arena.take(second), Ok(2));
+    assert_eq!(arena.size(), 1);
+}
+
+#[test]
+fn can_insert_into_any_freed_slot() {
+    let arena = GrowableArena::<u32>::new();
+    let indices = [0, 1, 2, 3].map(|x| arena.alloc(x).unwrap());
+    for index in indices.iter().cloned() {
+        arena.take(index).unwrap();
+    }
+    // Refill a slot from the middle of the free list, then check that every
+    // other freed slot is still handed out exactly once.
+    arena
+        .insert(indices[2].clone(), 2)
+        .unwrap();
+    let mut reused = [4, 5, 6].map(|x| usize::from(arena.alloc(x).unwrap()));
+    reused.sort();
+    assert_eq!(reused, [0, 1, 3]);
+    assert_eq!(usize::from(arena.alloc(7).unwrap()), 4);
+}
+
+#[test]
+fn alloc_and_take_forever_does_not_reach_limit() {
+    let arena = GrowableArena::<u32>::new();
+    let held = arena.alloc(0).unwrap();
+    for x in 0..2 * u32::from(u16::MAX) {
+        let index = arena.alloc(x);
+        assert_ne!(index, Err(ArenaError::LimitReached));
+        assert_eq!(arena.take(index.unwrap()), Ok(x));
+    }
+    assert_eq!(arena.take(held), Ok(0));
+}
+
+#[test]
+fn stale_indices_are_detected_after_reuse() {
+    let arena = GrowableArena::<u32>::new();
+    let stale = arena.alloc(1).unwrap();
+    arena.take(stale.clone()).unwrap();
+    let fresh = arena.alloc(2).unwrap();
+    assert_eq!(usize::from(fresh.clone()), usize::from(stale.clone()));
+    assert_eq!(arena.take(stale.clone()), Err(ArenaError::StaleIndex));
+    assert_eq!(arena.has_slot(stale.clone()), Err(ArenaError::StaleIndex));
+    assert_eq!(arena.insert(stale.clone(), 3), Err(ArenaError::StaleIndex));
+    assert_eq!(arena.inspect(stale, |x| *x), Err(ArenaError::StaleIndex));
+    assert_eq!(arena.inspect(fresh, |x| *x), Ok(2));
+}
+
+#[test]
+fn scapegoat_arena_allocates_up_to_capacity() {
+    let arena = ScapegoatArena::<u32, 4>::new();
+    let indices = [0, 1, 2, 3].map(|x| arena.alloc(x).unwrap());
+    assert_eq!(arena.size(), 4);
+    assert_eq!(arena.alloc(4), Err(ArenaError::LimitReached));
+    let [first, second, ..] = indices;
+    arena.take(first).unwrap();
+    arena.take(second).unwrap();
+    assert_eq!(arena.size(), 2);
+    assert!(arena.alloc(5).is_ok());
+    assert_eq!(arena.size(), 3);
+}
+
+#[test]
+fn static_arena_is_const_and_allocates_up_to_capacity() {
+    let arena = const { StaticArena::<u32, 2>::new() };
+    let first = arena.alloc(0).unwrap();
+    arena.alloc(1).unwrap();
+    assert_eq!(arena.alloc(2), Err(ArenaError::LimitReached));
+    arena.take(first).unwrap();
+    assert!(arena.alloc(3).is_ok());
+    assert_eq!(arena.size(), 2);
+}
+
+#[test]
+fn static_arena_can_keep_its_slots_in_a_given_buffer() {
+    type Buffer = [Slot<u32, u16>; 64];
+    let buffer: &'static mut Buffer = Box::leak(Box::new([Slot::EMPTY; 64]));
+    let arena = StaticArena::in_buffer(buffer);
+    assert!(size_of_val(&arena) < size_of::<Buffer>());
+    let indices = (0..64)
+        .map(|value| arena.alloc(value).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(arena.alloc(64), Err(ArenaError::LimitReached));
+    assert_eq!(arena.take(indices[3].clone()), Ok(3));
+    assert!(arena.alloc(65).is_ok());
+    arena.reset().unwrap();
+    assert_eq!(arena.size(), 0);
+    assert_eq!(
+        arena.take(indices[5].clone()),
+        Err(ArenaError::IndexOutOfBounds)
+    );
+}
+
+#[test]
+fn inspecting_borrows_items_in_place() {
+    let arena = GrowableArena::<u32>::new();
+    let index = arena.alloc(1).unwrap();
+    let doubled = arena.inspect_mut(index.clone(), |x| {
+        *x *= 2;
+        *x
+    });
+    assert_eq!(doubled, Ok(2));
+    let nested = arena.inspect(index.clone(), |x| {
+        let y = arena.inspect(index.clone(), |y| *y);
+        let taken = arena.take(index.clone());
+        (*x, y, taken)
+    });
+    assert_eq!(nested, Ok((2, Ok(2), Err(ArenaError::AlreadyBorrowed))));
+    let reentrant = arena.inspect_mut(index.clone(), |_| arena.has_slot(index));
+    assert_eq!(reentrant, Ok(Err(ArenaError::AlreadyBorrowed)));
+}
+
+#[test]
+fn panicking_while_inspecting_keeps_item() {
+    let arena = GrowableArena::<u32>::new();
+    let index = arena.alloc(1).unwrap();
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        arena.inspect(index.clone(), |_| panic!("inspection failed"))
+    }));
+    assert!(result.is_err());
+    assert_eq!(arena.has_slot(index.clone()), Ok(true));
+    assert_eq!(arena.take(index), Ok(1));
+}
+
+static SHARED_ARENA: SpinStaticArena<u32, 8> = SpinStaticArena::new();
+
+#[test]
+fn spin_arena_can_be_shared_from_a_static() {
+    let indices = thread::scope(|scope| {
+        let handles = [0, 1, 2, 3].map(|x| {
+            scope.spawn(move || {
+                loop {
+                    match SHARED_ARENA.alloc(x) {
+                        Err(ArenaError::AlreadyBorrowed) => continue,
+                        index => break index.unwrap(),
+                    }
+                }
+            })
+        });
+        handles.map(|handle| handle.join().unwrap())
+    });
+    assert_eq!(SHARED_ARENA.size(), 4);
+    let mut values = indices.map(|index| SHARED_ARENA.take(index).unwrap());
+    values.sort();
+    assert_eq!(values, [0, 1, 2, 3]);
+}
+
+#[test]
+fn spin_arena_reports_conflicting_access_instead_of_spinning() {
+    let arena = SpinGrowableArena::<u32>::new();
+    let index = arena.alloc(1).unwrap();
+    let interrupted = arena.inspect_mut(index.clone(), |x| {
+        *x += 1;
+        (arena.alloc(2), arena.inspect(index.clone(), |y| *y))
+    });
+    assert_eq!(
+        interrupted,
+        Ok((
+            Err(ArenaError::AlreadyBorrowed),
+            Err(ArenaError::AlreadyBorrowed)
+        ))
+    );
+    let nested = arena
+        .inspect(index.clone(), |x| (*x, arena.inspect(index.clone(), |y| *y)));
+    assert_eq!(nested, Ok((2, Ok(2))));
+}
+
+#[test]
+fn stats_count_usage_and_failures() {
+    let arena = StaticArena::<u32, 2>::new();
+    let first = arena.alloc(0).unwrap();
+    let second = arena.alloc(1).unwrap();
+    assert_eq!(arena.alloc(2), Err(ArenaError::LimitReached));
+    arena.take(first).unwrap();
+    let borrowed = arena.inspect(second.clone(), |_| arena.alloc(3));
+    assert_eq!(borrowed, Ok(Err(ArenaError::AlreadyBorrowed)));
+    let stats = arena.stats();
+    assert_eq!((stats.occupied, stats.capacity, stats.high_water), (1, 2, 2));
+    assert_eq!((stats.allocs, stats.takes), (2, 1));
+    assert_eq!(
+        stats
+            .failures
+            .count(ArenaError::LimitReached),
+        1
+    );
+    assert_eq!(
+        stats
+            .failures
+            .count(ArenaError::AlreadyBorrowed),
+        1
+    );
+    assert_eq!(stats.failures.total(), 2);
+    arena.take(second).unwrap();
+    arena.reset().unwrap();
+    assert_eq!(arena.stats().occupied, 0);
+    assert_eq!(arena.stats().high_water, 2);
+}
+
+#[test]
+fn stats_are_gathered_across_a_tuple() {
+    let arenas =
+        (GrowableArena::<u32>::new(), (StaticArena::<i8, 4>::new(), ()));
+    let dyn_arenas = ArenaTuple::to_dyn_arenas(&arenas);
+    arenas.0.alloc(0).unwrap();
+    arenas.1.0.alloc(0).unwrap();
+    arenas.1.0.alloc(1).unwrap();
+    let (words, (bytes, ())) = dyn_arenas.stats();
+    assert_eq!((words.occupied, words.capacity), (1, usize::from(u16::MAX)));
+    assert_eq!((bytes.occupied, bytes.capacity), (2, 4));
+    let mut logged = Vec::new();
+    dyn_arenas.for_each_stats(&mut |name, stats| logged.push((name, stats)));
+    assert_eq!(logged, [("u32", words), ("i8", bytes)]);
+    assert_ne!(words, ArenaStats::default());
+}
+
+#[test]
+fn compaction_forwards_moved_items_until_truncated() {
+    let arena = StaticArena::<u32, 8>::new();
+    let indices = [0, 1, 2, 3, 4].map(|x| arena.alloc(x).unwrap());
+    let [first, second, third, fourth, fifth] = indices;
+    arena.take(first.clone()).unwrap();
+    arena.take(third.clone()).unwrap();
+    arena.compact().unwrap();
+    // Nothing but reads and in-place mutation is allowed until truncation.
+    assert_eq!(arena.alloc(5), Err(ArenaError::Compacting));
+    assert_eq!(arena.take(second.clone()), Err(ArenaError::Compacting));
+    assert_eq!(arena.begin(), Err(ArenaError::Compacting));
+    assert_eq!(arena.compact(), Err(ArenaError::Compacting));
+    assert_eq!(arena.forward(second.clone()), Ok(second.clone()));
+    let moved = [fourth, fifth].map(|index| {
+        let forwarded = arena.forward(index.clone()).unwrap();
+        assert_eq!(arena.inspect(index, |x| *x), Err(ArenaError::StaleIndex));
+        forwarded
+    });
+    assert_eq!(moved.clone().map(usize::from), [0, 2]);
+    assert_eq!(
+        moved
+            .clone()
+            .map(|i| arena.inspect(i, |x| *x)),
+        [Ok(3), Ok(4)]
+    );
+    arena.truncate().unwrap();
+    assert_eq!(arena.size(), 3);
+    assert_eq!(usize::from(arena.alloc(5).unwrap()), 3);
+    assert_eq!(arena.forward(first), Err(ArenaError::StaleIndex));
+}
+
+#[test]
+fn indices_to_dropped_slots_do_not_match_the_slots_made_in_their_place() {
+    let growable = GrowableArena::<u32>::new();
+    let scapegoat = ScapegoatArena::<u32, 8>::new();
+    let fixed = StaticArena::<u32, 8>::new();
+    let arenas: [&dyn Arena<u32>; 3] = [&growable, &scapegoat, &fixed];
+    for arena in arenas {
+        let [first, second] = [0, 1].map(|x| arena.alloc(x).unwrap());
+        arena.take(first).unwrap();
+        // The second item moves into the first slot, and truncating drops
+        // the second slot, which is then made again.
+        arena.compact().unwrap();
+        arena.truncate().unwrap();
+        let third = arena.alloc(2).unwrap();
+        assert_eq!(usize::from(third.clone()), 1);
+        assert_eq!(arena.inspect(second, |x| *x), Err(ArenaError::StaleIndex));
+        assert_eq!(arena.inspect(third, |x| *x), Ok(2));
+    }
+
+    let arena = GrowableArena::<u32>::new();
+    let first = arena.alloc(0).unwrap();
+    arena.reset().unwrap();
+    let second = arena.alloc(1).unwrap();
+    assert_eq!(usize::from(second.clone()), 0);
+    assert_eq!(arena.inspect(first, |x| *x), Err(ArenaError::StaleIndex));
+    assert_eq!(arena.inspect(second, |x| *x), Ok(1));
+}
+
+/// A [GAMap] whose slot at a chosen index can be read but not written, to
+/// make an arena operation fail part-way through.
+struct FaultyMap {
+    map: GAMap<u32>,
+    broken: Rc<Cell<Option<u16>>>,
+}
+
+impl IndexableMap<u32, u16> for FaultyMap {
+    const CAPACITY: usize = usize::MAX;
+
+    fn size(&self) -> usize {
+        self.map.size()
+    }
+
+    fn get_slot(&mut self, index: u16) -> Option<&mut Slot<u32, u16>> {
+        if self.broken.get() == Some(index) {
+            return None;
+        }
+        self.map.get_slot(index)
+    }
+
+    fn get_slot_ref(&self, index: u16) -> Option<&Slot<u32, u16>> {
+        self.map.get_slot_ref(index)
+    }
+
+    fn new_slot(&mut self, index: u16) -> ArenaResult<&mut Slot<u32, u16>> {
+        self.map.new_slot(index)
+    }
+
+    fn full_slots_from<'a>(
+        &'a self,
+        from: u16,
+    ) -> impl Iterator<Item = (u16, &'a Slot<u32, u16>)>
+    where
+        u32: 'a,
+    {
+        self.map.full_slots_from(from)
+    }
+
+    fn clear(&mut self) {
+        self.map.clear()
+    }
+
+    fn truncate(&mut self, len: u16) {
+        self.map.truncate(len)
+    }
+}
+
+#[test]
+fn failed_compaction_keeps_every_item() {
+    let broken = Rc::new(Cell::new(None));
+    let arena = IndexableMapArena::new(FaultyMap {
+        map: GAMap::new(),
+        broken: broken.clone(),
+    });
+    let indices = [0, 1, 2, 3, 4, 5].map(|x| arena.alloc(x).unwrap());
+    let [first, second, third, fourth, fifth, sixth] = indices;
+    for index in [third, second, first] {
+        arena.take(index).unwrap();
+    }
+    // The fourth item moves into the first slot, but the fifth cannot be
+    // moved into the second, as that unlinks the broken third slot.
+    broken.set(Some(2));
+    assert_eq!(arena.compact(), Err(ArenaError::IndexOutOfBounds));
+    broken.set(None);
+    let forwarded = arena.forward(fourth).unwrap();
+    assert_eq!(usize::from(forwarded.clone()), 0);
+    assert_eq!(arena.inspect(forwarded, |x| *x), Ok(3));
+    for (index, value) in [(fifth, 4), (sixth, 5)] {
+        assert_eq!(arena.forward(index.clone()), Ok(index.clone()));
+        assert_eq!(arena.inspect(index, |x| *x), Ok(value));
+    }
+    assert_eq!(arena.alloc(6), Err(ArenaError::Compacting));
+    arena.truncate().unwrap();
+    assert_eq!(arena.size(), 3);
+    assert_eq!(usize::from(arena.alloc(6).unwrap()), 1);
+}
+
+#[test]
+fn rollback_takes_out_only_items_allocated_in_the_transaction() {
+    let arena = GrowableArena::<u32>::new();
+    let kept = arena.alloc(0).unwrap();
+    arena.begin().unwrap();
+    assert_eq!(arena.begin(), Err(ArenaError::TransactionOpen));
+    let first = arena.alloc(1).unwrap();
+    let second = arena.alloc(2).unwrap();
+    let third = arena.alloc(3).unwrap();
+    assert_eq!(arena.take(second), Ok(2));
+    assert_eq!(arena.compact(), Err(ArenaError::TransactionOpen));
+    arena.rollback().unwrap();
+    assert_eq!(arena.size(), 1);
+    assert_eq!(arena.has_slot(first), Ok(false));
+    assert_eq!(arena.has_slot(third), Ok(false));
+    arena.begin().unwrap();
+    let committed = arena.alloc(4).unwrap();
+    arena.commit().unwrap();
+    arena.rollback().unwrap();
+    assert_eq!(arena.take(committed), Ok(4));
+    assert_eq!(arena.take(kept), Ok(0));
+}
+
+#[test]
+fn rollback_puts_back_items_taken_or_mutated_in_the_transaction() {
+    let arena = GrowableArena::<u32>::new();
+    let taken = arena.alloc(0).unwrap();
+    let mutated = arena.alloc(1).unwrap();
+    arena.begin().unwrap();
+    assert_eq!(arena.take(taken.clone()), Ok(0));
+    assert_eq!(arena.has_slot(taken.clone()), Ok(false));
+    arena
+        .inspect_mut(mutated.clone(), |x| *x = 10)
+        .unwrap();
+    assert_eq!(arena.inspect(mutated.clone(), |x| *x), Ok(10));
+    // The slot of the taken item is not reused until the transaction closes.
+    let allocated = arena.alloc(2).unwrap();
+    assert_eq!(usize::from(allocated.clone()), 2);
+    assert_eq!(arena.size(), 2);
+    arena.rollback().unwrap();
+    assert_eq!(arena.size(), 2);
+    assert_eq!(arena.has_slot(allocated), Ok(false));
+    assert_eq!(arena.inspect(taken.clone(), |x| *x), Ok(0));
+    assert_eq!(arena.inspect(mutated.clone(), |x| *x), Ok(1));
+
+    arena.begin().unwrap();
+    assert_eq!(arena.take(taken.clone()), Ok(0));
+    arena
+        .inspect_mut(mutated.clone(), |x| *x = 10)
+        .unwrap();
+    arena.commit().unwrap();
+    assert_eq!(arena.size(), 1);
+    assert_eq!(arena.has_slot(taken.clone()), Ok(false));
+    assert_eq!(arena.inspect(mutated, |x| *x), Ok(10));
+    assert_eq!(usize::from(arena.alloc(3).unwrap()), usize::from(taken));
+}
+
+#[test]
+fn rollback_takes_out_items_reinserted_in_the_transaction() {
+    let arena = GrowableArena::<u32>::new();
+    let held = arena.alloc(0).unwrap();
+    arena.begin().unwrap();
+    let allocated = arena.alloc(1).unwrap();
+    assert_eq!(arena.take(allocated.clone()), Ok(1));
+    arena
+        .insert(allocated.clone(), 2)
+        .unwrap();
+    assert_eq!(arena.take(held.clone()), Ok(0));
+    arena.insert(held.clone(), 3).unwrap();
+    assert_eq!(arena.size(), 2);
+    arena.rollback().unwrap();
+    assert_eq!(arena.has_slot(allocated), Ok(false));
+    assert_eq!(arena.inspect(held, |x| *x), Ok(0));
+    assert_eq!(arena.size(), 1);
+    assert_eq!(arena.stats().occupied, 1);
+}
+
+#[test]
+fn index_width_bounds_the_number_of_slots() {
+    let arena = GrowableArena::<u32, u8>::new();
+    let indices: Vec<_> = (0..u32::from(u8::MAX))
+        .map(|x| arena.alloc(x).unwrap())
+        .collect();
+    assert_eq!(arena.alloc(0), Err(ArenaError::LimitReached));
+    assert_eq!(arena.stats().capacity, usize::from(u8::MAX));
+    // Slots freed after reaching the limit are still handed out again.
+    let last = indices.last().cloned().unwrap();
+    assert_eq!(arena.take(last.clone()), Ok(254));
+    assert_eq!(usize::from(arena.alloc(255).unwrap()), usize::from(last));
+    let wide = StaticArena::<u32, 4, u32>::new();
+    assert_eq!(usize::from(wide.alloc(0).unwrap()), 0);
+    assert_eq!(wide.stats().capacity, 4);
+    assert_eq!(size_of::<Index<u32, u8>>(), 2);
+    assert_eq!(size_of::<Index<u32, u32>>(), 8);
+}
+
+#[test]
+fn occupied_slots_can_be_iterated_through_dyn_arenas() {
+    let growable = GrowableArena::<u32>::new();
+    let scapegoat = ScapegoatArena::<u32, 8>::new();
+    for arena in [&growable as &dyn Arena<u32>, &scapegoat] {
+        let indices = [0, 1, 2, 3, 4].map(|x| arena.alloc(x * 10).unwrap());
+        let [_, second, _, fourth, _] = indices.clone();
+        arena.take(second).unwrap();
+        arena.take(fourth).unwrap();
+        let positions: Vec<_> = arena
+            .occupied()
+            .map(|index| usize::from(index.unwrap()))
+            .collect();
+        assert_eq!(positions, [0, 2, 4]);
+        let values: Vec<_> = arena
+            .occupied_map(|x| *x)
+            .map(|entry| entry.unwrap().1)
+            .collect();
+        assert_eq!(values, [0, 20, 40]);
+        let mut visited = Vec::new();
+        arena
+            .for_each_occupied(&mut |index, x| {
+                // The arena may still be read while it is being iterated.
+                assert_eq!(arena.has_slot(index.clone()), Ok(true));
+                visited.push((usize::from(index), *x));
+            })
+            .unwrap();
+        assert_eq!(visited, [(0, 0), (2, 20), (4, 40)]);
+        // An item allocated in between is visited once the iterator reaches
+        // its slot.
+        let mut occupied = arena.occupied();
+        assert_eq!(
+            occupied
+                .next()
+                .map(|i| i.map(usize::from)),
+            Some(Ok(0))
+        );
+        arena.alloc(50).unwrap();
+        assert_eq!(occupied.count(), 3);
+    }
+}
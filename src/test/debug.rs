@@ -0,0 +1,78 @@
+use crate::arena::chain::Chain;
+use crate::arena::debug::ArenaDebug;
+use crate::arena::handler::ArenaHandler;
+use crate::arena::shared::share;
+use crate::arena::tuple::ArenaTuple;
+use crate::ast::note::NoteUnit;
+use crate::ast::note::Number;
+use crate::ast::pattern::Pattern;
+use crate::ast::pattern::TimeUnit;
+use crate::ast::pattern::TimedStep;
+use crate::test::equality::pattern_arenas;
+
+#[test]
+fn patterns_are_written_as_indented_trees() {
+    let arena_tuple = pattern_arenas();
+    let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let (patterns, (chains, (timed_steps, (timed_step_chains, (shared, ()))))) =
+        arenas;
+    let chain = |items: Vec<Pattern>| {
+        items
+            .into_iter()
+            .rev()
+            .fold(Chain::Nil, |tail, head| Chain::Cons {
+                head: patterns.alloc(head).unwrap(),
+                tail: chains.alloc(tail).unwrap(),
+            })
+    };
+    let step = TimedStep(
+        TimeUnit(2),
+        patterns
+            .alloc(Pattern::Silence)
+            .unwrap(),
+    );
+    let time_cat = Pattern::TimeCat(Chain::Cons {
+        head: timed_steps.alloc(step).unwrap(),
+        tail: timed_step_chains
+            .alloc(Chain::Nil)
+            .unwrap(),
+    });
+    let stack = share(shared, Pattern::Stack(chain(vec![]))).unwrap();
+    let seq =
+        Pattern::Seq(chain(vec![Pattern::Silence, Pattern::Shared(stack)]));
+    let note = Pattern::Note(NoteUnit::Number(Number(60)));
+    let pattern = Pattern::Cat(chain(vec![note, seq, time_cat]));
+
+    let expected = "\
+Cat
+  Note(Number(Number(60)))
+  Seq
+    Silence
+    Shared
+      Stack
+  TimeCat
+    TimedStep(TimeUnit(2))
+      Silence";
+    assert_eq!(format!("{}", pattern.debug_in(&arenas)), expected);
+    assert_eq!(format!("{:?}", pattern.debug_in(&arenas)), expected);
+    pattern.drop_in(&arenas);
+}
+
+#[test]
+fn indices_which_cannot_be_followed_are_written_as_errors() {
+    let arena_tuple = pattern_arenas();
+    let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let (patterns, (chains, _)) = arenas;
+    let head = patterns
+        .alloc(Pattern::Silence)
+        .unwrap();
+    let stale = head.clone();
+    patterns.take(head).unwrap();
+    let tail = chains.alloc(Chain::Nil).unwrap();
+    let pattern = Pattern::Stack(Chain::Cons { head: stale, tail });
+
+    let expected = "\
+Stack
+  <ExpectedFullSlot>";
+    assert_eq!(format!("{}", pattern.debug_in(&arenas)), expected);
+}
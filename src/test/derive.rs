@@ -0,0 +1,287 @@
+use synth_derive::ArenaDebug;
+use synth_derive::ArenaEq;
+use synth_derive::ArenaHandler;
+
+use crate::arena::arena_impl::growable_arena::GrowableArena;
+use crate::arena::chain::Chain;
+use crate::arena::compact::compact_in;
+use crate::arena::debug::ArenaDebug;
+use crate::arena::equality::ArenaEq;
+use crate::arena::error::ArenaError;
+use crate::arena::extension::Occupied;
+use crate::arena::handler::ArenaHandler;
+use crate::arena::index::Index;
+use crate::arena::node::ArenaNode;
+use crate::arena::reachability::check_reachability;
+use crate::arena::shared::Counted;
+use crate::arena::shared::retain;
+use crate::arena::shared::share;
+use crate::arena::transfer::MoveError;
+use crate::arena::tuple::ArenaTuple;
+use crate::arena::tuple::DynArenasOf;
+use crate::arena::work_stack::TRAVERSAL_DEPTH;
+
+/// A small expression tree with every kind of field the derive follows: bare
+/// indices, a chain held by value, an index of a narrower width, and a
+/// reference-counted item.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    ArenaHandler,
+    ArenaEq,
+    ArenaDebug,
+)]
+#[arena(
+    index = ExprIndex,
+    arenas(Expr, Chain<Expr>, Atom: u8, Counted<Expr>)
+)]
+enum Expr {
+    Add(Index<Expr>, Index<Expr>),
+    Sum(Chain<Expr>),
+    Unit(Index<Atom, u8>),
+    Shared(Index<Counted<Expr>>),
+    Zero,
+}
+
+/// An item without a placeholder, which is copied eagerly.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, ArenaHandler)]
+#[arena(index = ExprIndex)]
+struct Atom {
+    value: u32,
+    scale: Index<Expr>,
+}
+
+/// A tree like the sums of [Expr], whose traversals are given four times the
+/// default depth.
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, ArenaHandler, ArenaEq,
+)]
+#[arena(index = DeepIndex, arenas(Deep), depth = 4 * TRAVERSAL_DEPTH)]
+enum Deep {
+    Leaf,
+    Pair(Index<Deep>, Index<Deep>),
+}
+
+type ExprArenas = (
+    GrowableArena<Expr>,
+    (
+        GrowableArena<Chain<Expr>>,
+        (GrowableArena<Atom, u8>, (GrowableArena<Counted<Expr>>, ())),
+    ),
+);
+
+fn expr_arenas() -> ExprArenas {
+    (
+        GrowableArena::new(),
+        (
+            GrowableArena::new(),
+            (GrowableArena::new(), (GrowableArena::new(), ())),
+        ),
+    )
+}
+
+/// Returns the number of full slots in each arena of `arenas`.
+fn occupied(arenas: &DynArenasOf<'_, Expr>) -> [usize; 4] {
+    let (exprs, (chains, (atoms, (shared, ())))) = *arenas;
+    [
+        exprs.occupied().count(),
+        chains.occupied().count(),
+        atoms.occupied().count(),
+        shared.occupied().count(),
+    ]
+}
+
+/// Builds `Add(Unit(Atom(7, Zero)), Sum[Shared(s), Shared(s)])`, where `s`
+/// is a shared `Sum[Zero]`.
+fn build(arenas: &DynArenasOf<'_, Expr>) -> Expr {
+    let (exprs, (chains, (atoms, (shared, ())))) = *arenas;
+    let cons = |head, tail| {
+        let head = exprs.alloc(head).unwrap();
+        let tail = chains.alloc(tail).unwrap();
+        Chain::Cons { head, tail }
+    };
+    let zero = exprs.alloc(Expr::Zero).unwrap();
+    let atom = atoms
+        .alloc(Atom { value: 7, scale: zero })
+        .unwrap();
+    let unit = exprs.alloc(Expr::Unit(atom)).unwrap();
+    let common =
+        share(shared, Expr::Sum(cons(Expr::Zero, Chain::Nil))).unwrap();
+    let again = retain(shared, &common).unwrap();
+    let sum = Expr::Sum(cons(
+        Expr::Shared(common),
+        cons(Expr::Shared(again), Chain::Nil),
+    ));
+    let sum = exprs.alloc(sum).unwrap();
+    Expr::Add(unit, sum)
+}
+
+#[test]
+fn derived_handlers_clone_and_drop_every_kind_of_field() {
+    let arena_tuple = expr_arenas();
+    let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let expr = build(&arenas);
+    assert_eq!(occupied(&arenas), [6, 3, 1, 1]);
+    assert!(
+        expr.validate_in(&arenas)
+            .unwrap()
+            .is_valid()
+    );
+
+    let clone = expr.clone_in(&arenas);
+    assert_eq!(occupied(&arenas), [11, 5, 2, 1]);
+    assert!(ArenaEq::eq_in(&expr, &clone, &arenas, &arenas));
+    assert!(
+        clone
+            .validate_in(&arenas)
+            .unwrap()
+            .is_valid()
+    );
+    let reachability =
+        check_reachability(&[expr.clone(), clone.clone()], &arenas).unwrap();
+    assert!(reachability.is_sound(), "{reachability:?}");
+
+    // Dropping the original leaves holes before the clone, which compaction
+    // moves it into.
+    expr.drop_in(&arenas);
+    assert_eq!(occupied(&arenas), [6, 3, 1, 1]);
+    let mut roots = [clone];
+    compact_in(&mut roots, &arenas).unwrap();
+    let [clone] = roots;
+    let reachability =
+        check_reachability(std::slice::from_ref(&clone), &arenas).unwrap();
+    assert!(reachability.is_sound(), "{reachability:?}");
+    clone.drop_in(&arenas);
+    assert_eq!(occupied(&arenas), [0, 0, 0, 0]);
+}
+
+#[test]
+fn derived_debug_writes_every_kind_of_field() {
+    let arena_tuple = expr_arenas();
+    let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let expr = build(&arenas);
+    let expected = "\
+Add
+  Unit
+    Atom { value: 7 }
+      Zero
+  Sum
+    Shared
+      Sum
+        Zero
+    Shared
+      Sum
+        Zero";
+    assert_eq!(format!("{}", expr.debug_in(&arenas)), expected);
+    expr.drop_in(&arenas);
+}
+
+#[test]
+fn derived_handlers_copy_shared_items_once() {
+    let source_tuple = expr_arenas();
+    let source = ArenaTuple::to_dyn_arenas(&source_tuple);
+    let destination_tuple = expr_arenas();
+    let destination = ArenaTuple::to_dyn_arenas(&destination_tuple);
+    let expr = build(&source);
+
+    let copy = expr
+        .try_move_to(&source, &destination)
+        .unwrap();
+    assert_eq!(occupied(&source), [0, 0, 0, 0]);
+    assert_eq!(occupied(&destination), [6, 3, 1, 1]);
+    let (_, (_, (_, (shared, ())))) = destination;
+    let counts = shared
+        .occupied_map(Counted::count)
+        .map(|entry| entry.unwrap().1)
+        .collect::<Vec<_>>();
+    assert_eq!(counts, [2]);
+    assert!(
+        copy.validate_in(&destination)
+            .unwrap()
+            .is_valid()
+    );
+    copy.drop_in(&destination);
+    assert_eq!(occupied(&destination), [0, 0, 0, 0]);
+}
+
+#[test]
+fn move_gives_back_the_copy_if_the_source_is_not_released() {
+    let source_tuple = expr_arenas();
+    let source = ArenaTuple::to_dyn_arenas(&source_tuple);
+    let destination_tuple = expr_arenas();
+    let destination = ArenaTuple::to_dyn_arenas(&destination_tuple);
+    let mut expr = Some(build(&source));
+
+    // Reading an atom still lets it be copied, but not taken out.
+    let (_, (_, (atoms, (_, ())))) = source;
+    let atom = atoms.next_occupied(0).unwrap().unwrap();
+    let mut moved = None;
+    atoms
+        .with_ref(atom, &mut |_| {
+            moved = expr
+                .take()
+                .map(|expr| expr.try_move_to(&source, &destination));
+        })
+        .unwrap();
+    let Some(Err(MoveError::NotReleased(error, copy))) = moved else {
+        panic!("Expected the copy to be given back, got {moved:?}");
+    };
+    assert_eq!(error, ArenaError::AlreadyBorrowed);
+    assert_eq!(occupied(&destination), [6, 3, 1, 1]);
+    assert!(
+        copy.validate_in(&destination)
+            .unwrap()
+            .is_valid()
+    );
+    copy.drop_in(&destination);
+}
+
+#[test]
+fn derived_nodes_count_the_indices_of_their_widest_variant() {
+    // `Add` holds two indices, as does the chain held by `Sum`.
+    assert_eq!(<Expr as ArenaNode<ExprIndex>>::CHILDREN, 2);
+    assert_eq!(<Atom as ArenaNode<ExprIndex>>::CHILDREN, 1);
+    assert_eq!(<Counted<Expr> as ArenaNode<ExprIndex>>::CHILDREN, 2);
+    assert_eq!(<Chain<Expr> as ArenaNode<ExprIndex>>::CHILDREN, 2);
+}
+
+#[test]
+fn derived_handlers_keep_to_a_bounded_stack() {
+    let arena_tuple = expr_arenas();
+    let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let (exprs, _) = arenas;
+    let mut expr = Expr::Zero;
+    for _ in 0..2 * TRAVERSAL_DEPTH {
+        // Nesting through the first child leaves the second waiting on the
+        // stack at every level.
+        let left = exprs.alloc(expr).unwrap();
+        let right = exprs.alloc(Expr::Zero).unwrap();
+        expr = Expr::Add(left, right);
+    }
+    assert_eq!(expr.try_clone_in(&arenas), Err(ArenaError::DepthLimitExceeded));
+    assert_eq!(occupied(&arenas), [4 * TRAVERSAL_DEPTH, 0, 0, 0]);
+    expr.try_drop_in(&arenas).unwrap();
+    assert_eq!(occupied(&arenas), [0, 0, 0, 0]);
+}
+
+#[test]
+fn derived_handlers_walk_as_deep_as_their_depth() {
+    let arena_tuple = (GrowableArena::<Deep>::new(), ());
+    let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let (deeps, ()) = arenas;
+    let mut deep = Deep::Leaf;
+    for _ in 0..2 * TRAVERSAL_DEPTH {
+        let left = deeps.alloc(deep).unwrap();
+        let right = deeps.alloc(Deep::Leaf).unwrap();
+        deep = Deep::Pair(left, right);
+    }
+    let clone = deep.try_clone_in(&arenas).unwrap();
+    assert_eq!(ArenaEq::try_eq_in(&deep, &clone, &arenas, &arenas), Ok(true));
+    clone.try_drop_in(&arenas).unwrap();
+    deep.try_drop_in(&arenas).unwrap();
+    assert_eq!(deeps.occupied().count(), 0);
+}
@@ -0,0 +1,373 @@
+use core::mem::discriminant;
+
+use proptest::test_runner::TestRunner;
+
+use crate::arena::arena_impl::growable_arena::GrowableArena;
+use crate::arena::chain::Chain;
+use crate::arena::debug::ArenaDebug;
+use crate::arena::equality::ArenaEq;
+use crate::arena::error::ArenaResult;
+use crate::arena::extension::Inspect;
+use crate::arena::handler::ArenaHandler;
+use crate::arena::tuple::ArenaTuple;
+use crate::arena::tuple::DynArenasOf;
+use crate::arena::work_stack::WorkStack;
+use crate::ast::pattern::Pattern;
+use crate::ast::pattern::PatternChain;
+use crate::ast::pattern::PatternIndex;
+use crate::ast::pattern::PatternWidths;
+use crate::ast::pattern::SharedPattern;
+use crate::ast::pattern::TimeUnit;
+use crate::ast::pattern::TimedStep;
+use crate::ast::pattern::TimedStepChain;
+use crate::ast::traversal::PatternArenas;
+use crate::test::arbitrary::arb_pattern;
+
+/// The pairs of items which are still to be compared, one from each side.
+type PendingPairs<W> = WorkStack<(PatternIndex<W>, PatternIndex<W>)>;
+
+/// Returns true if the shallow patterns `this` and `other` agree in everything
+/// but the items they point to, pushing each pair of those to be compared
+/// next.
+fn patterns_eq<W: PatternWidths>(
+    this: &Pattern<W>,
+    other: &Pattern<W>,
+    stack: &mut PendingPairs<W>,
+) -> ArenaResult<bool> {
+    if discriminant(this) != discriminant(other) {
+        return Ok(false);
+    }
+    match (this, other) {
+        (Pattern::Cat(this_chain), Pattern::Cat(other_chain))
+        | (Pattern::Seq(this_chain), Pattern::Seq(other_chain))
+        | (Pattern::Stack(this_chain), Pattern::Stack(other_chain)) => {
+            pattern_chains_eq(this_chain, other_chain, stack)
+        }
+        (Pattern::TimeCat(this_chain), Pattern::TimeCat(other_chain)) => {
+            timed_step_chains_eq(this_chain, other_chain, stack)
+        }
+        (Pattern::Note(this_note), Pattern::Note(other_note)) => {
+            Ok(this_note == other_note)
+        }
+        (Pattern::Silence, Pattern::Silence) => Ok(true),
+        (Pattern::Shared(this_index), Pattern::Shared(other_index)) => {
+            stack.push((
+                PatternIndex::SharedPattern(this_index.clone()),
+                PatternIndex::SharedPattern(other_index.clone()),
+            ))?;
+            Ok(true)
+        }
+        // Already ruled out by comparing discriminants above.
+        _ => Ok(false),
+    }
+}
+
+/// Returns true if the cells `this` and `other` are both the end of their
+/// chains, or both not, pushing their tails and then their heads to be
+/// compared next.
+fn pattern_chains_eq<W: PatternWidths>(
+    this: &PatternChain<W>,
+    other: &PatternChain<W>,
+    stack: &mut PendingPairs<W>,
+) -> ArenaResult<bool> {
+    match (this, other) {
+        (
+            Chain::Cons { head: this_head, tail: this_tail },
+            Chain::Cons { head: other_head, tail: other_tail },
+        ) => {
+            stack.push((
+                PatternIndex::PatternChain(this_tail.clone()),
+                PatternIndex::PatternChain(other_tail.clone()),
+            ))?;
+            stack.push((
+                PatternIndex::Pattern(this_head.clone()),
+                PatternIndex::Pattern(other_head.clone()),
+            ))?;
+            Ok(true)
+        }
+        (Chain::Nil, Chain::Nil) => Ok(true),
+        _ => Ok(false),
+    }
+}
+
+/// Compares the cells `this` and `other` as in [pattern_chains_eq].
+fn timed_step_chains_eq<W: PatternWidths>(
+    this: &TimedStepChain<W>,
+    other: &TimedStepChain<W>,
+    stack: &mut PendingPairs<W>,
+) -> ArenaResult<bool> {
+    match (this, other) {
+        (
+            Chain::Cons { head: this_head, tail: this_tail },
+            Chain::Cons { head: other_head, tail: other_tail },
+        ) => {
+            stack.push((
+                PatternIndex::TimedStepChain(this_tail.clone()),
+                PatternIndex::TimedStepChain(other_tail.clone()),
+            ))?;
+            stack.push((
+                PatternIndex::TimedStep(this_head.clone()),
+                PatternIndex::TimedStep(other_head.clone()),
+            ))?;
+            Ok(true)
+        }
+        (Chain::Nil, Chain::Nil) => Ok(true),
+        _ => Ok(false),
+    }
+}
+
+/// Compares each pair of items on `stack`, and each pair they point to, until
+/// a pair differs or the stack is empty.
+fn pending_eq<W: PatternWidths>(
+    mut stack: PendingPairs<W>,
+    this_arenas: &PatternArenas<'_, W>,
+    other_arenas: &PatternArenas<'_, W>,
+) -> ArenaResult<bool> {
+    while let Some(pair) = stack.pop() {
+        let equal = match pair {
+            (PatternIndex::Pattern(this), PatternIndex::Pattern(other)) => {
+                patterns_eq(
+                    &this_arenas
+                        .patterns
+                        .inspect(this, Pattern::clone)?,
+                    &other_arenas
+                        .patterns
+                        .inspect(other, Pattern::clone)?,
+                    &mut stack,
+                )?
+            }
+            (
+                PatternIndex::PatternChain(this),
+                PatternIndex::PatternChain(other),
+            ) => pattern_chains_eq(
+                &this_arenas
+                    .chains
+                    .inspect(this, Chain::clone)?,
+                &other_arenas
+                    .chains
+                    .inspect(other, Chain::clone)?,
+                &mut stack,
+            )?,
+            (PatternIndex::TimedStep(this), PatternIndex::TimedStep(other)) => {
+                let TimedStep(this_unit, this_pattern) = this_arenas
+                    .timed_steps
+                    .inspect(this, TimedStep::clone)?;
+                let TimedStep(other_unit, other_pattern) = other_arenas
+                    .timed_steps
+                    .inspect(other, TimedStep::clone)?;
+                stack.push((
+                    PatternIndex::Pattern(this_pattern),
+                    PatternIndex::Pattern(other_pattern),
+                ))?;
+                this_unit == other_unit
+            }
+            (
+                PatternIndex::TimedStepChain(this),
+                PatternIndex::TimedStepChain(other),
+            ) => timed_step_chains_eq(
+                &this_arenas
+                    .timed_step_chains
+                    .inspect(this, Chain::clone)?,
+                &other_arenas
+                    .timed_step_chains
+                    .inspect(other, Chain::clone)?,
+                &mut stack,
+            )?,
+            (
+                PatternIndex::SharedPattern(this),
+                PatternIndex::SharedPattern(other),
+            ) => patterns_eq(
+                &this_arenas
+                    .shared
+                    .inspect(this, |shared| shared.get().clone())?,
+                &other_arenas
+                    .shared
+                    .inspect(other, |shared| shared.get().clone())?,
+                &mut stack,
+            )?,
+            // Only pairs of the same kind are ever pushed.
+            _ => false,
+        };
+        if !equal {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// The hand-written comparison of [TimedStep]s which `#[derive(ArenaEq)]`
+/// replaced, kept as a reference for the derived one.
+fn reference_timed_step_eq<W: PatternWidths>(
+    this: &TimedStep<W>,
+    other: &TimedStep<W>,
+    this_arenas: &DynArenasOf<'_, TimedStep<W>>,
+    other_arenas: &DynArenasOf<'_, TimedStep<W>>,
+) -> ArenaResult<bool> {
+    let TimedStep(this_unit, this_pattern_index) = this;
+    let TimedStep(other_unit, other_pattern_index) = other;
+    if this_unit != other_unit {
+        return Ok(false);
+    }
+    let mut stack = WorkStack::new();
+    stack.push((
+        PatternIndex::Pattern(this_pattern_index.clone()),
+        PatternIndex::Pattern(other_pattern_index.clone()),
+    ))?;
+    pending_eq(
+        stack,
+        &PatternArenas::new(this_arenas),
+        &PatternArenas::new(other_arenas),
+    )
+}
+
+/// The hand-written comparison of [Pattern]s which `#[derive(ArenaEq)]`
+/// replaced, kept as a reference for the derived one.
+fn reference_eq<W: PatternWidths>(
+    this: &Pattern<W>,
+    other: &Pattern<W>,
+    this_arenas: &DynArenasOf<'_, Pattern<W>>,
+    other_arenas: &DynArenasOf<'_, Pattern<W>>,
+) -> ArenaResult<bool> {
+    let mut stack = WorkStack::new();
+    if !patterns_eq(this, other, &mut stack)? {
+        return Ok(false);
+    }
+    pending_eq(
+        stack,
+        &PatternArenas::new(this_arenas),
+        &PatternArenas::new(other_arenas),
+    )
+}
+
+/// A tuple of growable arenas for [Pattern]s.
+pub type PatternArenaTuple = (
+    GrowableArena<Pattern>,
+    (
+        GrowableArena<PatternChain>,
+        (
+            GrowableArena<TimedStep>,
+            (GrowableArena<TimedStepChain>, (GrowableArena<SharedPattern>, ())),
+        ),
+    ),
+);
+
+pub fn pattern_arenas() -> PatternArenaTuple {
+    pattern_arena_tuple!(GrowableArena)
+}
+
+/// Asserts that the derived and the hand-written comparisons agree on `this`
+/// and `other`, returning what they say.
+fn check_agrees(
+    this: &Pattern,
+    other: &Pattern,
+    this_arenas: &DynArenasOf<'_, Pattern>,
+    other_arenas: &DynArenasOf<'_, Pattern>,
+) -> bool {
+    let derived = ArenaEq::try_eq_in(this, other, this_arenas, other_arenas);
+    let reference = reference_eq(this, other, this_arenas, other_arenas);
+    assert_eq!(
+        derived,
+        reference,
+        "Comparing\n{}\nwith\n{}",
+        this.debug_in(this_arenas),
+        other.debug_in(other_arenas),
+    );
+    derived.unwrap()
+}
+
+#[test]
+fn derived_eq_agrees_with_reference_on_arbitrary_pairs() {
+    let mut test_runner = TestRunner::deterministic();
+    let strat = (arb_pattern(), arb_pattern());
+    test_runner
+        .run(&strat, |(this, other)| {
+            let arena_tuple = pattern_arenas();
+            let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+            let this = (this.0)(arenas).unwrap();
+            let other = (other.0)(arenas).unwrap();
+            check_agrees(&this, &other, &arenas, &arenas);
+            check_agrees(&other, &this, &arenas, &arenas);
+            Ok(())
+        })
+        .unwrap()
+}
+
+#[test]
+fn derived_eq_agrees_with_reference_on_copies() {
+    let mut test_runner = TestRunner::deterministic();
+    test_runner
+        .run(&arb_pattern(), |pattern| {
+            let arena_tuple = pattern_arenas();
+            let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+            let other_tuple = pattern_arenas();
+            let other_arenas = ArenaTuple::to_dyn_arenas(&other_tuple);
+            let pattern = (pattern.0)(arenas).unwrap();
+            let clone = pattern.clone_in(&arenas);
+            assert!(check_agrees(&pattern, &clone, &arenas, &arenas));
+            let copy = pattern
+                .try_copy_to(&arenas, &other_arenas)
+                .unwrap();
+            assert!(check_agrees(&pattern, &copy, &arenas, &other_arenas));
+            Ok(())
+        })
+        .unwrap()
+}
+
+#[test]
+fn derived_eq_agrees_with_reference_on_timed_steps() {
+    let arena_tuple = pattern_arenas();
+    let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let (patterns, (chains, (timed_steps, (timed_step_chains, _)))) = arenas;
+    let step = |unit, pattern| {
+        TimedStep(TimeUnit(unit), patterns.alloc(pattern).unwrap())
+    };
+    let time_cat = |steps: &[TimedStep]| {
+        let chain = steps
+            .iter()
+            .rev()
+            .fold(Chain::Nil, |tail, head| Chain::Cons {
+                head: timed_steps.alloc(head.clone()).unwrap(),
+                tail: timed_step_chains.alloc(tail).unwrap(),
+            });
+        Pattern::TimeCat(chain)
+    };
+    let silent_cat = Pattern::Cat(Chain::Cons {
+        head: patterns
+            .alloc(Pattern::Silence)
+            .unwrap(),
+        tail: chains.alloc(Chain::Nil).unwrap(),
+    });
+    let steps = [
+        step(1, Pattern::Silence),
+        step(2, Pattern::Silence),
+        step(1, silent_cat.clone_in(&arenas)),
+        step(1, Pattern::Cat(Chain::Nil)),
+    ];
+    for this in &steps {
+        for other in &steps {
+            let derived = ArenaEq::try_eq_in(this, other, &arenas, &arenas);
+            let reference =
+                reference_timed_step_eq(this, other, &arenas, &arenas);
+            assert_eq!(
+                derived,
+                reference,
+                "Comparing\n{}\nwith\n{}",
+                this.debug_in(&arenas),
+                other.debug_in(&arenas),
+            );
+        }
+    }
+    let time_cats = [
+        time_cat(&[]),
+        time_cat(&steps[..1]),
+        time_cat(&steps[..2]),
+        time_cat(&[steps[0].clone(), steps[2].clone()]),
+        time_cat(&[steps[0].clone(), steps[3].clone()]),
+    ];
+    for (position, this) in time_cats.iter().enumerate() {
+        for (other_position, other) in time_cats.iter().enumerate() {
+            let equal = check_agrees(this, other, &arenas, &arenas);
+            assert_eq!(equal, position == other_position);
+        }
+    }
+}
@@ -1,4 +1,61 @@
 #![cfg(test)]
 
+/// Creates a tuple of arenas for [Pattern](crate::ast::pattern::Pattern)s,
+/// each of the given arena type, in the order of their
+/// [DynArenasOf](crate::arena::tuple::DynArenasOf).
+///
+/// A fixed-capacity arena type is followed by one capacity for every arena,
+/// or by a list of five capacities, one for each arena in order.
+macro_rules! pattern_arena_tuple {
+    ($arena:ident, [$p:expr, $c:expr, $t:expr, $tc:expr, $s:expr $(,)?]) => {
+        pattern_arena_tuple!(@ $arena; $p; $c; $t; $tc; $s)
+    };
+    ($arena:ident $(, $capacity:expr)?) => {
+        pattern_arena_tuple!(
+            @ $arena;
+            $($capacity)?;
+            $($capacity)?;
+            $($capacity)?;
+            $($capacity)?;
+            $($capacity)?
+        )
+    };
+    (
+        @ $arena:ident;
+        $($p:expr)?;
+        $($c:expr)?;
+        $($t:expr)?;
+        $($tc:expr)?;
+        $($s:expr)?
+    ) => {
+        (
+            $arena::<$crate::ast::pattern::Pattern $(, $p)?>::new(),
+            (
+                $arena::<$crate::ast::pattern::PatternChain $(, $c)?>::new(),
+                (
+                    $arena::<$crate::ast::pattern::TimedStep $(, $t)?>::new(),
+                    (
+                        $arena::<
+                            $crate::ast::pattern::TimedStepChain $(, $tc)?
+                        >::new(),
+                        (
+                            $arena::<
+                                $crate::ast::pattern::SharedPattern $(, $s)?
+                            >::new(),
+                            (),
+                        ),
+                    ),
+                ),
+            ),
+        )
+    };
+}
+
 mod arbitrary;
 mod arena_alloc;
+mod arena_impl;
+mod debug;
+mod derive;
+mod equality;
+mod ordering;
+mod visit;
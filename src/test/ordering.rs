@@ -0,0 +1,166 @@
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+
+use proptest::test_runner::TestRunner;
+
+use crate::arena::chain::Chain;
+use crate::arena::debug::ArenaDebug;
+use crate::arena::equality::ArenaEq;
+use crate::arena::error::ArenaError;
+use crate::arena::handler::ArenaHandler;
+use crate::arena::hash::ArenaHash;
+use crate::arena::ordering::ArenaOrd;
+use crate::arena::tuple::ArenaTuple;
+use crate::arena::tuple::DynArenasOf;
+use crate::ast::note::Letter;
+use crate::ast::note::NoteUnit;
+use crate::ast::pattern::Pattern;
+use crate::test::arbitrary::arb_pattern;
+use crate::test::equality::pattern_arenas;
+
+/// Returns the hash of `pattern` by content.
+fn content_hash(pattern: &Pattern, arenas: &DynArenasOf<'_, Pattern>) -> u64 {
+    let mut state = DefaultHasher::new();
+    pattern
+        .try_hash_in(arenas, &mut state)
+        .unwrap();
+    state.finish()
+}
+
+#[test]
+fn ordering_agrees_with_equality_and_hashing() {
+    let mut test_runner = TestRunner::deterministic();
+    let strat = (arb_pattern(), arb_pattern(), arb_pattern());
+    test_runner
+        .run(&strat, |(a, b, c)| {
+            let arena_tuple = pattern_arenas();
+            let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+            let patterns = [
+                (a.0)(arenas).unwrap(),
+                (b.0)(arenas).unwrap(),
+                (c.0)(arenas).unwrap(),
+            ];
+            let cmp = |this: &Pattern, other: &Pattern| {
+                ArenaOrd::try_cmp_in(this, other, &arenas, &arenas).unwrap()
+            };
+            for this in &patterns {
+                for other in &patterns {
+                    let ordering = cmp(this, other);
+                    assert_eq!(ordering.reverse(), cmp(other, this));
+                    let equal = ArenaEq::eq_in(this, other, &arenas, &arenas);
+                    assert_eq!(
+                        ordering == Ordering::Equal,
+                        equal,
+                        "Comparing\n{}\nwith\n{}",
+                        this.debug_in(&arenas),
+                        other.debug_in(&arenas),
+                    );
+                    if equal {
+                        assert_eq!(
+                            content_hash(this, &arenas),
+                            content_hash(other, &arenas)
+                        );
+                    }
+                }
+            }
+            // Whatever order the patterns were sorted into, a total order
+            // keeps every pair in it.
+            let mut sorted = patterns.iter().collect::<Vec<_>>();
+            sorted.sort_by(|this, other| cmp(this, other));
+            for (position, this) in sorted.iter().enumerate() {
+                for other in &sorted[position..] {
+                    assert_ne!(cmp(this, other), Ordering::Greater);
+                }
+            }
+            Ok(())
+        })
+        .unwrap()
+}
+
+#[test]
+fn copies_compare_and_hash_as_equal_across_arenas() {
+    let mut test_runner = TestRunner::deterministic();
+    test_runner
+        .run(&arb_pattern(), |pattern| {
+            let arena_tuple = pattern_arenas();
+            let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+            let other_tuple = pattern_arenas();
+            let other_arenas = ArenaTuple::to_dyn_arenas(&other_tuple);
+            let pattern = (pattern.0)(arenas).unwrap();
+            let copy = pattern
+                .try_copy_to(&arenas, &other_arenas)
+                .unwrap();
+            let ordering =
+                ArenaOrd::try_cmp_in(&pattern, &copy, &arenas, &other_arenas);
+            assert_eq!(ordering, Ok(Ordering::Equal));
+            assert_eq!(
+                content_hash(&pattern, &arenas),
+                content_hash(&copy, &other_arenas)
+            );
+            Ok(())
+        })
+        .unwrap()
+}
+
+#[test]
+fn patterns_are_ordered_by_content_rather_than_by_slot() {
+    let arena_tuple = pattern_arenas();
+    let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let (patterns, (chains, _)) = arenas;
+    let chain = |items: &[Pattern]| {
+        items
+            .iter()
+            .rev()
+            .fold(Chain::Nil, |tail, head| Chain::Cons {
+                head: patterns.alloc(head.clone()).unwrap(),
+                tail: chains.alloc(tail).unwrap(),
+            })
+    };
+    let note = |letter| Pattern::Note(NoteUnit::Letter(letter));
+    // Allocated first, so its indices are the lowest.
+    let b = Pattern::Cat(chain(&[note(Letter::B)]));
+    let a = Pattern::Cat(chain(&[note(Letter::A)]));
+    let a_then_b = Pattern::Cat(chain(&[note(Letter::A), note(Letter::B)]));
+    let seq = Pattern::Seq(chain(&[]));
+    assert_eq!(b.cmp(&a), Ordering::Less);
+
+    let mut set = BTreeSet::new();
+    for pattern in [&seq, &a_then_b, &b, &a, &b] {
+        set.insert(pattern.by_content(&arenas).unwrap());
+    }
+    let sorted = set
+        .into_iter()
+        .map(|key| format!("{key:?}").replace('\n', " "))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        sorted,
+        [
+            "Cat   Note(Letter(A))",
+            "Cat   Note(Letter(A))   Note(Letter(B))",
+            "Cat   Note(Letter(B))",
+            "Seq",
+        ]
+    );
+}
+
+#[test]
+fn items_which_cannot_be_compared_are_not_wrapped() {
+    let arena_tuple = pattern_arenas();
+    let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let (patterns, (chains, _)) = arenas;
+    let note = Pattern::Note(NoteUnit::Letter(Letter::A));
+    let head = patterns.alloc(note).unwrap();
+    let cat = Pattern::Cat(Chain::Cons {
+        head: head.clone(),
+        tail: chains.alloc(Chain::Nil).unwrap(),
+    });
+    assert!(cat.by_content(&arenas).is_ok());
+
+    patterns.take(head).unwrap();
+    assert_eq!(
+        cat.by_content(&arenas).map(|_| ()),
+        Err(ArenaError::ExpectedFullSlot)
+    );
+}
@@ -0,0 +1,312 @@
+use std::any::Any;
+
+use proptest::test_runner::TestRunner;
+
+use crate::arena::chain::Chain;
+use crate::arena::debug::ArenaDebug;
+use crate::arena::error::ArenaResult;
+use crate::arena::handler::ArenaHandler;
+use crate::arena::tuple::ArenaTuple;
+use crate::arena::visit::ArenaFold;
+use crate::arena::visit::ArenaVisit;
+use crate::arena::visit::ArenaVisitMut;
+use crate::arena::visit::Flow;
+use crate::arena::visit::Folder;
+use crate::arena::visit::Visitor;
+use crate::arena::visit::VisitorMut;
+use crate::ast::interner::Interned;
+use crate::ast::interner::Interner;
+use crate::ast::note::Letter;
+use crate::ast::note::NoteUnit;
+use crate::ast::pattern::Pattern;
+use crate::ast::pattern::TimedStep;
+use crate::test::arbitrary::arb_pattern;
+use crate::test::equality::pattern_arenas;
+
+/// Returns the line which [ArenaDebug] writes for `node`.
+fn label(node: &dyn Any) -> String {
+    if let Some(TimedStep(unit, _)) = node.downcast_ref::<TimedStep>() {
+        return format!("TimedStep({unit:?})");
+    }
+    match node.downcast_ref::<Pattern>() {
+        Some(Pattern::Cat(_)) => "Cat".into(),
+        Some(Pattern::Seq(_)) => "Seq".into(),
+        Some(Pattern::Stack(_)) => "Stack".into(),
+        Some(Pattern::TimeCat(_)) => "TimeCat".into(),
+        Some(Pattern::Note(note)) => format!("Note({note:?})"),
+        Some(Pattern::Silence) => "Silence".into(),
+        Some(Pattern::Shared(_)) => "Shared".into(),
+        Some(Pattern::Repeated(leaf, _)) => format!("Repeated({leaf:?})"),
+        None => "?".into(),
+    }
+}
+
+/// Writes each node on its own line, indented by how deeply it is nested.
+#[derive(Default)]
+struct Printer {
+    depth: usize,
+    lines: Vec<String>,
+}
+
+impl Visitor for Printer {
+    fn pre(&mut self, node: &dyn Any) -> ArenaResult<Flow> {
+        let indent = "  ".repeat(self.depth);
+        self.lines
+            .push(format!("{indent}{}", label(node)));
+        self.depth += 1;
+        Ok(Flow::Continue)
+    }
+
+    fn post(&mut self, _: &dyn Any) -> ArenaResult<()> {
+        self.depth -= 1;
+        Ok(())
+    }
+}
+
+/// Counts the nodes of a pattern.
+struct Size;
+
+impl Folder for Size {
+    type Output = usize;
+
+    fn enter(&mut self, _: &dyn Any) -> ArenaResult<usize> {
+        Ok(1)
+    }
+
+    fn combine(&mut self, owner: &mut usize, child: usize) -> ArenaResult<()> {
+        *owner += child;
+        Ok(())
+    }
+}
+
+/// Counts the nodes of a pattern whose label starts with the given prefix.
+struct Count(&'static str);
+
+impl Folder for Count {
+    type Output = usize;
+
+    fn enter(&mut self, node: &dyn Any) -> ArenaResult<usize> {
+        Ok(usize::from(label(node).starts_with(self.0)))
+    }
+
+    fn combine(&mut self, owner: &mut usize, child: usize) -> ArenaResult<()> {
+        *owner += child;
+        Ok(())
+    }
+}
+
+/// Counts the nodes of a pattern whose label starts with the given prefix,
+/// outside of its shared patterns.
+struct Unshared(&'static str, usize);
+
+impl Visitor for Unshared {
+    fn pre(&mut self, node: &dyn Any) -> ArenaResult<Flow> {
+        let label = label(node);
+        self.1 += usize::from(label.starts_with(self.0));
+        Ok(match label.as_str() {
+            "Shared" => Flow::Skip,
+            _ => Flow::Continue,
+        })
+    }
+}
+
+/// Rewrites every `Seq` into a `Cat` of the same patterns, and every note into
+/// silence.
+struct Mute;
+
+impl VisitorMut for Mute {
+    fn pre(&mut self, node: &mut dyn Any) -> ArenaResult<Flow> {
+        if let Some(pattern) = node.downcast_mut::<Pattern>() {
+            match pattern {
+                Pattern::Seq(chain) => {
+                    *pattern = Pattern::Cat(chain.clone());
+                }
+                Pattern::Note(_) => *pattern = Pattern::Silence,
+                _ => {}
+            }
+        }
+        Ok(Flow::Continue)
+    }
+}
+
+#[test]
+fn visitors_see_every_node_in_order() {
+    let mut test_runner = TestRunner::deterministic();
+    test_runner
+        .run(&arb_pattern(), |pattern| {
+            let arena_tuple = pattern_arenas();
+            let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+            let pattern = (pattern.0)(arenas).unwrap();
+
+            let mut printer = Printer::default();
+            pattern
+                .try_visit_in(&arenas, &mut printer)
+                .unwrap();
+            assert_eq!(printer.depth, 0);
+            let expected = format!("{}", pattern.debug_in(&arenas));
+            assert_eq!(printer.lines.join("\n"), expected);
+
+            let size = pattern
+                .try_fold_in(&arenas, &mut Size)
+                .unwrap();
+            assert_eq!(size, printer.lines.len());
+            Ok(())
+        })
+        .unwrap()
+}
+
+#[test]
+fn mutable_visitors_rewrite_nodes_in_place() {
+    let mut test_runner = TestRunner::deterministic();
+    test_runner
+        .run(&arb_pattern(), |pattern| {
+            let arena_tuple = pattern_arenas();
+            let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+            let mut pattern = (pattern.0)(arenas).unwrap();
+            let count = |pattern: &Pattern, label| {
+                pattern
+                    .try_fold_in(&arenas, &mut Count(label))
+                    .unwrap()
+            };
+            let size = pattern
+                .try_fold_in(&arenas, &mut Size)
+                .unwrap();
+            let unshared = |pattern: &Pattern, label| {
+                let mut unshared = Unshared(label, 0);
+                pattern
+                    .try_visit_in(&arenas, &mut unshared)
+                    .unwrap();
+                unshared.1
+            };
+            let cats = count(&pattern, "Cat") + count(&pattern, "Seq");
+            let silences = count(&pattern, "Silence") + count(&pattern, "Note");
+            // Shared patterns are left as they are.
+            let shared_seqs =
+                count(&pattern, "Seq") - unshared(&pattern, "Seq");
+            let shared_notes =
+                count(&pattern, "Note") - unshared(&pattern, "Note");
+
+            pattern
+                .try_visit_mut_in(&arenas, &mut Mute)
+                .unwrap();
+            assert_eq!(unshared(&pattern, "Seq"), 0);
+            assert_eq!(unshared(&pattern, "Note"), 0);
+            assert_eq!(count(&pattern, "Seq"), shared_seqs);
+            assert_eq!(count(&pattern, "Note"), shared_notes);
+            assert_eq!(count(&pattern, "Cat") + shared_seqs, cats);
+            assert_eq!(count(&pattern, "Silence") + shared_notes, silences);
+            assert_eq!(
+                pattern
+                    .try_fold_in(&arenas, &mut Size)
+                    .unwrap(),
+                size
+            );
+            let validation = pattern.validate_in(&arenas).unwrap();
+            assert!(validation.is_valid(), "Rewriting left {validation:?}");
+            Ok(())
+        })
+        .unwrap()
+}
+
+/// Records each hook it runs, skipping the patterns of a `Stack` and stopping
+/// at the first `Seq`.
+#[derive(Default)]
+struct Tracer(Vec<String>);
+
+impl Visitor for Tracer {
+    fn pre(&mut self, node: &dyn Any) -> ArenaResult<Flow> {
+        let label = label(node);
+        self.0.push(format!("pre {label}"));
+        Ok(match label.as_str() {
+            "Stack" => Flow::Skip,
+            "Seq" => Flow::Stop,
+            _ => Flow::Continue,
+        })
+    }
+
+    fn post(&mut self, node: &dyn Any) -> ArenaResult<()> {
+        self.0
+            .push(format!("post {}", label(node)));
+        Ok(())
+    }
+}
+
+#[test]
+fn visitors_can_skip_and_stop() {
+    let arena_tuple = pattern_arenas();
+    let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let (patterns, (chains, _)) = arenas;
+    let chain = |items: Vec<Pattern>| {
+        items
+            .into_iter()
+            .rev()
+            .fold(Chain::Nil, |tail, head| Chain::Cons {
+                head: patterns.alloc(head).unwrap(),
+                tail: chains.alloc(tail).unwrap(),
+            })
+    };
+    let note = |letter| Pattern::Note(NoteUnit::Letter(letter));
+    let pattern = Pattern::Cat(chain(vec![
+        Pattern::Stack(chain(vec![note(Letter::A)])),
+        note(Letter::B),
+        Pattern::Seq(chain(vec![note(Letter::C)])),
+        note(Letter::D),
+    ]));
+
+    let mut tracer = Tracer::default();
+    pattern
+        .try_visit_in(&arenas, &mut tracer)
+        .unwrap();
+    assert_eq!(
+        tracer.0,
+        [
+            "pre Cat",
+            "pre Stack",
+            "post Stack",
+            "pre Note(Letter(B))",
+            "post Note(Letter(B))",
+            "pre Seq",
+        ]
+    );
+    pattern.drop_in(&arenas);
+}
+
+#[test]
+fn rewrites_leave_interned_patterns_as_they_were_stored() {
+    let arena_tuple = pattern_arenas();
+    let arenas = ArenaTuple::to_dyn_arenas(&arena_tuple);
+    let (pattern_arena, (chain_arena, _)) = arenas;
+    let seq = |letters: &[Letter]| {
+        let mut chain = Chain::Nil;
+        for letter in letters.iter().rev() {
+            let note = Pattern::Note(NoteUnit::Letter(*letter));
+            let head = pattern_arena.alloc(note).unwrap();
+            let tail = chain_arena.alloc(chain).unwrap();
+            chain = Chain::Cons { head, tail };
+        }
+        Pattern::Seq(chain)
+    };
+    let mut interner = Interner::new();
+    let (shared, outcome) = interner
+        .intern_tree(seq(&[Letter::A, Letter::B]), &arenas)
+        .unwrap();
+    assert_eq!(outcome, Interned::Stored);
+    let tail = chain_arena.alloc(Chain::Nil).unwrap();
+    let head = pattern_arena.alloc(shared).unwrap();
+    let mut pattern = Pattern::Cat(Chain::Cons { head, tail });
+
+    pattern
+        .try_visit_mut_in(&arenas, &mut Mute)
+        .unwrap();
+    assert_eq!(
+        format!("{:?}", pattern.debug_in(&arenas)),
+        "Cat\n  Shared\n    Seq\n      Repeated(Note(Letter(A)))\n      \
+         Repeated(Note(Letter(B)))"
+    );
+    let (repeat, outcome) = interner
+        .intern_tree(seq(&[Letter::A, Letter::B]), &arenas)
+        .unwrap();
+    assert_eq!(outcome, Interned::Deduplicated);
+    repeat.drop_in(&arenas);
+    pattern.drop_in(&arenas);
+}